@@ -0,0 +1,39 @@
+//! Reads frames through a `ReadBuffer`, consuming each one once it is complete.
+//!
+//! Run with `cargo bench --bench read_buffer`.
+
+use std::{io::Cursor, time::Instant};
+
+use blitz_ws::buffer::ReadBuffer;
+use bytes::Buf;
+
+const TOTAL_BYTES: usize = 16 * 1024 * 1024;
+const ROUNDS: u32 = 10;
+
+fn main() {
+    for frame_len in [8, 64, 1024, 16 * 1024] {
+        let input: Vec<u8> = (0..TOTAL_BYTES).map(|i| i as u8).collect();
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            let mut source = Cursor::new(&input[..]);
+            let mut buf = ReadBuffer::<4096>::new();
+            let mut frames = 0;
+            let mut checksum = 0u8;
+
+            while buf.read_from(&mut source).unwrap() > 0 {
+                while buf.remaining() >= frame_len {
+                    checksum ^= buf.chunk()[frame_len - 1];
+                    buf.advance(frame_len);
+                    frames += 1;
+                }
+            }
+
+            assert_eq!(frames, TOTAL_BYTES / frame_len);
+            assert_eq!(checksum, 0);
+        }
+
+        let elapsed = start.elapsed() / ROUNDS;
+        println!("{TOTAL_BYTES} bytes in frames of {frame_len} bytes: {elapsed:?}");
+    }
+}
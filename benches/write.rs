@@ -0,0 +1,77 @@
+//! Sends unmasked binary messages through a server `WebSocket`, against formatting each frame
+//! into a buffer first.
+//!
+//! Run with `cargo bench --bench write`.
+
+use std::{
+    io::{IoSlice, Read, Result as IoResult, Write},
+    time::Instant,
+};
+
+use blitz_ws::{
+    protocol::{
+        frame::{
+            codec::{Data, OpCode},
+            Frame,
+        },
+        message::Message,
+        websocket::{OperationMode, WebSocket},
+    },
+    Bytes,
+};
+
+const TOTAL_BYTES: usize = 64 * 1024 * 1024;
+const ROUNDS: u32 = 10;
+
+/// Discards everything written, like a socket that is always ready.
+struct Discard;
+
+impl Read for Discard {
+    fn read(&mut self, _: &mut [u8]) -> IoResult<usize> {
+        Ok(0)
+    }
+}
+
+impl Write for Discard {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> IoResult<usize> {
+        Ok(bufs.iter().map(|buf| buf.len()).sum())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+fn main() {
+    for message_len in [1024, 64 * 1024, 1024 * 1024] {
+        let payload = Bytes::from(vec![0xAB; message_len]);
+        let messages = TOTAL_BYTES / message_len;
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            let mut socket = WebSocket::new(Discard, OperationMode::Server, None);
+            for _ in 0..messages {
+                socket.send(Message::Binary(payload.clone())).unwrap();
+            }
+        }
+        let sent = start.elapsed() / ROUNDS;
+
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            let mut buf = Vec::new();
+            for _ in 0..messages {
+                let frame = Frame::new_data(payload.clone(), OpCode::Data(Data::Binary), true);
+                buf.clear();
+                frame.format_to_buf(&mut buf).unwrap();
+                Discard.write_all(&buf).unwrap();
+            }
+        }
+        let copied = start.elapsed() / ROUNDS;
+
+        println!("{TOTAL_BYTES} bytes in messages of {message_len} bytes: {sent:?} sent, {copied:?} copied");
+    }
+}
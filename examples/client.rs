@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use blitz_ws::{connect, protocol::message::Message};
 
 fn main() {
@@ -10,9 +12,10 @@ fn main() {
         println!("* {header}");
     }
 
-    socket.write(Message::Text("Hello!".into())).unwrap();
-    loop {
-        let msg = socket.read().expect("Error reading message.");
-        println!("Received: {msg}");
-    }
+    socket.send(Message::Text("Hello!".into())).unwrap();
+
+    let msg = socket.read().expect("Error reading message.");
+    println!("Received: {msg}");
+
+    socket.close_gracefully(None, Some(Duration::from_secs(5))).expect("Error closing connection.");
 }
@@ -1,3 +1,5 @@
+#![allow(clippy::result_large_err)]
+
 use std::{net::TcpListener, thread::spawn};
 
 use blitz_ws::{
@@ -0,0 +1,47 @@
+//! Driving a WebSocket from an event loop, e.g. one built on `mio`.
+//!
+//! The stream here is scripted to return `WouldBlock` in the middle of a frame, as a
+//! non-blocking socket does when the rest of the frame has not arrived yet. With a real event
+//! loop, `try_read` is called whenever the socket is readable and `try_flush` whenever it is
+//! writable, until they report that the socket would block again.
+
+#![allow(clippy::result_large_err)]
+
+use std::io::Cursor;
+
+use blitz_ws::{
+    protocol::{
+        message::Message,
+        websocket::{OperationMode, WebSocket},
+    },
+    test_util::ThrottledStream,
+};
+
+fn main() {
+    // An unmasked "Hello!" text frame, as sent by a server.
+    let frame = [&[0x81, 6][..], b"Hello!"].concat();
+
+    let stream = ThrottledStream::builder()
+        .read_limit(4)
+        .read_would_block()
+        .write_would_block()
+        .build(Cursor::new(frame));
+    let mut socket = WebSocket::new(stream, OperationMode::Client, None);
+
+    // Only the header and two bytes of the payload are readable so far.
+    assert_eq!(socket.try_read().unwrap(), None);
+    println!("Would block in the middle of a frame");
+
+    // The socket became readable again.
+    let msg = socket.try_read().unwrap();
+    assert_eq!(msg, Some(Message::Text("Hello!".into())));
+    println!("Received: {}", msg.unwrap());
+
+    socket.write(Message::Text("Hi!".into())).unwrap();
+    assert!(!socket.try_flush().unwrap());
+    println!("Would block while flushing");
+
+    // The socket became writable again.
+    assert!(socket.try_flush().unwrap());
+    println!("Flushed");
+}
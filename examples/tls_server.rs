@@ -1,3 +1,5 @@
+#![allow(clippy::result_large_err)]
+
 use std::{net::TcpListener, sync::Arc, thread::spawn};
 
 use blitz_ws::{
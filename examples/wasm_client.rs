@@ -0,0 +1,39 @@
+//! A browser client for the `echo_server` example.
+//!
+//! Build it with `cargo build --example wasm_client --features wasm --target wasm32-unknown-unknown`
+//! (see the README for the `getrandom` flag), generate the JavaScript bindings with
+//! `wasm-bindgen --target web` and call the exported `poll` function from a timer in the page.
+
+use std::cell::RefCell;
+
+use blitz_ws::{protocol::message::Message, wasm::BrowserWebSocket};
+use wasm_bindgen::prelude::wasm_bindgen;
+
+thread_local! {
+    static SOCKET: RefCell<Option<BrowserWebSocket>> = const { RefCell::new(None) };
+}
+
+fn main() {
+    let mut socket = BrowserWebSocket::connect("ws://localhost:8080/socket").unwrap();
+
+    // Queued until the connection is open.
+    socket.send(Message::Text("Hello from the browser!".into())).unwrap();
+
+    SOCKET.with(|cell| *cell.borrow_mut() = Some(socket));
+}
+
+/// Return the messages received since the last call.
+#[wasm_bindgen]
+pub fn poll() -> Vec<String> {
+    SOCKET.with(|cell| {
+        let mut received = Vec::new();
+
+        if let Some(socket) = cell.borrow_mut().as_mut() {
+            while let Ok(Some(msg)) = socket.read() {
+                received.push(msg.to_string());
+            }
+        }
+
+        received
+    })
+}
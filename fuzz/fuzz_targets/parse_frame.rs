@@ -0,0 +1,15 @@
+#![no_main]
+
+use blitz_ws::protocol::frame::Frame;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(Some((frame, consumed))) = Frame::parse(data) {
+        assert!(consumed <= data.len());
+
+        // A parsed frame formats into a frame that parses back to the same one.
+        let mut formatted = Vec::new();
+        frame.clone().format_to_buf(&mut formatted).unwrap();
+        assert_eq!(Frame::parse(&formatted).unwrap(), Some((frame, formatted.len())));
+    }
+});
@@ -0,0 +1,412 @@
+//! Asynchronous WebSocket on top of tokio.
+//!
+//! Only available with the `tokio` feature. [`AsyncWebSocket`] drives the same
+//! [`WebSocket`] state machine as the blocking API: the underlying `AsyncRead + AsyncWrite`
+//! stream is presented to it as a non-blocking `Read + Write` stream, and `WouldBlock` is
+//! turned into `Poll::Pending`.
+
+use std::{
+    future::Future,
+    io::{self, Read, Write},
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll, Wake, Waker},
+};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+#[cfg(feature = "handshake")]
+use http::HeaderMap;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+#[cfg(feature = "handshake")]
+use crate::{
+    client::{client_with_config, IntoClientRequest},
+    handshake::{
+        client::Response,
+        core::{HandshakeError, HandshakeRole, MidHandshake},
+        server::{Callback, NoCallback},
+    },
+    server::accept_header_with_config,
+};
+use crate::{
+    error::{Error, Result},
+    protocol::{
+        config::WebSocketConfig,
+        frame::CloseFrame,
+        message::Message,
+        websocket::{OperationMode, WebSocket},
+    },
+};
+
+/// A WebSocket over an asynchronous tokio stream.
+///
+/// Created with [`AsyncWebSocket::new`] from an upgraded stream or with the `*_async`
+/// handshake functions of this module.
+#[derive(Debug)]
+pub struct AsyncWebSocket<T> {
+    inner: WebSocket<AllowStd<T>>,
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> AsyncWebSocket<T> {
+    /// Convert a raw stream into a WebSocket without performing a handshake.
+    ///
+    /// # Panics
+    /// Panics if config is invalid e.g. `max_write_buffer_size <= write_buffer_size`.
+    pub fn new(stream: T, mode: OperationMode, config: Option<WebSocketConfig>) -> Self {
+        Self { inner: WebSocket::new(AllowStd::new(stream), mode, config) }
+    }
+
+    /// Returns a shared reference to the stream
+    pub fn get_ref(&self) -> &T {
+        &self.inner.get_ref().inner
+    }
+
+    /// Returns a mutable reference to the stream
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner.get_mut().inner
+    }
+
+    /// Returns the inner instance of the stream
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner().inner
+    }
+
+    /// Read the configuration.
+    pub fn get_config(&self) -> &WebSocketConfig {
+        self.inner.get_config()
+    }
+
+    /// See [`WebSocket::request_headers`].
+    #[cfg(feature = "handshake")]
+    pub fn request_headers(&self) -> Option<&HeaderMap> {
+        self.inner.request_headers()
+    }
+
+    /// Read a message.
+    ///
+    /// Like [`WebSocket::read`], this drives automatic pong and close replies.
+    pub async fn read(&mut self) -> Result<Message> {
+        poll_fn(|cx| self.poll_with(Task::Read, cx, |ws| ws.read())).await
+    }
+
+    /// Write a message and flush it.
+    ///
+    /// If the write buffer is full, it is flushed and the message written again. A message that
+    /// does not fit in the empty buffer fails with [`Error::WriteBufferFull`], as with
+    /// [`WebSocket::write`].
+    pub async fn send(&mut self, msg: Message) -> Result<()> {
+        let mut msg = Some(msg);
+        let mut flushed = false;
+
+        poll_fn(|cx| {
+            self.inner.get_mut().set_waker(Task::Write, cx);
+
+            while let Some(m) = msg.take() {
+                match self.inner.write(m.clone()) {
+                    // The frame is buffered, only writing it out is pending.
+                    Err(Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    // The message does not fit even in an empty buffer, flushing won't help.
+                    Err(Error::WriteBufferFull) if flushed => {
+                        return Poll::Ready(Err(Error::WriteBufferFull))
+                    }
+                    Err(Error::WriteBufferFull) => match self.inner.flush() {
+                        Ok(()) => {
+                            flushed = true;
+                            msg = Some(m);
+                        }
+                        Err(Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {
+                            msg = Some(m);
+                            return Poll::Pending;
+                        }
+                        Err(e) => return Poll::Ready(Err(e)),
+                    },
+                    Err(e) => return Poll::Ready(Err(e)),
+                    Ok(()) => {}
+                }
+            }
+
+            pending_on_would_block(self.inner.flush())
+        })
+        .await
+    }
+
+    /// Flush writes, including queued automatic replies.
+    pub async fn flush(&mut self) -> Result<()> {
+        poll_fn(|cx| self.poll_with(Task::Write, cx, |ws| ws.flush())).await
+    }
+
+    /// Close the connection and flush the close frame.
+    ///
+    /// As with [`WebSocket::close`], keep calling [`read`](Self::read) afterwards to drive the
+    /// close handshake to completion.
+    pub async fn close(&mut self, code: Option<CloseFrame>) -> Result<()> {
+        let mut code = Some(code);
+
+        poll_fn(|cx| {
+            self.inner.get_mut().set_waker(Task::Write, cx);
+
+            match code.take() {
+                // The close frame is queued even if writing it out would block.
+                Some(code) => pending_on_would_block(self.inner.close(code)),
+                None => pending_on_would_block(self.inner.flush()),
+            }
+        })
+        .await
+    }
+
+    fn poll_with<R>(
+        &mut self,
+        task: Task,
+        cx: &mut Context<'_>,
+        f: impl FnOnce(&mut WebSocket<AllowStd<T>>) -> Result<R>,
+    ) -> Poll<Result<R>> {
+        self.inner.get_mut().set_waker(task, cx);
+        pending_on_would_block(f(&mut self.inner))
+    }
+}
+
+/// Yields received messages, driving automatic replies, until the connection is closed.
+impl<T: AsyncRead + AsyncWrite + Unpin> Stream for AsyncWebSocket<T> {
+    type Item = Result<Message>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut().poll_with(Task::Read, cx, |ws| ws.read()) {
+            Poll::Ready(Err(Error::ConnectionClosed | Error::AlreadyClosed)) => Poll::Ready(None),
+            other => other.map(Some),
+        }
+    }
+}
+
+/// Sends messages, flushing pending writes before accepting a new one.
+/// Closing the sink starts the close handshake.
+impl<T: AsyncRead + AsyncWrite + Unpin> Sink<Message> for AsyncWebSocket<T> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_with(Task::Write, cx, |ws| ws.flush())
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Message) -> Result<()> {
+        match self.get_mut().inner.write(item) {
+            // The frame is buffered, it is written out by the next flush.
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            other => other,
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_with(Task::Write, cx, |ws| ws.flush())
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        match self.get_mut().poll_with(Task::Write, cx, |ws| ws.close(None)) {
+            Poll::Ready(Err(Error::ConnectionClosed | Error::AlreadyClosed)) => Poll::Ready(Ok(())),
+            other => other,
+        }
+    }
+}
+
+fn pending_on_would_block<R>(result: Result<R>) -> Poll<Result<R>> {
+    match result {
+        Err(Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => Poll::Pending,
+        other => Poll::Ready(other),
+    }
+}
+
+/// Future polling a closure, `std::future::poll_fn` is not available on the MSRV.
+struct PollFn<F>(F);
+
+impl<T, F: FnMut(&mut Context<'_>) -> Poll<T> + Unpin> Future for PollFn<F> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        (self.get_mut().0)(cx)
+    }
+}
+
+fn poll_fn<T, F: FnMut(&mut Context<'_>) -> Poll<T> + Unpin>(f: F) -> PollFn<F> {
+    PollFn(f)
+}
+
+/// Accept the given stream as a WebSocket.
+///
+/// The asynchronous counterpart of [`accept`](crate::accept).
+#[cfg(feature = "handshake")]
+pub async fn accept_async<S>(stream: S) -> Result<AsyncWebSocket<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    accept_header_async_with_config(stream, NoCallback, None).await
+}
+
+/// Accept the given stream as a WebSocket, using the provided configuration and header callback.
+///
+/// The asynchronous counterpart of [`accept_header_with_config`].
+#[cfg(feature = "handshake")]
+pub async fn accept_header_async_with_config<S, C>(
+    stream: S,
+    callback: C,
+    config: Option<WebSocketConfig>,
+) -> Result<AsyncWebSocket<S>>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    C: Callback,
+{
+    handshake(stream, move |stream| accept_header_with_config(stream, callback, config))
+        .await
+        .map(|inner| AsyncWebSocket { inner })
+}
+
+/// Do the client handshake over the given stream.
+///
+/// The asynchronous counterpart of [`client`](crate::client()).
+#[cfg(feature = "handshake")]
+pub async fn client_async<S, R>(request: R, stream: S) -> Result<(AsyncWebSocket<S>, Response)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    R: IntoClientRequest,
+{
+    client_async_with_config(request, stream, None).await
+}
+
+/// Do the client handshake over the given stream, using the provided configuration.
+///
+/// The asynchronous counterpart of [`client_with_config`].
+#[cfg(feature = "handshake")]
+pub async fn client_async_with_config<S, R>(
+    request: R,
+    stream: S,
+    config: Option<WebSocketConfig>,
+) -> Result<(AsyncWebSocket<S>, Response)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    R: IntoClientRequest,
+{
+    handshake(stream, move |stream| client_with_config(request, stream, config))
+        .await
+        .map(|(inner, res)| (AsyncWebSocket { inner }, res))
+}
+
+/// Run a handshake, resuming it from the interrupted state whenever the stream is ready.
+#[cfg(feature = "handshake")]
+async fn handshake<S, Role, F>(stream: S, start: F) -> Result<Role::FinalResult>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    Role: HandshakeRole<InternalStream = AllowStd<S>>,
+    F: FnOnce(AllowStd<S>) -> Result<Role::FinalResult, HandshakeError<Role>>,
+{
+    let mut start = Some((start, AllowStd::new(stream)));
+    let mut mid: Option<MidHandshake<Role>> = None;
+
+    poll_fn(|cx| {
+        let result = match (start.take(), mid.take()) {
+            (Some((start, mut stream)), _) => {
+                stream.set_waker(Task::Read, cx);
+                start(stream)
+            }
+            (None, Some(mut m)) => {
+                m.get_mut().get_mut().set_waker(Task::Read, cx);
+                m.handshake()
+            }
+            (None, None) => unreachable!("Bug: handshake polled after completion"),
+        };
+
+        match result {
+            Ok(res) => Poll::Ready(Ok(res)),
+            Err(HandshakeError::Interrupted(m)) => {
+                mid = Some(m);
+                Poll::Pending
+            }
+            Err(HandshakeError::Failure(e)) => Poll::Ready(Err(e)),
+        }
+    })
+    .await
+}
+
+/// The kind of operation a task is polling the WebSocket for.
+#[derive(Debug, Clone, Copy)]
+enum Task {
+    Read,
+    Write,
+}
+
+/// Wakers of the tasks currently reading and writing, which differ once the WebSocket is split.
+///
+/// Any readiness of the stream wakes both, as reading may need to write (automatic replies)
+/// and vice versa.
+#[derive(Debug, Default)]
+struct TaskWakers {
+    read: Mutex<Option<Waker>>,
+    write: Mutex<Option<Waker>>,
+}
+
+impl Wake for TaskWakers {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        for slot in [&self.read, &self.write] {
+            if let Some(waker) = slot.lock().unwrap_or_else(PoisonError::into_inner).take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// Adapter presenting an asynchronous stream as a non-blocking `Read + Write` stream.
+///
+/// Operations poll the stream with a waker notifying the tasks currently polling the WebSocket
+/// and return `WouldBlock` when the stream is not ready.
+#[derive(Debug)]
+struct AllowStd<S> {
+    inner: S,
+    wakers: Arc<TaskWakers>,
+    waker: Waker,
+}
+
+impl<S> AllowStd<S> {
+    fn new(inner: S) -> Self {
+        let wakers = Arc::new(TaskWakers::default());
+        Self { inner, waker: Waker::from(wakers.clone()), wakers }
+    }
+
+    fn set_waker(&mut self, task: Task, cx: &Context<'_>) {
+        let slot = match task {
+            Task::Read => &self.wakers.read,
+            Task::Write => &self.wakers.write,
+        };
+        *slot.lock().unwrap_or_else(PoisonError::into_inner) = Some(cx.waker().clone());
+    }
+}
+
+impl<S: Unpin> AllowStd<S> {
+    fn poll_io<R>(
+        &mut self,
+        f: impl FnOnce(Pin<&mut S>, &mut Context<'_>) -> Poll<io::Result<R>>,
+    ) -> io::Result<R> {
+        match f(Pin::new(&mut self.inner), &mut Context::from_waker(&self.waker)) {
+            Poll::Ready(result) => result,
+            Poll::Pending => Err(io::ErrorKind::WouldBlock.into()),
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> Read for AllowStd<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut buf = ReadBuf::new(buf);
+        self.poll_io(|stream, cx| stream.poll_read(cx, &mut buf))?;
+        Ok(buf.filled().len())
+    }
+}
+
+impl<S: AsyncWrite + Unpin> Write for AllowStd<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.poll_io(|stream, cx| stream.poll_write(cx, buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.poll_io(|stream, cx| stream.poll_flush(cx))
+    }
+}
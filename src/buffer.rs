@@ -8,6 +8,35 @@ use bytes::Buf;
 use std::io::{Cursor, Read, Result as IoResult};
 
 /// A FIFO buffer for reading packets from the network.
+///
+/// Bytes are appended at the end of the storage and consumed through the cursor position.
+/// Consumed bytes are only reclaimed when the storage would otherwise have to grow, so reading
+/// does not move the unconsumed bytes every time.
+///
+/// # Example
+/// ```
+/// # use std::io::Cursor;
+/// # use blitz_ws::buffer::ReadBuffer;
+/// use bytes::Buf;
+///
+/// let mut buf = ReadBuffer::<4>::with_capacity(8);
+/// let mut source = Cursor::new(b"Hello, world!".to_vec());
+///
+/// assert_eq!(buf.read_from(&mut source).unwrap(), 4);
+/// assert_eq!(buf.read_from(&mut source).unwrap(), 4);
+/// assert_eq!((buf.remaining(), buf.chunk()), (8, &b"Hello, w"[..]));
+///
+/// buf.advance(7);
+/// assert_eq!(buf.chunk(), b"w");
+///
+/// // Reclaims the consumed bytes instead of growing the storage.
+/// assert_eq!(buf.read_from(&mut source).unwrap(), 4);
+/// assert_eq!((buf.remaining(), buf.chunk()), (5, &b"world"[..]));
+/// assert_eq!(buf.as_cursor().get_ref().capacity(), 8);
+///
+/// buf.advance(10);
+/// assert_eq!(buf.remaining(), 0);
+/// ```
 #[derive(Debug)]
 pub struct ReadBuffer<const CHUNK_SIZE: usize> {
     storage: Cursor<Vec<u8>>,
@@ -30,9 +59,12 @@ impl<const CHUNK_SIZE: usize> ReadBuffer<CHUNK_SIZE> {
 
     /// Reads the next portion of the data from the given input stream
     pub fn read_from<S: Read>(&mut self, source: &mut S) -> IoResult<usize> {
-        self.clean();
-
         let read_size = source.read(&mut *self.chunk)?;
+
+        let storage = self.storage.get_ref();
+        if !self.has_remaining() || storage.len() + read_size > storage.capacity() {
+            self.clean();
+        }
         self.storage.get_mut().extend_from_slice(&self.chunk[..read_size]);
 
         Ok(read_size)
@@ -41,8 +73,10 @@ impl<const CHUNK_SIZE: usize> ReadBuffer<CHUNK_SIZE> {
     /// Cleans up the parts of the vector that has already been ready by the cursor
     fn clean(&mut self) {
         let pos = self.storage.position() as usize;
-        self.storage.get_mut().drain(..pos);
-        self.storage.set_position(0);
+        if pos > 0 {
+            self.storage.get_mut().drain(..pos);
+            self.storage.set_position(0);
+        }
     }
 
     /// Consumes the `ReadBuffer` and gets the internal data storage
@@ -5,7 +5,7 @@ use std::{io, str::Utf8Error, string::FromUtf8Error};
 use http::{HeaderName, Response};
 use thiserror::Error;
 
-use crate::protocol::frame::codec::Data;
+use crate::protocol::frame::codec::{CloseCode, Data};
 
 /// Generic result type
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -86,6 +86,42 @@ pub enum Error {
     AttackAttempt,
 }
 
+#[cfg(feature = "handshake")]
+impl Error {
+    /// Returns the status of the HTTP response if this is an [`Error::Http`], e.g. a
+    /// `401 Unauthorized` from a server rejecting the handshake.
+    ///
+    /// # Example
+    /// ```
+    /// # use blitz_ws::{error::Error, http::{Response, StatusCode}};
+    /// let res = Response::builder()
+    ///     .status(StatusCode::UNAUTHORIZED)
+    ///     .body(Some(br#"{"error":"invalid token"}"#.to_vec()))
+    ///     .unwrap();
+    /// let err = Error::Http(res);
+    ///
+    /// assert_eq!(err.http_status(), Some(StatusCode::UNAUTHORIZED));
+    /// assert_eq!(err.http_body(), Some(&br#"{"error":"invalid token"}"#[..]));
+    /// assert_eq!(Error::ConnectionClosed.http_status(), None);
+    /// ```
+    pub fn http_status(&self) -> Option<http::StatusCode> {
+        match self {
+            Error::Http(res) => Some(res.status()),
+            _ => None,
+        }
+    }
+
+    /// Returns the body of the HTTP response if this is an [`Error::Http`] with a body.
+    ///
+    /// See [`http_status`](Self::http_status).
+    pub fn http_body(&self) -> Option<&[u8]> {
+        match self {
+            Error::Http(res) => res.body().as_deref(),
+            _ => None,
+        }
+    }
+}
+
 impl From<Utf8Error> for Error {
     fn from(value: Utf8Error) -> Self {
         Error::Utf8(value.to_string())
@@ -240,10 +276,24 @@ pub enum ProtocolError {
     #[error("Invalid close frame payload")]
     InvalidCloseFrame,
 
+    /// A close code which is not allowed on the wire was sent or received.
+    #[error("Close code {0} must not be sent")]
+    InvalidCloseCode(CloseCode),
+
     /// Connection closed without performing the closing handshake.
     #[error("Connection closed without proper handshake")]
     ResetWithoutClosing,
 
+    /// Connection closed in the middle of a frame, before all of its header and payload were
+    /// received.
+    #[error("Connection closed in the middle of a frame")]
+    TruncatedFrame,
+
+    /// The peer sent more messages or bytes than allowed for the whole connection, see
+    /// `WebSocketConfig::max_messages` and `WebSocketConfig::max_total_bytes`.
+    #[error("Connection limit exceeded")]
+    ConnectionLimitExceeded,
+
     /// Garbage data encountered after client request.
     #[error("Junk after client request")]
     JunkAfterRequest,
@@ -251,6 +301,11 @@ pub enum ProtocolError {
     /// Custom responses must be unsuccessful.
     #[error("Custom response must not be successful")]
     CustomResponseSuccessful,
+
+    /// The payload of a frame could not be decoded by the negotiated extension, e.g. invalid
+    /// `permessage-deflate` data.
+    #[error("Invalid payload for the negotiated extension")]
+    InvalidExtensionPayload,
 }
 
 /// Indicates the specific type/cause of a subprotocol header error.
@@ -307,11 +362,16 @@ pub enum UrlError {
     #[error("TLS feature not enabled but 'wss://' URL used")]
     TlsFeatureNotEnabled,
 
+    /// `Connector::Plain` was passed explicitly for a `wss://` URL.
+    #[error("Plain connector forced but 'wss://' URL used")]
+    PlainConnectorForTlsUrl,
+
     /// The URL does not include a path/query.
     #[error("No path / query segment in URL")]
     NoPathOrQuery,
 
-    /// Failed to connect with this URL.
+    /// Failed to connect with this URL. Every address the host resolved to was tried, the
+    /// reason each of them failed is listed along with the URL.
     #[error("Unable to connect to host: {0}")]
     UnableToConnect(String),
 }
@@ -320,6 +380,25 @@ pub enum UrlError {
 ///
 /// Note that even if you enable only the rustls-based TLS support, the error at runtime could still
 /// be `Native`, as another crate in the dependency graph may enable native TLS support.
+///
+/// The underlying TLS error is the [`source`](std::error::Error::source) of a `TlsError`, which
+/// is in turn the source of [`Error::Tls`], so error reporters print the whole chain.
+///
+/// # Example
+/// ```
+/// # #[cfg(feature = "rustls")]
+/// # {
+/// use std::error::Error as _;
+/// # use blitz_ws::error::{Error, TlsError};
+///
+/// let error = Error::from(TlsError::Rustls(rustls::Error::DecryptError));
+///
+/// let tls = error.source().unwrap();
+/// assert!(tls.is::<TlsError>());
+/// let rustls = tls.source().unwrap().downcast_ref::<rustls::Error>();
+/// assert_eq!(rustls, Some(&rustls::Error::DecryptError));
+/// # }
+/// ```
 #[allow(missing_copy_implementations)]
 #[derive(Error, Debug)]
 #[non_exhaustive]
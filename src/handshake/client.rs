@@ -10,17 +10,21 @@ use http::{
     HeaderMap, HeaderName, Method, Request as HttpRequest, Response as HttpResponse, StatusCode,
     Version,
 };
-use httparse::{Status, EMPTY_HEADER};
+use httparse::Status;
 
 use crate::{
     error::{Error, ProtocolError, Result, SubProtocolError, UrlError},
     handshake::{
-        core::{derive_accept_key, HandshakeRole, MidHandshake, ProcessingResult},
-        headers::{FromHttparse, MAX_HEADERS},
+        core::{
+            derive_accept_key, verify_accept_key, HandshakeRole, MidHandshake, ProcessingResult,
+        },
+        headers::{parse_extensions, with_header_buffer, FromHttparse, MAX_HEADERS},
         machine::{HandshakeMachine, StageResult, TryParse},
     },
     protocol::{
+        compression::NegotiatedCompression,
         config::WebSocketConfig,
+        extension::WebSocketExtension,
         websocket::{OperationMode, WebSocket},
     },
 };
@@ -30,11 +34,55 @@ pub type Request = HttpRequest<()>;
 /// Client Response Type
 pub type Response = HttpResponse<Option<Vec<u8>>>;
 
+/// The details negotiated with the server during a successful client handshake.
+///
+/// It is attached to the handshake [`Response`] and can be retrieved without re-parsing the
+/// headers with [`HandshakeOutcome::from_response`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HandshakeOutcome {
+    /// The subprotocol selected by the server, if any.
+    pub subprotocol: Option<String>,
+    /// The names of the extensions accepted by the server, in order.
+    pub extensions: Vec<String>,
+    /// The `permessage-deflate` parameters, if the server accepted compression.
+    pub compression: Option<NegotiatedCompression>,
+}
+
+impl HandshakeOutcome {
+    /// Returns the outcome attached to a response of a completed client handshake.
+    pub fn from_response(res: &Response) -> Option<&Self> {
+        res.extensions().get::<Self>()
+    }
+
+    fn parse(res: &Response) -> Result<Self> {
+        let headers = res.headers();
+
+        let subprotocol = match headers.get("Sec-WebSocket-Protocol") {
+            Some(protocol) => Some(protocol.to_str()?.trim().to_string()),
+            None => None,
+        };
+
+        let mut outcome = HandshakeOutcome { subprotocol, ..Default::default() };
+        for value in headers.get_all("Sec-WebSocket-Extensions") {
+            for (name, params) in parse_extensions(value.to_str()?) {
+                if name == NegotiatedCompression::EXTENSION_NAME {
+                    outcome.compression = Some(NegotiatedCompression::from_params(&params));
+                }
+
+                outcome.extensions.push(name);
+            }
+        }
+
+        Ok(outcome)
+    }
+}
+
 /// Client handshake
 #[derive(Debug)]
 pub struct ClientHandshake<S> {
     verify_data: VerifyData,
     config: Option<WebSocketConfig>,
+    extension: Option<Box<dyn WebSocketExtension>>,
     _marker: PhantomData<S>,
 }
 
@@ -44,6 +92,30 @@ impl<S: Read + Write> ClientHandshake<S> {
         stream: S,
         req: Request,
         config: Option<WebSocketConfig>,
+    ) -> Result<MidHandshake<Self>> {
+        Self::start_with(stream, req, config, None)
+    }
+
+    /// Initiate a client handshake offering an extension to the server.
+    ///
+    /// The offer is added to the `Sec-WebSocket-Extensions` headers of the request. If the
+    /// server accepts it, the extension is configured with
+    /// [`WebSocketExtension::accept_response`] and installed on the connection, otherwise it is
+    /// dropped. See [`WebSocketExtension`] for an example.
+    pub fn start_with_extension(
+        stream: S,
+        req: Request,
+        config: Option<WebSocketConfig>,
+        extension: impl WebSocketExtension + 'static,
+    ) -> Result<MidHandshake<Self>> {
+        Self::start_with(stream, req, config, Some(Box::new(extension)))
+    }
+
+    fn start_with(
+        stream: S,
+        mut req: Request,
+        config: Option<WebSocketConfig>,
+        extension: Option<Box<dyn WebSocketExtension>>,
     ) -> Result<MidHandshake<Self>> {
         if req.method() != Method::GET {
             return Err(Error::Protocol(ProtocolError::InvalidHttpMethod));
@@ -54,6 +126,15 @@ impl<S: Read + Write> ClientHandshake<S> {
 
         let subprotocols = extract_subprotocols(&req)?;
 
+        let user_agent = config.and_then(|c| c.user_agent);
+        if let Some(user_agent) = user_agent.filter(|_| !req.headers().contains_key("User-Agent")) {
+            req.headers_mut().insert("User-Agent", user_agent.parse()?);
+        }
+
+        if let Some(extension) = &extension {
+            req.headers_mut().append("Sec-WebSocket-Extensions", extension.offer().parse()?);
+        }
+
         let (request, key) = generate_request(req)?;
 
         let machine = HandshakeMachine::start_write(stream, request);
@@ -63,6 +144,7 @@ impl<S: Read + Write> ClientHandshake<S> {
             ClientHandshake {
                 verify_data: VerifyData { accept_key, subprotocols },
                 config,
+                extension,
                 _marker: PhantomData,
             }
         };
@@ -72,6 +154,7 @@ impl<S: Read + Write> ClientHandshake<S> {
 }
 
 impl<S: Read + Write> HandshakeRole for ClientHandshake<S> {
+    const NAME: &'static str = "client";
     type IncomingData = Response;
     type InternalStream = S;
     type FinalResult = (WebSocket<S>, Response);
@@ -81,11 +164,11 @@ impl<S: Read + Write> HandshakeRole for ClientHandshake<S> {
         finish: StageResult<Self::IncomingData, Self::InternalStream>,
     ) -> Result<ProcessingResult<Self::InternalStream, Self::FinalResult>> {
         Ok(match finish {
-            StageResult::DoneWriting(stream) => {
-                ProcessingResult::Continue(HandshakeMachine::start_read(stream))
-            }
+            StageResult::DoneWriting(stream) => ProcessingResult::Continue(
+                HandshakeMachine::start_read(stream).with_config(self.config.as_ref()),
+            ),
             StageResult::DoneReading { result, stream, tail } => {
-                let res = match self.verify_data.verify_response(result) {
+                let mut res = match self.verify_data.verify_response(result) {
                     Ok(r) => r,
                     Err(Error::Http(mut e)) => {
                         *e.body_mut() = Some(tail);
@@ -94,18 +177,48 @@ impl<S: Read + Write> HandshakeRole for ClientHandshake<S> {
                     Err(e) => return Err(e),
                 };
 
-                let websocket = WebSocket::from_partially_read(
+                let outcome = HandshakeOutcome::parse(&res)?;
+                res.extensions_mut().insert(outcome);
+
+                let extension = self.accepted_extension(&res)?;
+
+                let mut websocket = WebSocket::from_partially_read(
                     stream,
                     tail,
                     OperationMode::Client,
                     self.config,
                 );
+                websocket.set_extension(extension);
                 ProcessingResult::Done((websocket, res))
             }
         })
     }
 }
 
+impl<S> ClientHandshake<S> {
+    /// Takes the offered extension if the server accepted it, configured with its parameters.
+    fn accepted_extension(
+        &mut self,
+        res: &Response,
+    ) -> Result<Option<Box<dyn WebSocketExtension>>> {
+        let mut extension = match self.extension.take() {
+            Some(extension) => extension,
+            None => return Ok(None),
+        };
+
+        for value in res.headers().get_all("Sec-WebSocket-Extensions") {
+            for (name, params) in parse_extensions(value.to_str()?) {
+                if name.eq_ignore_ascii_case(extension.name()) {
+                    extension.accept_response(&params)?;
+                    return Ok(Some(extension));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
 /// Verifies and generates a client WebSocket request from a raw request and extracts a WebSocket key from it
 pub fn generate_request(mut request: Request) -> Result<(Vec<u8>, String)> {
     let mut req = Vec::new();
@@ -163,9 +276,15 @@ pub fn generate_request(mut request: Request) -> Result<(Vec<u8>, String)> {
         if name == "sec-websocket-protocol" {
             name = "Sec-WebSocket-Protocol";
         }
+        if name == "sec-websocket-extensions" {
+            name = "Sec-WebSocket-Extensions";
+        }
         if name == "origin" {
             name = "Origin";
         }
+        if name == "user-agent" {
+            name = "User-Agent";
+        }
 
         writeln!(
             req,
@@ -222,28 +341,27 @@ impl VerifyData {
             return Err(Error::Protocol(ProtocolError::MissingUpgradeHeader));
         }
 
-        if !headers.get("Sec-WebSocket-Accept").map(|h| h == &self.accept_key).unwrap_or(false) {
+        if !headers
+            .get("Sec-WebSocket-Accept")
+            .map(|h| verify_accept_key(&self.accept_key, h.as_bytes()))
+            .unwrap_or(false)
+        {
             return Err(Error::Protocol(ProtocolError::AcceptKeyMismatch));
         }
 
-        if headers.get("Sec-WebSocket-Protocol").is_none() && self.subprotocols.is_some() {
-            return Err(Error::Protocol(ProtocolError::SecWebSocketSubProtocolError(
-                SubProtocolError::NoSubProtocol,
-            )));
-        }
-        if headers.get("Sec-Websocket-Protocol").is_some() && self.subprotocols.is_none() {
-            return Err(Error::Protocol(ProtocolError::SecWebSocketSubProtocolError(
-                SubProtocolError::ServerSentSubProtocolNoneRequested,
-            )));
-        }
-        if let Some(returned_subprotocol) = headers.get("Sec-WebSocket-Protocol") {
-            if let Some(accepted_subprotocols) = &self.subprotocols {
-                if !accepted_subprotocols.contains(&returned_subprotocol.to_str()?.to_string()) {
-                    return Err(Error::Protocol(ProtocolError::SecWebSocketSubProtocolError(
-                        SubProtocolError::InvalidSubProtocol,
-                    )));
-                }
+        // Subprotocol tokens are case-sensitive, surrounding whitespace is not part of them.
+        let returned_subprotocol =
+            headers.get("Sec-WebSocket-Protocol").map(|h| h.to_str()).transpose()?.map(str::trim);
+        let error = match (returned_subprotocol, &self.subprotocols) {
+            (None, Some(_)) => Some(SubProtocolError::NoSubProtocol),
+            (Some(_), None) => Some(SubProtocolError::ServerSentSubProtocolNoneRequested),
+            (Some(returned), Some(accepted)) if !accepted.iter().any(|p| p == returned) => {
+                Some(SubProtocolError::InvalidSubProtocol)
             }
+            _ => None,
+        };
+        if let Some(error) = error {
+            return Err(Error::Protocol(ProtocolError::SecWebSocketSubProtocolError(error)));
         }
 
         Ok(res)
@@ -252,12 +370,20 @@ impl VerifyData {
 
 impl TryParse for Response {
     fn try_parse(data: &[u8]) -> crate::error::Result<Option<(usize, Self)>> {
-        let mut hbuffer = [EMPTY_HEADER; MAX_HEADERS];
-        let mut req = httparse::Response::new(&mut hbuffer);
+        Self::try_parse_with_max_headers(data, MAX_HEADERS)
+    }
 
-        Ok(match req.parse(data)? {
-            Status::Partial => None,
-            Status::Complete(n) => Some((n, Response::from_httparse(req)?)),
+    fn try_parse_with_max_headers(
+        data: &[u8],
+        max_headers: usize,
+    ) -> crate::error::Result<Option<(usize, Self)>> {
+        with_header_buffer(max_headers, |hbuffer| {
+            let mut req = httparse::Response::new(hbuffer);
+
+            Ok(match req.parse(data)? {
+                Status::Partial => None,
+                Status::Complete(n) => Some((n, Response::from_httparse(req)?)),
+            })
         })
     }
 }
@@ -3,10 +3,12 @@
 use std::{
     fmt::{Debug, Display},
     io::{Read, Write},
+    time::Instant,
 };
 
 use base64::Engine;
 use sha1::{Digest, Sha1};
+use subtle::ConstantTimeEq;
 
 use crate::{
     error::{Error, Result},
@@ -33,9 +35,72 @@ impl<Role: HandshakeRole> MidHandshake<Role> {
         &mut self.machine
     }
 
+    /// Inspect the peer's headers as they arrive, see [`HandshakeMachine::with_header_callback`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::net::TcpListener;
+    /// # use blitz_ws::{error::Error, handshake::server::NoCallback, http, ServerHandshake};
+    /// # let (stream, _) = TcpListener::bind("127.0.0.1:9001").unwrap().accept().unwrap();
+    /// let ws = ServerHandshake::start(stream, NoCallback, None)
+    ///     .with_header_callback(|name, value| {
+    ///         if name.eq_ignore_ascii_case("Host") && value != b"example.com" {
+    ///             let forbidden = http::Response::builder().status(403).body(None).unwrap();
+    ///             return Err(Error::Http(forbidden));
+    ///         }
+    ///         Ok(())
+    ///     })
+    ///     .handshake();
+    /// ```
+    pub fn with_header_callback<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&str, &[u8]) -> Result<()> + Send + 'static,
+    {
+        self.machine = self.machine.with_header_callback(callback);
+        self
+    }
+
+    /// Fail the handshake with an [`std::io::ErrorKind::TimedOut`] error once `deadline`
+    /// passed, see [`HandshakeMachine::with_deadline`].
+    ///
+    /// # Example
+    /// ```
+    /// # use std::{io::{self, Read, Write}, thread, time::{Duration, Instant}};
+    /// # use blitz_ws::{error::Error, handshake::{server::NoCallback, HandshakeError}, ServerHandshake};
+    /// /// Sends one byte of the request every 10 ms.
+    /// struct Slow(&'static [u8]);
+    ///
+    /// impl Read for Slow {
+    ///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    ///         thread::sleep(Duration::from_millis(10));
+    ///         (&mut self.0).take(1).read(buf)
+    ///     }
+    /// }
+    /// # impl Write for Slow {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { Ok(buf.len()) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    ///
+    /// let request = b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\r\n";
+    /// let deadline = Instant::now() + Duration::from_millis(100);
+    ///
+    /// match ServerHandshake::start(Slow(request), NoCallback, None).with_deadline(deadline).handshake() {
+    ///     Err(HandshakeError::Failure(Error::Io(e))) => assert_eq!(e.kind(), io::ErrorKind::TimedOut),
+    ///     _ => panic!("expected a timeout"),
+    /// }
+    /// ```
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.machine = self.machine.with_deadline(Some(deadline));
+        self
+    }
+
     /// Restarts the handshake process
     pub fn handshake(mut self) -> Result<Role::FinalResult, HandshakeError<Role>> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("handshake", role = Role::NAME).entered();
+
         let mut machine = self.machine;
+        let deadline = machine.deadline();
 
         loop {
             machine = match machine.single_round()? {
@@ -44,8 +109,13 @@ impl<Role: HandshakeRole> MidHandshake<Role> {
                 }
                 RoundResult::Incomplete(m) => m,
                 RoundResult::StageFinished(s) => match self.role.stage_finished(s)? {
-                    ProcessingResult::Continue(m) => m,
-                    ProcessingResult::Done(res) => return Ok(res),
+                    ProcessingResult::Continue(m) => m.with_deadline(deadline),
+                    ProcessingResult::Done(res) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!("handshake complete");
+
+                        return Ok(res);
+                    }
                 },
             }
         }
@@ -88,6 +158,8 @@ impl<Role: HandshakeRole> From<Error> for HandshakeError<Role> {
 
 /// Handshake Role
 pub trait HandshakeRole {
+    #[doc(hidden)]
+    const NAME: &'static str;
     #[doc(hidden)]
     type IncomingData: TryParse;
     #[doc(hidden)]
@@ -122,3 +194,11 @@ pub fn derive_accept_key(req_key: &[u8]) -> String {
 
     base64::engine::general_purpose::STANDARD.encode(<Sha1 as Digest>::finalize(hasher))
 }
+
+/// Checks a received `Sec-WebSocket-Accept` header value against the expected one.
+///
+/// The comparison runs in constant time with respect to the contents of the keys, so it does
+/// not leak how many leading bytes matched.
+pub fn verify_accept_key(expected: &str, received: &[u8]) -> bool {
+    expected.as_bytes().ct_eq(received).into()
+}
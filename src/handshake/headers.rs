@@ -1,13 +1,32 @@
 //! HTTP Request and Respose header handlers
 
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use http::{HeaderMap, HeaderName, HeaderValue};
 use httparse::{parse_headers, Header, EMPTY_HEADER};
 
 use crate::{error::Result, handshake::machine::TryParse};
 
-/// Limit for the number of header lines
+/// Default limit for the number of header lines, see [`WebSocketConfig::max_headers`].
+///
+/// [`WebSocketConfig::max_headers`]: crate::protocol::config::WebSocketConfig::max_headers
 pub const MAX_HEADERS: usize = 124;
 
+/// Call `f` with a buffer for parsing up to `max_headers` headers.
+///
+/// The buffer lives on the stack unless more than [`MAX_HEADERS`] headers are allowed.
+pub(crate) fn with_header_buffer<'h, R>(
+    max_headers: usize,
+    f: impl FnOnce(&mut [Header<'h>]) -> R,
+) -> R {
+    if max_headers <= MAX_HEADERS {
+        let mut hbuffer = [EMPTY_HEADER; MAX_HEADERS];
+        f(&mut hbuffer[..max_headers])
+    } else {
+        f(&mut vec![EMPTY_HEADER; max_headers])
+    }
+}
+
 /// Trait to convert raw objects into HTTP parse-able objects
 pub(crate) trait FromHttparse<T>: Sized {
     /// Convert raw object into HTTP headers
@@ -29,13 +48,100 @@ impl<'b: 'h, 'h> FromHttparse<&'b [Header<'h>]> for HeaderMap {
     }
 }
 
+/// Extension parameters as `(key, value)` pairs
+pub type ExtensionParams = Vec<(String, Option<String>)>;
+
+/// Parse a `Sec-WebSocket-Extensions` header value into `(name, params)` pairs.
+///
+/// Parameters without a value (e.g. `client_no_context_takeover`) are returned with `None`.
+/// Quotes around parameter values are removed.
+///
+/// # Example
+/// ```
+/// # use blitz_ws::handshake::headers::parse_extensions;
+/// let extensions = parse_extensions("permessage-deflate; client_max_window_bits=10, x-custom");
+///
+/// assert_eq!(
+///     extensions,
+///     [
+///         (
+///             "permessage-deflate".to_string(),
+///             vec![("client_max_window_bits".to_string(), Some("10".to_string()))],
+///         ),
+///         ("x-custom".to_string(), vec![]),
+///     ]
+/// );
+/// ```
+pub fn parse_extensions(value: &str) -> Vec<(String, ExtensionParams)> {
+    value
+        .split(',')
+        .filter_map(|ext| {
+            let mut parts = ext.split(';').map(str::trim);
+            let name = parts.next().filter(|n| !n.is_empty())?.to_string();
+            let params = parts
+                .filter(|p| !p.is_empty())
+                .map(|p| match p.split_once('=') {
+                    Some((k, v)) => {
+                        (k.trim().to_string(), Some(v.trim().trim_matches('"').to_string()))
+                    }
+                    None => (p.to_string(), None),
+                })
+                .collect();
+
+            Some((name, params))
+        })
+        .collect()
+}
+
 impl TryParse for HeaderMap {
     fn try_parse(data: &[u8]) -> crate::error::Result<Option<(usize, Self)>> {
-        let mut hbuffer = [EMPTY_HEADER; MAX_HEADERS];
+        Self::try_parse_with_max_headers(data, MAX_HEADERS)
+    }
 
-        Ok(match parse_headers(data, &mut hbuffer)? {
-            httparse::Status::Partial => None,
-            httparse::Status::Complete((size, hdr)) => Some((size, HeaderMap::from_httparse(hdr)?)),
+    fn try_parse_with_max_headers(
+        data: &[u8],
+        max_headers: usize,
+    ) -> crate::error::Result<Option<(usize, Self)>> {
+        with_header_buffer(max_headers, |hbuffer| {
+            Ok(match parse_headers(data, hbuffer)? {
+                httparse::Status::Partial => None,
+                httparse::Status::Complete((size, hdr)) => {
+                    Some((size, HeaderMap::from_httparse(hdr)?))
+                }
+            })
         })
     }
 }
+
+/// Formats a time as an RFC 7231 `IMF-fixdate`, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub(crate) fn http_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] =
+        ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let days = secs / 86400;
+    let rem = secs % 86400;
+
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        WEEKDAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
@@ -1,11 +1,17 @@
 //! WebSocket handshake machine
 
-use std::io::{Cursor, Read, Write};
+use std::{
+    fmt::{Debug, Formatter},
+    io::{self, Cursor, Read, Write},
+    time::Instant,
+};
 
 use bytes::Buf;
 
 use crate::{
     error::{Error, ProtocolError, Result},
+    handshake::headers::MAX_HEADERS,
+    protocol::config::WebSocketConfig,
     util::NonBlockingResult,
     ReadBuffer,
 };
@@ -15,17 +21,79 @@ use crate::{
 pub struct HandshakeMachine<Stream> {
     stream: Stream,
     state: HandshakeState,
+    max_headers: usize,
+    deadline: Option<Instant>,
 }
 
 impl<Stream> HandshakeMachine<Stream> {
     /// Start reading data from the peer
     pub fn start_read(stream: Stream) -> Self {
-        Self { stream, state: HandshakeState::Reading(ReadBuffer::new(), AttackCheck::new()) }
+        let attack_check = AttackCheck::new(&WebSocketConfig::default());
+        Self {
+            stream,
+            state: HandshakeState::Reading(ReadBuffer::new(), attack_check, None),
+            max_headers: MAX_HEADERS,
+            deadline: None,
+        }
+    }
+
+    /// Use the handshake limits of `config` instead of the default ones.
+    ///
+    /// See [`WebSocketConfig::handshake_max_bytes`], [`WebSocketConfig::handshake_max_packets`],
+    /// [`WebSocketConfig::handshake_min_packet_size`] and [`WebSocketConfig::max_headers`].
+    ///
+    /// Has no effect if the machine is not in the reading stage.
+    pub fn with_config(mut self, config: Option<&WebSocketConfig>) -> Self {
+        if let (HandshakeState::Reading(_, ref mut attack_check, _), Some(config)) =
+            (&mut self.state, config)
+        {
+            *attack_check = AttackCheck::new(config);
+            self.max_headers = config.max_headers;
+        }
+        self
+    }
+
+    /// Inspect the headers of the incoming request or response as they arrive.
+    ///
+    /// While reading, `callback` is invoked with the name and value of every header as soon as
+    /// its line has been received, before the whole message is parsed. Returning an error aborts
+    /// the handshake with that error, so clearly unwanted peers can be dropped early (e.g. based
+    /// on `Host` or `Origin`). The request or status line is not passed to the callback and
+    /// malformed lines are left to the regular parser.
+    ///
+    /// Has no effect if the machine is not in the reading stage.
+    pub fn with_header_callback<F>(mut self, callback: F) -> Self
+    where
+        F: FnMut(&str, &[u8]) -> Result<()> + Send + 'static,
+    {
+        if let HandshakeState::Reading(_, _, ref mut scan) = self.state {
+            *scan = Some(HeaderScan { callback: Box::new(callback), offset: 0, done: false });
+        }
+        self
     }
 
     /// Start writing data to the peer
     pub fn start_write<D: Into<Vec<u8>>>(stream: Stream, data: D) -> Self {
-        HandshakeMachine { stream, state: HandshakeState::Writing(Cursor::new(data.into())) }
+        HandshakeMachine {
+            stream,
+            state: HandshakeState::Writing(Cursor::new(data.into())),
+            max_headers: MAX_HEADERS,
+            deadline: None,
+        }
+    }
+
+    /// Fail rounds started after `deadline` with an [`io::ErrorKind::TimedOut`] error.
+    ///
+    /// The deadline is checked before every round, a round blocked on the stream is not
+    /// interrupted. Set a read timeout on blocking streams as well to bound each round.
+    pub fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// The deadline set with [`with_deadline`](Self::with_deadline).
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
     }
 
     /// Returns a shared reference to the internal stream
@@ -42,14 +110,24 @@ impl<Stream> HandshakeMachine<Stream> {
 impl<Stream: Read + Write> HandshakeMachine<Stream> {
     /// Performs a single-round handshake
     pub fn single_round<Object: TryParse>(mut self) -> Result<RoundResult<Object, Stream>> {
+        if self.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            return Err(Error::Io(io::ErrorKind::TimedOut.into()));
+        }
+
         match self.state {
-            HandshakeState::Reading(mut buf, mut attack_check) => {
+            HandshakeState::Reading(mut buf, mut attack_check, mut scan) => {
                 let read = buf.read_from(&mut self.stream).no_block()?;
                 match read {
                     Some(0) => Err(Error::Protocol(ProtocolError::IncompleteHandshake)),
                     Some(count) => {
                         attack_check.check_incoming_packet(count)?;
-                        if let Some((size, obj)) = Object::try_parse(Buf::chunk(&buf))? {
+                        if let Some(scan) = scan.as_mut() {
+                            scan.scan(Buf::chunk(&buf))?;
+                        }
+
+                        let parsed =
+                            Object::try_parse_with_max_headers(Buf::chunk(&buf), self.max_headers)?;
+                        if let Some((size, obj)) = parsed {
                             buf.advance(size);
 
                             Ok(RoundResult::StageFinished(StageResult::DoneReading {
@@ -59,13 +137,13 @@ impl<Stream: Read + Write> HandshakeMachine<Stream> {
                             }))
                         } else {
                             Ok(RoundResult::Incomplete(HandshakeMachine {
-                                state: HandshakeState::Reading(buf, attack_check),
+                                state: HandshakeState::Reading(buf, attack_check, scan),
                                 ..self
                             }))
                         }
                     }
                     None => Ok(RoundResult::WouldBlock(HandshakeMachine {
-                        state: HandshakeState::Reading(buf, attack_check),
+                        state: HandshakeState::Reading(buf, attack_check, scan),
                         ..self
                     })),
                 }
@@ -132,19 +210,89 @@ pub enum StageResult<Object, Stream> {
 pub trait TryParse: Sized {
     /// Returns Ok(None) if incomplete, Err on syntax errors
     fn try_parse(data: &[u8]) -> Result<Option<(usize, Self)>>;
+
+    /// Same as [`try_parse`](Self::try_parse), allowing up to `max_headers` header lines
+    /// instead of [`MAX_HEADERS`].
+    fn try_parse_with_max_headers(
+        data: &[u8],
+        max_headers: usize,
+    ) -> Result<Option<(usize, Self)>> {
+        let _ = max_headers;
+        Self::try_parse(data)
+    }
 }
 
 /// The handshake state
 #[derive(Debug)]
 enum HandshakeState {
     /// Reading data from peer
-    Reading(ReadBuffer, AttackCheck),
+    Reading(ReadBuffer, AttackCheck, Option<HeaderScan>),
     /// Sending data to peer
     Writing(Cursor<Vec<u8>>),
     /// Flushing data to ensure that all intermediaries reach their destinations
     Flushing,
 }
 
+type HeaderCallback = Box<dyn FnMut(&str, &[u8]) -> Result<()> + Send>;
+
+/// Incremental header inspection while reading, see [`HandshakeMachine::with_header_callback`].
+struct HeaderScan {
+    callback: HeaderCallback,
+    /// Number of bytes already scanned, always at the start of a line.
+    offset: usize,
+    /// The end of the header section was reached.
+    done: bool,
+}
+
+impl HeaderScan {
+    /// Pass every complete header line of `data` not seen before to the callback.
+    fn scan(&mut self, data: &[u8]) -> Result<()> {
+        while !self.done {
+            let rest = &data[self.offset..];
+            let len = match rest.windows(2).position(|w| w == b"\r\n") {
+                Some(len) => len,
+                None => break,
+            };
+
+            let line = &rest[..len];
+            let is_start_line = self.offset == 0;
+            self.offset += len + 2;
+
+            if line.is_empty() {
+                self.done = true;
+            } else if !is_start_line {
+                if let Some(colon) = line.iter().position(|&b| b == b':') {
+                    if let Ok(name) = std::str::from_utf8(&line[..colon]) {
+                        (self.callback)(name, trim(&line[colon + 1..]))?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Strip optional whitespace around a header value.
+fn trim(mut value: &[u8]) -> &[u8] {
+    while let [b' ' | b'\t', rest @ ..] = value {
+        value = rest;
+    }
+    while let [rest @ .., b' ' | b'\t'] = value {
+        value = rest;
+    }
+    value
+}
+
+impl Debug for HeaderScan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HeaderScan")
+            .field("offset", &self.offset)
+            .field("done", &self.done)
+            .finish_non_exhaustive()
+    }
+}
+
 /// Attack mitigation against DoS attacks
 #[derive(Debug)]
 pub(crate) struct AttackCheck {
@@ -152,30 +300,43 @@ pub(crate) struct AttackCheck {
     packets: usize,
     /// Total number of bytes in HTTP header
     bytes: usize,
+    max_bytes: usize,
+    max_packets: usize,
+    min_packet_size: usize,
 }
 
 impl AttackCheck {
     /// Initialize attack checking for incoming buffer
-    fn new() -> Self {
-        Self { packets: 0, bytes: 0 }
+    pub(crate) fn new(config: &WebSocketConfig) -> Self {
+        Self {
+            packets: 0,
+            bytes: 0,
+            max_bytes: config.handshake_max_bytes,
+            max_packets: config.handshake_max_packets,
+            min_packet_size: config.handshake_min_packet_size,
+        }
     }
 
     /// Check the size of an incoming packet. To be called immediately after `read()`
     /// passing its returned bytes count as `size`
-    fn check_incoming_packet(&mut self, size: usize) -> Result<()> {
+    pub(crate) fn check_incoming_packet(&mut self, size: usize) -> Result<()> {
         self.packets += 1;
         self.bytes += size;
 
-        const MAX_BYTES: usize = 65536;
-        const MAX_PACKETS: usize = 512;
-        const MIN_PACKET_SIZE: usize = 128;
         const MIN_PACKET_CHECK_THRESHOLD: usize = 64;
 
-        if self.bytes > MAX_BYTES
-            || self.packets > MAX_PACKETS
+        if self.bytes > self.max_bytes
+            || self.packets > self.max_packets
             || (self.packets > MIN_PACKET_CHECK_THRESHOLD
-                && self.packets * MIN_PACKET_SIZE > self.bytes)
+                && self.packets.saturating_mul(self.min_packet_size) > self.bytes)
         {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                packets = self.packets,
+                bytes = self.bytes,
+                "handshake attack check tripped"
+            );
+
             return Err(Error::AttackAttempt);
         }
 
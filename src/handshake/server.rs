@@ -1,24 +1,27 @@
 //! Server handshake machine
 
 use http::{
-    HeaderMap, Method, Request as HttpRequest, Response as HttpResponse, StatusCode, Version,
+    HeaderMap, HeaderValue, Method, Request as HttpRequest, Response as HttpResponse, StatusCode,
+    Version,
 };
-use httparse::{Status, EMPTY_HEADER};
+use httparse::Status;
 use std::{
     io::{Read, Write},
     marker::PhantomData,
     result::Result as StdResult,
+    time::SystemTime,
 };
 
 use crate::{
     error::{Error, ProtocolError, Result},
     handshake::{
         core::{derive_accept_key, HandshakeRole, MidHandshake, ProcessingResult},
-        headers::{FromHttparse, MAX_HEADERS},
+        headers::{http_date, parse_extensions, with_header_buffer, FromHttparse, MAX_HEADERS},
         machine::{HandshakeMachine, StageResult, TryParse},
     },
     protocol::{
         config::WebSocketConfig,
+        extension::WebSocketExtension,
         websocket::{OperationMode, WebSocket},
     },
 };
@@ -102,12 +105,20 @@ pub fn write_response<T>(mut w: impl Write, res: &HttpResponse<T>) -> Result<()>
 
 impl TryParse for Request {
     fn try_parse(data: &[u8]) -> Result<Option<(usize, Self)>> {
-        let mut header_buf = [EMPTY_HEADER; MAX_HEADERS];
-        let mut req = httparse::Request::new(&mut header_buf);
+        Self::try_parse_with_max_headers(data, MAX_HEADERS)
+    }
 
-        Ok(match req.parse(data)? {
-            Status::Complete(n) => Some((n, Request::from_httparse(req)?)),
-            Status::Partial => None,
+    fn try_parse_with_max_headers(
+        data: &[u8],
+        max_headers: usize,
+    ) -> Result<Option<(usize, Self)>> {
+        with_header_buffer(max_headers, |header_buf| {
+            let mut req = httparse::Request::new(header_buf);
+
+            Ok(match req.parse(data)? {
+                Status::Complete(n) => Some((n, Request::from_httparse(req)?)),
+                Status::Partial => None,
+            })
         })
     }
 }
@@ -122,9 +133,12 @@ impl<'b: 'h, 'h> FromHttparse<httparse::Request<'h, 'b>> for Request {
             return Err(Error::Protocol(ProtocolError::InvalidHttpVersion));
         }
 
+        let path = raw.path.expect("Bug: no path in header");
+
         let mut req = Request::new(());
         *req.method_mut() = Method::GET;
-        *req.uri_mut() = raw.path.expect("Bug: no path in header").parse()?;
+        *req.uri_mut() = path.parse()?;
+        req.extensions_mut().insert(RequestTarget(path.into()));
         *req.version_mut() = Version::HTTP_11;
         *req.headers_mut() = HeaderMap::from_httparse(raw.headers)?;
 
@@ -132,6 +146,28 @@ impl<'b: 'h, 'h> FromHttparse<httparse::Request<'h, 'b>> for Request {
     }
 }
 
+/// The request target as sent by the client, see [`RequestExt::request_target`].
+#[derive(Clone, Debug)]
+struct RequestTarget(String);
+
+/// Convenience accessors for the [`Request`] passed to a [`Callback`].
+pub trait RequestExt {
+    /// The request target of the request line exactly as sent by the client, query included,
+    /// e.g. `/chat?room=123`.
+    ///
+    /// Falls back to the path and query of the URI for requests not read by the handshake.
+    fn request_target(&self) -> &str;
+}
+
+impl RequestExt for Request {
+    fn request_target(&self) -> &str {
+        match self.extensions().get::<RequestTarget>() {
+            Some(RequestTarget(target)) => target,
+            None => self.uri().path_and_query().map_or("/", |target| target.as_str()),
+        }
+    }
+}
+
 /// Callback trait
 ///
 /// The callback is called when the server receives an incoming WebSocket
@@ -142,6 +178,10 @@ pub trait Callback: Sized {
     /// Called whenever the server reads the request from the client and is ready to respond to it.
     /// May return additional reply headers.
     /// Returning an error resulting in rejecting the incoming connection.
+    ///
+    /// The raw `Sec-WebSocket-Extensions` headers of the request can be read with
+    /// [`parse_extensions`]. Extensions accepted in the response must have been offered by the
+    /// client, otherwise the handshake fails with [`ProtocolError::InvalidHeader`].
     fn on_request(self, req: &Request, res: Response) -> StdResult<Response, ErrorResponse>;
 }
 
@@ -164,6 +204,72 @@ impl Callback for NoCallback {
     }
 }
 
+/// Callback negotiating the subprotocol of the connection.
+///
+/// The wrapped function receives the subprotocols offered by the client in its
+/// `Sec-WebSocket-Protocol` headers, in order of preference, and returns the selected one, if any.
+/// The selected subprotocol is then sent back in the `Sec-WebSocket-Protocol` response header,
+/// a subprotocol the client did not offer is ignored.
+#[derive(Clone, Copy, Debug)]
+pub struct SubprotocolSelector<F>(F);
+
+impl<F> SubprotocolSelector<F>
+where
+    F: for<'a> FnOnce(&[&'a str]) -> Option<&'a str>,
+{
+    /// Wrap a function selecting one of the offered subprotocols.
+    pub fn new(select: F) -> Self {
+        Self(select)
+    }
+}
+
+impl<F> Callback for SubprotocolSelector<F>
+where
+    F: for<'a> FnOnce(&[&'a str]) -> Option<&'a str>,
+{
+    fn on_request(self, req: &Request, mut res: Response) -> StdResult<Response, ErrorResponse> {
+        let offered: Vec<&str> = req
+            .headers()
+            .get_all("Sec-WebSocket-Protocol")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|protocol| !protocol.is_empty())
+            .collect();
+
+        if let Some(protocol) = (self.0)(&offered).filter(|p| offered.contains(p)) {
+            if let Ok(value) = HeaderValue::from_str(protocol) {
+                res.headers_mut().insert("Sec-WebSocket-Protocol", value);
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+/// Check that every extension accepted in the response was offered by the client.
+///
+/// Catches callbacks answering with extensions of their own, which the client must reject.
+fn check_extensions_offered(req: &Request, res: &Response) -> Result<()> {
+    let mut offered = vec![];
+    for value in req.headers().get_all("Sec-WebSocket-Extensions") {
+        offered.extend(parse_extensions(value.to_str()?).into_iter().map(|(name, _)| name));
+    }
+
+    for value in res.headers().get_all("Sec-WebSocket-Extensions") {
+        for (name, _) in parse_extensions(value.to_str()?) {
+            if !offered.iter().any(|o| o.eq_ignore_ascii_case(&name)) {
+                return Err(Error::Protocol(ProtocolError::InvalidHeader(
+                    http::header::SEC_WEBSOCKET_EXTENSIONS,
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Server handshake role
 #[allow(missing_copy_implementations)]
 #[derive(Debug)]
@@ -176,6 +282,12 @@ pub struct ServerHandshake<S, C> {
     config: Option<WebSocketConfig>,
     /// Error code/flag. If set, an error will be returned after sending response to the client.
     error_response: Option<ErrorResponse>,
+    /// Origins accepted from the client, any origin (or none) is accepted if `None`.
+    allowed_origins: Option<Vec<String>>,
+    /// Headers of the accepted request, kept if `WebSocketConfig::retain_request_headers` is set.
+    request_headers: Option<HeaderMap>,
+    /// Extension to accept if the client offers it, dropped if it does not.
+    extension: Option<Box<dyn WebSocketExtension>>,
     /// Internal stream type.
     _marker: PhantomData<S>,
 }
@@ -187,18 +299,108 @@ impl<S: Read + Write, C: Callback> ServerHandshake<S, C> {
     /// upon join based on the incoming headers.
     pub fn start(stream: S, callback: C, config: Option<WebSocketConfig>) -> MidHandshake<Self> {
         MidHandshake {
-            machine: HandshakeMachine::start_read(stream),
+            machine: HandshakeMachine::start_read(stream).with_config(config.as_ref()),
             role: ServerHandshake {
                 callback: Some(callback),
                 config,
                 error_response: None,
+                allowed_origins: None,
+                request_headers: None,
+                extension: None,
                 _marker: PhantomData,
             },
         }
     }
+
+    /// Check if the `Origin` of the request is one of the allowed origins, if any.
+    fn origin_allowed(&self, req: &Request) -> bool {
+        let allowed = match &self.allowed_origins {
+            Some(allowed) => allowed,
+            None => return true,
+        };
+
+        req.headers()
+            .get("Origin")
+            .and_then(|h| h.to_str().ok())
+            .map(|origin| allowed.iter().any(|a| a.eq_ignore_ascii_case(origin)))
+            .unwrap_or(false)
+    }
+
+    /// Accept the first offer of the extension that it agrees to, unless the callback already
+    /// answered for it. The extension is dropped if it is not accepted.
+    fn negotiate_extension(&mut self, req: &Request, res: &mut Response) -> Result<()> {
+        let mut extension = match self.extension.take() {
+            Some(extension) => extension,
+            None => return Ok(()),
+        };
+
+        for value in res.headers().get_all("Sec-WebSocket-Extensions") {
+            if parse_extensions(value.to_str()?)
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case(extension.name()))
+            {
+                return Ok(());
+            }
+        }
+
+        for value in req.headers().get_all("Sec-WebSocket-Extensions") {
+            for (name, params) in parse_extensions(value.to_str()?) {
+                if !name.eq_ignore_ascii_case(extension.name()) {
+                    continue;
+                }
+
+                if let Some(reply) = extension.accept_offer(&params) {
+                    res.headers_mut().append("Sec-WebSocket-Extensions", reply.parse()?);
+                    self.extension = Some(extension);
+                    return Ok(());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: Read + Write, C: Callback> MidHandshake<ServerHandshake<S, C>> {
+    /// Only accept clients sending one of the given origins in their `Origin` header,
+    /// e.g. `https://example.com`, as a protection against cross-site WebSocket hijacking.
+    ///
+    /// Requests with a missing or different `Origin` are answered with `403 Forbidden` before
+    /// the callback is called, and the handshake fails with [`Error::Http`]. Origins are
+    /// compared ignoring ASCII case.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::net::TcpListener;
+    /// # use blitz_ws::{handshake::server::NoCallback, ServerHandshake};
+    /// # let (stream, _) = TcpListener::bind("127.0.0.1:9001").unwrap().accept().unwrap();
+    /// let ws = ServerHandshake::start(stream, NoCallback, None)
+    ///     .allowed_origins(["https://example.com", "https://www.example.com"])
+    ///     .handshake();
+    /// ```
+    pub fn allowed_origins<I>(mut self, origins: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.role.allowed_origins = Some(origins.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Accept an extension if the client offers it.
+    ///
+    /// The offers of the client are passed to [`WebSocketExtension::accept_offer`] in order,
+    /// the first accepted one is answered in the `Sec-WebSocket-Extensions` response header and
+    /// the extension is installed on the connection. Nothing is negotiated if the callback
+    /// already answered for the extension. See [`WebSocketExtension`] for an example.
+    pub fn extension(mut self, extension: impl WebSocketExtension + 'static) -> Self {
+        self.role.extension = Some(Box::new(extension));
+        self
+    }
 }
 
 impl<S: Read + Write, C: Callback> HandshakeRole for ServerHandshake<S, C> {
+    const NAME: &'static str = "server";
     type IncomingData = Request;
     type InternalStream = S;
     type FinalResult = WebSocket<S>;
@@ -213,15 +415,33 @@ impl<S: Read + Write, C: Callback> HandshakeRole for ServerHandshake<S, C> {
                     return Err(Error::Protocol(ProtocolError::JunkAfterRequest));
                 }
 
-                let response = create_response(&result)?;
-                let callback_result = if let Some(callback) = self.callback.take() {
+                let mut response = create_response(&result)?;
+                let config = self.config.unwrap_or_default();
+
+                if let Some(server) = config.server_header {
+                    response.headers_mut().insert("Server", server.parse()?);
+                }
+                if config.date_header {
+                    response.headers_mut().insert("Date", http_date(SystemTime::now()).parse()?);
+                }
+
+                let callback_result = if !self.origin_allowed(&result) {
+                    Err(HttpResponse::builder().status(StatusCode::FORBIDDEN).body(None)?)
+                } else if let Some(callback) = self.callback.take() {
                     callback.on_request(&result, response)
                 } else {
                     Ok(response)
                 };
 
                 match callback_result {
-                    Ok(resp) => {
+                    Ok(mut resp) => {
+                        self.negotiate_extension(&result, &mut resp)?;
+                        check_extensions_offered(&result, &resp)?;
+
+                        if config.retain_request_headers {
+                            self.request_headers = Some(result.headers().clone());
+                        }
+
                         let mut output = vec![];
                         write_response(&mut output, &resp)?;
 
@@ -259,11 +479,11 @@ impl<S: Read + Write, C: Callback> HandshakeRole for ServerHandshake<S, C> {
                     )));
                 }
 
-                Ok(ProcessingResult::Done(WebSocket::new(
-                    stream,
-                    OperationMode::Server,
-                    self.config,
-                )))
+                let mut websocket = WebSocket::new(stream, OperationMode::Server, self.config);
+                websocket.set_request_headers(self.request_headers.take());
+                websocket.set_extension(self.extension.take());
+
+                Ok(ProcessingResult::Done(websocket))
             }
         }
     }
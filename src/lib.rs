@@ -21,16 +21,26 @@ pub mod client;
 #[cfg(feature = "handshake")]
 pub mod handshake;
 #[cfg(feature = "handshake")]
+mod proxy;
+#[cfg(feature = "handshake")]
+mod reconnect;
+#[cfg(feature = "handshake")]
 mod server;
 
 #[cfg(all(any(feature = "native-tls", feature = "rustls"), feature = "handshake"))]
 mod tls;
 
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
 pub mod buffer;
 pub mod error;
 pub mod protocol;
 pub mod stream;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 pub mod util;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 /// Constant for maximum message payload length
 pub const MAX_ALLOWED_LEN: usize = 16 * 1024 * 1024;
@@ -44,12 +54,16 @@ type ReadBuffer = buffer::ReadBuffer<READ_BUFFER_SIZE>;
 
 pub use bytes::Bytes;
 
+#[cfg(feature = "tokio")]
+pub use crate::asynchronous::AsyncWebSocket;
+
 #[cfg(feature = "handshake")]
 pub use crate::{
-    client::{client, connect, ClientRequestBuilder},
+    client::{client, connect, ClientRequestBuilder, ConnectOptions},
     handshake::{client::ClientHandshake, server::ServerHandshake, HandshakeError},
-    server::{accept, accept_header, accept_header_with_config, accept_with_config},
+    reconnect::ReconnectingClient,
+    server::{accept, accept_header, accept_header_with_config, accept_with_config, Acceptor},
 };
 
 #[cfg(all(any(feature = "native-tls", feature = "__rustls-tls"), feature = "handshake"))]
-pub use tls::{client_tls, client_tls_with_config, Connector};
+pub use tls::{client_tls, client_tls_with_config, client_tls_with_options, Connector};
@@ -5,13 +5,26 @@ use std::io::{self, Read};
 
 use flate2::{
     bufread::{DeflateDecoder, DeflateEncoder},
-    Compression,
+    Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status,
+};
+
+use crate::{
+    error::{CapacityError, Error, ProtocolError, Result},
+    protocol::{
+        extension::WebSocketExtension,
+        frame::{
+            codec::{Data, OpCode},
+            Frame,
+        },
+    },
 };
 
 const PERMESSAFE_DEFLATE_TRAILER: &[u8] = &[0x00, 0x00, 0xff, 0xff];
 
 #[allow(missing_docs)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct WebSocketCompressionConfig {
     pub enabled: bool,
     pub client_no_context_takeover: bool,
@@ -32,49 +45,499 @@ impl Default for WebSocketCompressionConfig {
     }
 }
 
+impl WebSocketCompressionConfig {
+    /// Set [`Self::enabled`].
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Set [`Self::client_no_context_takeover`].
+    pub fn client_no_context_takeover(mut self, client_no_context_takeover: bool) -> Self {
+        self.client_no_context_takeover = client_no_context_takeover;
+        self
+    }
+
+    /// Set [`Self::server_no_context_takeover`].
+    pub fn server_no_context_takeover(mut self, server_no_context_takeover: bool) -> Self {
+        self.server_no_context_takeover = server_no_context_takeover;
+        self
+    }
+
+    /// Set [`Self::client_max_window_bits`].
+    pub fn client_max_window_bits(mut self, client_max_window_bits: Option<u8>) -> Self {
+        self.client_max_window_bits = client_max_window_bits;
+        self
+    }
+
+    /// Set [`Self::server_max_window_bits`].
+    pub fn server_max_window_bits(mut self, server_max_window_bits: Option<u8>) -> Self {
+        self.server_max_window_bits = server_max_window_bits;
+        self
+    }
+}
+
+/// Parameters of a `permessage-deflate` extension agreed upon during the handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NegotiatedCompression {
+    pub client_no_context_takeover: bool,
+    pub server_no_context_takeover: bool,
+    pub client_max_window_bits: Option<u8>,
+    pub server_max_window_bits: Option<u8>,
+}
+
+impl NegotiatedCompression {
+    /// The extension token used in `Sec-WebSocket-Extensions`.
+    pub const EXTENSION_NAME: &'static str = "permessage-deflate";
+
+    /// Builds the negotiated parameters from the extension params, ignoring unknown ones.
+    pub fn from_params<K, V>(params: &[(K, Option<V>)]) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut negotiated = Self::default();
+
+        for (key, value) in params {
+            let bits = value.as_ref().and_then(|v| v.as_ref().parse().ok());
+
+            match key.as_ref() {
+                "client_no_context_takeover" => negotiated.client_no_context_takeover = true,
+                "server_no_context_takeover" => negotiated.server_no_context_takeover = true,
+                "client_max_window_bits" => negotiated.client_max_window_bits = bits,
+                "server_max_window_bits" => negotiated.server_max_window_bits = bits,
+                _ => {}
+            }
+        }
+
+        negotiated
+    }
+}
+
+/// A `server_max_window_bits` or `client_max_window_bits` parameter of a `permessage-deflate`
+/// offer, which may appear with or without a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaxWindowBits {
+    /// The parameter is absent.
+    #[default]
+    Absent,
+    /// The parameter is present without a value: the offer has no preference and the
+    /// response may pick the number of bits.
+    NoValue,
+    /// The parameter is present with a number of bits between 8 and 15.
+    Bits(u8),
+}
+
+impl MaxWindowBits {
+    /// Parses the value of a parameter that is present, `None` if the value is invalid.
+    pub fn from_value<V: AsRef<str>>(value: Option<V>) -> Option<Self> {
+        match value {
+            None => Some(Self::NoValue),
+            Some(v) => match v.as_ref().parse() {
+                Ok(bits @ 8..=15) => Some(Self::Bits(bits)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns the number of bits, if given.
+    pub fn bits(self) -> Option<u8> {
+        match self {
+            Self::Bits(bits) => Some(bits),
+            Self::Absent | Self::NoValue => None,
+        }
+    }
+}
+
+/// Parameters of a `permessage-deflate` extension offered by a client.
+///
+/// # Example
+/// ```
+/// # use blitz_ws::handshake::headers::parse_extensions;
+/// # use blitz_ws::protocol::compression::{CompressionOffer, MaxWindowBits};
+/// let offer = |value: &str| {
+///     let (_, params) = parse_extensions(value).remove(0);
+///     CompressionOffer::from_params(&params)
+/// };
+///
+/// let absent = offer("permessage-deflate").unwrap();
+/// assert_eq!(absent.server_max_window_bits, MaxWindowBits::Absent);
+///
+/// let no_value = offer("permessage-deflate; server_max_window_bits").unwrap();
+/// assert_eq!(no_value.server_max_window_bits, MaxWindowBits::NoValue);
+///
+/// let bits = offer("permessage-deflate; server_max_window_bits=12").unwrap();
+/// assert_eq!(bits.server_max_window_bits, MaxWindowBits::Bits(12));
+///
+/// assert_eq!(offer("permessage-deflate; server_max_window_bits=16"), None);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CompressionOffer {
+    pub client_no_context_takeover: bool,
+    pub server_no_context_takeover: bool,
+    pub client_max_window_bits: MaxWindowBits,
+    pub server_max_window_bits: MaxWindowBits,
+}
+
+impl CompressionOffer {
+    /// Builds the offered parameters from the extension params, ignoring unknown ones.
+    ///
+    /// Returns `None` if a window bits value is invalid, the offer must be declined then.
+    pub fn from_params<K, V>(params: &[(K, Option<V>)]) -> Option<Self>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut offer = Self::default();
+
+        for (key, value) in params {
+            let value = value.as_ref().map(AsRef::as_ref);
+
+            match key.as_ref() {
+                "client_no_context_takeover" => offer.client_no_context_takeover = true,
+                "server_no_context_takeover" => offer.server_no_context_takeover = true,
+                "client_max_window_bits" => {
+                    offer.client_max_window_bits = MaxWindowBits::from_value(value)?
+                }
+                "server_max_window_bits" => {
+                    offer.server_max_window_bits = MaxWindowBits::from_value(value)?
+                }
+                _ => {}
+            }
+        }
+
+        Some(offer)
+    }
+}
+
+/// Cumulative `permessage-deflate` byte counters of a connection.
+///
+/// The compression ratio of a direction is the compressed count divided by the uncompressed one,
+/// see [`Self::sent_ratio`] and [`Self::received_ratio`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CompressionStats {
+    /// Payload bytes of outgoing messages before compression.
+    pub sent_uncompressed: u64,
+    /// Payload bytes of outgoing messages after compression.
+    pub sent_compressed: u64,
+    /// Payload bytes of incoming messages before decompression.
+    pub received_compressed: u64,
+    /// Payload bytes of incoming messages after decompression.
+    pub received_uncompressed: u64,
+}
+
+impl CompressionStats {
+    /// Reset all counters to zero.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Compressed to uncompressed size of everything sent, or `None` if nothing was sent.
+    pub fn sent_ratio(&self) -> Option<f64> {
+        ratio(self.sent_compressed, self.sent_uncompressed)
+    }
+
+    /// Compressed to uncompressed size of everything received, or `None` if nothing was received.
+    pub fn received_ratio(&self) -> Option<f64> {
+        ratio(self.received_compressed, self.received_uncompressed)
+    }
+}
+
+fn ratio(compressed: u64, uncompressed: u64) -> Option<f64> {
+    (uncompressed > 0).then(|| compressed as f64 / uncompressed as f64)
+}
+
+/// The `permessage-deflate` extension of RFC 7692, a [`WebSocketExtension`] compressing the
+/// payload of every outgoing message and decompressing incoming messages with `RSV1` set.
+///
+/// Outgoing messages are always compressed without context takeover: a client asks for
+/// `client_no_context_takeover` and a server replies with `server_no_context_takeover`.
+/// Incoming messages may use context takeover unless it was negotiated away. Window sizes below
+/// 15 bits cannot be applied to outgoing messages, so a server declines offers restricting
+/// `server_max_window_bits` and a client fails the handshake if `client_max_window_bits` is
+/// restricted in the reply.
+///
+/// # Example
+/// ```
+/// # use blitz_ws::error::{CapacityError, Error};
+/// # use blitz_ws::protocol::{
+/// #     compression::{PerMessageDeflate, WebSocketCompressionConfig},
+/// #     extension::WebSocketExtension,
+/// #     frame::{codec::{Data, OpCode}, Frame},
+/// # };
+/// let mut client = PerMessageDeflate::new(WebSocketCompressionConfig::default());
+/// let mut server = PerMessageDeflate::new(WebSocketCompressionConfig::default());
+/// assert_eq!(client.offer(), "permessage-deflate; client_no_context_takeover");
+///
+/// let params = vec![("client_no_context_takeover".to_string(), None)];
+/// let reply = server.accept_offer(&params).unwrap();
+/// assert_eq!(reply, "permessage-deflate; server_no_context_takeover");
+///
+/// let text = "a".repeat(1000);
+/// let mut frame = Frame::new_data(text.clone(), OpCode::Data(Data::Text), true);
+/// client.transform_outgoing(&mut frame);
+/// assert!(frame.header().rsv1);
+/// assert!(frame.payload().len() < 100);
+///
+/// server.transform_incoming(&mut frame, None).unwrap();
+/// assert!(!frame.header().rsv1);
+/// assert_eq!(frame.payload(), text.as_bytes());
+///
+/// // A few compressed bytes must not inflate past the limit.
+/// let mut frame = Frame::new_data(vec![0; 1 << 20], OpCode::Data(Data::Binary), true);
+/// client.transform_outgoing(&mut frame);
+/// assert!(frame.payload().len() < 2048);
+/// assert!(matches!(
+///     server.transform_incoming(&mut frame, Some(4096)),
+///     Err(Error::Capacity(CapacityError::MessageTooLarge { max: 4096, .. }))
+/// ));
+/// ```
+#[derive(Debug)]
+pub struct PerMessageDeflate {
+    config: WebSocketCompressionConfig,
+    compressor: Compressor,
+    decompressor: Decompressor,
+    /// Send: the message being sent is compressed.
+    compressing: bool,
+    /// Receive: the message being received is compressed.
+    decompressing: bool,
+}
+
+impl PerMessageDeflate {
+    /// Create the extension, offered or accepted according to `config`.
+    ///
+    /// A server declines every offer if [`WebSocketCompressionConfig::enabled`] is unset.
+    pub fn new(config: WebSocketCompressionConfig) -> Self {
+        Self {
+            config,
+            compressor: Compressor::new(true),
+            decompressor: Decompressor::new(false),
+            compressing: false,
+            decompressing: false,
+        }
+    }
+}
+
+impl WebSocketExtension for PerMessageDeflate {
+    fn name(&self) -> &str {
+        NegotiatedCompression::EXTENSION_NAME
+    }
+
+    fn offer(&self) -> String {
+        let mut offer = format!("{}; client_no_context_takeover", self.name());
+        if self.config.server_no_context_takeover {
+            offer.push_str("; server_no_context_takeover");
+        }
+        if let Some(bits) = self.config.server_max_window_bits {
+            offer.push_str(&format!("; server_max_window_bits={bits}"));
+        }
+
+        offer
+    }
+
+    fn accept_offer(&mut self, params: &[(String, Option<String>)]) -> Option<String> {
+        if !self.config.enabled {
+            return None;
+        }
+
+        let offer = CompressionOffer::from_params(params)?;
+        if matches!(offer.server_max_window_bits, MaxWindowBits::Bits(bits) if bits < 15) {
+            return None;
+        }
+
+        let mut reply = format!("{}; server_no_context_takeover", self.name());
+        if self.config.client_no_context_takeover {
+            reply.push_str("; client_no_context_takeover");
+        }
+
+        self.decompressor = Decompressor::new(
+            self.config.client_no_context_takeover || offer.client_no_context_takeover,
+        );
+        Some(reply)
+    }
+
+    fn accept_response(&mut self, params: &[(String, Option<String>)]) -> Result<()> {
+        let negotiated = NegotiatedCompression::from_params(params);
+        #[cfg(feature = "handshake")]
+        if matches!(negotiated.client_max_window_bits, Some(bits) if bits < 15) {
+            return Err(Error::Protocol(ProtocolError::InvalidHeader(
+                http::header::SEC_WEBSOCKET_EXTENSIONS,
+            )));
+        }
+
+        self.decompressor = Decompressor::new(negotiated.server_no_context_takeover);
+        Ok(())
+    }
+
+    fn transform_outgoing(&mut self, frame: &mut Frame) {
+        let first = match frame.header().opcode {
+            OpCode::Data(Data::Text | Data::Binary) => true,
+            OpCode::Data(Data::Continuation) if self.compressing => false,
+            _ => return,
+        };
+
+        let fin = frame.header().fin;
+        let payload = self
+            .compressor
+            .compress_fragment(frame.payload(), fin)
+            .expect("Bug: failed to compress into memory");
+
+        frame.header_mut().rsv1 = first;
+        frame.set_payload(payload.into());
+        self.compressing = !fin;
+    }
+
+    fn transform_incoming(&mut self, frame: &mut Frame, max_size: Option<usize>) -> Result<()> {
+        let header = frame.header();
+        let first = match header.opcode {
+            OpCode::Data(Data::Text | Data::Binary) if header.rsv1 => true,
+            // `RSV1` is only set on the first frame, the connection rejects it on the others.
+            OpCode::Data(Data::Continuation) if self.decompressing && !header.rsv1 => false,
+            _ => return Ok(()),
+        };
+
+        let fin = header.fin;
+        let payload = self.decompressor.decompress_fragment(frame.payload(), fin, max_size)?;
+
+        if first {
+            frame.header_mut().rsv1 = false;
+        }
+        frame.set_payload(payload.into());
+        self.decompressing = !fin;
+
+        Ok(())
+    }
+}
+
 #[allow(missing_docs)]
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug)]
 pub struct Compressor {
-    _no_context_takeover: bool,
+    compress: Compress,
+    no_context_takeover: bool,
 }
 
 #[allow(missing_docs)]
 impl Compressor {
     pub fn new(no_context_takeover: bool) -> Self {
-        Self { _no_context_takeover: no_context_takeover }
+        Self { compress: Compress::new(Compression::default(), false), no_context_takeover }
     }
 
     pub fn compress(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
-        let mut encoder = DeflateEncoder::new(data, Compression::default());
-        let mut compressed = Vec::new();
+        self.compress_fragment(data, true)
+    }
+
+    /// Compress a fragment of a message, the trailer is stripped from the final one.
+    pub fn compress_fragment(&mut self, data: &[u8], is_final: bool) -> io::Result<Vec<u8>> {
+        let mut compressed = Vec::with_capacity(data.len() / 2 + 64);
+        let start = self.compress.total_in();
+
+        loop {
+            let consumed = (self.compress.total_in() - start) as usize;
+            self.compress
+                .compress_vec(&data[consumed..], &mut compressed, FlushCompress::Sync)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+            // The flush is complete once the output is not filled up anymore.
+            let consumed = (self.compress.total_in() - start) as usize;
+            if consumed == data.len() && compressed.len() < compressed.capacity() {
+                break;
+            }
+            compressed.reserve(compressed.capacity().max(64));
+        }
+
+        if is_final {
+            if compressed.ends_with(PERMESSAFE_DEFLATE_TRAILER) {
+                compressed.truncate(compressed.len() - PERMESSAFE_DEFLATE_TRAILER.len());
+            }
+            if self.no_context_takeover {
+                self.compress.reset();
+            }
+        }
 
-        encoder.read_to_end(&mut compressed)?;
         Ok(compressed)
     }
 }
 
 #[allow(missing_docs)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug)]
 pub struct Decompressor {
-    _no_context_takeover: bool,
+    decompress: Decompress,
+    no_context_takeover: bool,
 }
 
 #[allow(missing_docs)]
 impl Decompressor {
     pub fn new(no_context_takeover: bool) -> Self {
-        Self { _no_context_takeover: no_context_takeover }
+        Self { decompress: Decompress::new(false), no_context_takeover }
+    }
+
+    pub fn decompress(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        self.decompress_fragment(data, true, None)
     }
 
-    pub fn decompress(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
-        let mut trailer_data = data.to_vec();
-        trailer_data.extend_from_slice(PERMESSAFE_DEFLATE_TRAILER);
+    /// Decompress a fragment of a message, the trailer is restored after the final one.
+    ///
+    /// Fails with [`CapacityError::MessageTooLarge`] as soon as the output would exceed
+    /// `max_size` bytes, without inflating the rest.
+    pub fn decompress_fragment(
+        &mut self,
+        data: &[u8],
+        is_final: bool,
+        max_size: Option<usize>,
+    ) -> Result<Vec<u8>> {
+        let max = max_size.unwrap_or(usize::MAX);
+        let mut decompressed =
+            Vec::with_capacity(data.len().saturating_mul(2).saturating_add(64).min(max));
 
-        let mut decoder = DeflateDecoder::new(&trailer_data[..]);
-        let mut decompressed = Vec::new();
+        self.inflate(data, &mut decompressed, max)?;
+        if is_final {
+            self.inflate(PERMESSAFE_DEFLATE_TRAILER, &mut decompressed, max)?;
+            if self.no_context_takeover {
+                self.decompress.reset(false);
+            }
+        }
 
-        decoder.read_to_end(&mut decompressed)?;
         Ok(decompressed)
     }
+
+    fn inflate(&mut self, mut data: &[u8], output: &mut Vec<u8>, max: usize) -> Result<()> {
+        loop {
+            check_inflated_size(output.len(), max)?;
+            if output.len() == output.capacity() {
+                // Room for one byte past the limit tells whether the output exceeds it.
+                let room = max.saturating_add(1) - output.len();
+                output.reserve_exact(output.capacity().max(64).min(room));
+            }
+
+            let (start, written) = (self.decompress.total_in(), output.len());
+            let status = self
+                .decompress
+                .decompress_vec(data, output, FlushDecompress::Sync)
+                .map_err(|_| Error::Protocol(ProtocolError::InvalidExtensionPayload))?;
+            let consumed = (self.decompress.total_in() - start) as usize;
+            data = &data[consumed..];
+
+            // A block with `BFINAL` set ends the stream, the next message starts a new one.
+            if status == Status::StreamEnd {
+                self.decompress.reset(false);
+                break;
+            }
+            // Output may still be pending after all input was consumed, stop once drained.
+            if consumed == 0 && output.len() == written {
+                break;
+            }
+        }
+
+        check_inflated_size(output.len(), max)
+    }
+}
+
+fn check_inflated_size(size: usize, max: usize) -> Result<()> {
+    if size > max {
+        return Err(Error::Capacity(CapacityError::MessageTooLarge { size, max }));
+    }
+
+    Ok(())
 }
 
 #[doc(hidden)]
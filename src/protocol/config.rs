@@ -6,12 +6,14 @@ use crate::protocol::compression::WebSocketCompressionConfig;
 ///
 /// # Example
 /// ```
-/// # use blitz::protocol::WebSocketConfig;;
+/// # use blitz_ws::protocol::config::WebSocketConfig;
 /// let conf = WebSocketConfig::default()
 ///     .read_buffer_size(256 * 1024)
 ///     .write_buffer_size(256 * 1024);
 /// ```
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 #[non_exhaustive]
 pub struct WebSocketConfig {
     /// Read buffer capacity. This buffer is eagerly allocated and used for receiving
@@ -48,11 +50,16 @@ pub struct WebSocketConfig {
     /// The maximum size of an incoming message. `None` means no size limit. The default value is 64 MiB
     /// which should be reasonably big for all normal use-cases but small enough to prevent
     /// memory eating by a malicious user.
+    ///
+    /// Outgoing text and binary messages bigger than this are rejected as well.
     pub max_message_size: Option<usize>,
     /// The maximum size of a single incoming message frame. `None` means no size limit. The limit is for
     /// frame payload NOT including the frame header. The default value is 16 MiB which should
     /// be reasonably big for all normal use-cases but small enough to prevent memory eating
     /// by a malicious user.
+    ///
+    /// Outgoing text and binary messages bigger than this are split into continuation frames
+    /// of at most this size.
     pub max_frame_size: Option<usize>,
     /// When set to `true`, the server will accept and handle unmasked frames
     /// from the client. According to the RFC 6455, the server must close the
@@ -60,8 +67,99 @@ pub struct WebSocketConfig {
     /// some popular libraries that are sending unmasked frames, ignoring the RFC.
     /// By default this option is set to `false`, i.e. according to RFC 6455.
     pub accept_unmasked_frames: bool,
+    /// When set to `true`, the client will unmask and accept masked frames from the server.
+    /// According to the RFC 6455, the client must close the connection in such cases, this is
+    /// only meant for interoperability with non-compliant servers. The default value is `false`,
+    /// which fails the connection with `ProtocolError::MaskedFrameFromServer`.
+    pub accept_masked_frames_from_server: bool,
     /// Configuration for compression module
     pub compression: WebSocketCompressionConfig,
+    /// When set to `true`, received ping and pong frames are not returned from `read` as
+    /// messages. They are queued as [`ControlEvent`](crate::protocol::message::ControlEvent)s
+    /// instead, to be retrieved with `take_control_events`. Automatic pong replies are sent
+    /// regardless. The default value is `false`.
+    pub control_events: bool,
+    /// RFC 6455 allows pings and pongs in between the frames of a fragmented message. By default
+    /// they are returned from `read` as soon as they arrive, i.e. before the message they
+    /// interrupted. When set to `true`, they are held back and returned after that message
+    /// instead. Automatic pong replies are sent right away regardless. The default value is
+    /// `false`.
+    pub defer_control_during_fragment: bool,
+    /// When set to `false`, received pings are not answered automatically. They are still
+    /// returned from `read` (or queued as control events), and the application is responsible
+    /// for replying with a pong, as RFC 6455 requires. The default value is `true`.
+    pub auto_pong: bool,
+    /// The maximum number of automatic pong replies queued while waiting to be written.
+    ///
+    /// When a peer sends pings faster than the replies are flushed, only the replies to the most
+    /// recent pings are kept, which RFC 6455 explicitly allows. Raising this value answers up to
+    /// that many distinct pings in order, for stricter conformance. The default value is `1`,
+    /// i.e. only the most recent ping is answered.
+    pub max_pending_pongs: usize,
+    /// The value of the `User-Agent` header a client sends with its handshake request, unless
+    /// the request already has one. `None` means no header is added. The default value is `None`.
+    ///
+    /// Not (de)serialized with the `serde` feature, as it cannot borrow from the input.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub user_agent: Option<&'static str>,
+    /// The value of the `Server` header a server sends with its `101 Switching Protocols`
+    /// response. `None` means no header is sent. The default value is `None`.
+    ///
+    /// Not (de)serialized with the `serde` feature, as it cannot borrow from the input.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub server_header: Option<&'static str>,
+    /// When set to `true`, a server sends an RFC 7231 `Date` header with its
+    /// `101 Switching Protocols` response. The default value is `false`.
+    pub date_header: bool,
+    /// When set to `true`, a server keeps the headers of the client's handshake request,
+    /// available from `WebSocket::request_headers` afterwards. The default value is `false`,
+    /// which drops them once the handshake is done.
+    pub retain_request_headers: bool,
+    /// When set to `true`, the WebSocket never responds on its own: received pings are not
+    /// answered with pongs and a close frame from the peer is not replied to. Frames are still
+    /// validated and delivered to the application, which becomes responsible for every response,
+    /// e.g. a proxy forwarding the traffic of both sides verbatim. After receiving a close frame,
+    /// the reply is sent with `close`.
+    ///
+    /// Misusing this mode violates RFC 6455, which requires endpoints to answer pings and close
+    /// frames. The default value is `false`.
+    pub passive: bool,
+    /// The maximum number of bytes read while waiting for the complete handshake request or
+    /// response. The default value is 64 KiB.
+    pub handshake_max_bytes: usize,
+    /// The maximum number of reads (usually TCP packets) while waiting for the complete
+    /// handshake request or response. The default value is `512`.
+    pub handshake_max_packets: usize,
+    /// After the first 64 reads of the handshake, the minimum average number of bytes per read.
+    /// Peers trickling the handshake in tiny packets are assumed to be attacking the server.
+    /// The default value is `128`.
+    ///
+    /// Proxies may legitimately split the handshake into many small packets. Setting this to `0`
+    /// and the handshake limits above to `usize::MAX` disables the checks altogether.
+    pub handshake_min_packet_size: usize,
+    /// The maximum number of header lines in the handshake request or response. Peers sending
+    /// more are rejected with [`CapacityError::TooManyHeaders`]. The default value is `124`.
+    ///
+    /// Servers behind several proxies adding forwarding headers may need to raise this.
+    ///
+    /// [`CapacityError::TooManyHeaders`]: crate::error::CapacityError::TooManyHeaders
+    pub max_headers: usize,
+    /// The maximum number of messages received over the whole connection. Control frames count
+    /// as messages, fragments of a message do not. `None` means no limit. The default value is
+    /// `None`.
+    ///
+    /// Once exceeded, the connection is closed with [`CloseCode::Policy`] and `read` returns
+    /// [`ProtocolError::ConnectionLimitExceeded`].
+    ///
+    /// [`CloseCode::Policy`]: crate::protocol::frame::codec::CloseCode::Policy
+    /// [`ProtocolError::ConnectionLimitExceeded`]: crate::error::ProtocolError::ConnectionLimitExceeded
+    pub max_messages: Option<usize>,
+    /// The maximum number of bytes received over the whole connection, frame headers included.
+    /// `None` means no limit. The default value is `None`.
+    ///
+    /// Once exceeded, the connection is closed the same way as with
+    /// [`max_messages`](Self::max_messages).
+    pub max_total_bytes: Option<usize>,
 }
 
 impl Default for WebSocketConfig {
@@ -73,7 +171,23 @@ impl Default for WebSocketConfig {
             max_message_size: Some(64 << 20),
             max_frame_size: Some(64 << 20),
             accept_unmasked_frames: false,
+            accept_masked_frames_from_server: false,
             compression: WebSocketCompressionConfig::default(),
+            control_events: false,
+            defer_control_during_fragment: false,
+            auto_pong: true,
+            max_pending_pongs: 1,
+            user_agent: None,
+            server_header: None,
+            date_header: false,
+            retain_request_headers: false,
+            passive: false,
+            handshake_max_bytes: 64 * 1024,
+            handshake_max_packets: 512,
+            handshake_min_packet_size: 128,
+            max_headers: 124,
+            max_messages: None,
+            max_total_bytes: None,
         }
     }
 }
@@ -102,14 +216,18 @@ impl WebSocketConfig {
 
     /// Set [`Self::max_message_size`].
     pub fn max_message_size(mut self, size: Option<usize>) -> Self {
-        assert!(if size.is_some() { size.unwrap() > 0 } else { true });
+        if let Some(size) = size {
+            assert!(size > 0);
+        }
         self.max_message_size = size;
         self
     }
 
     /// Set [`Self::max_frame_size`].
     pub fn max_frame_size(mut self, size: Option<usize>) -> Self {
-        assert!(if size.is_some() { size.unwrap() > 0 } else { true });
+        if let Some(size) = size {
+            assert!(size > 0);
+        }
         self.max_frame_size = size;
         self
     }
@@ -120,6 +238,169 @@ impl WebSocketConfig {
         self
     }
 
+    /// Set [`Self::accept_masked_frames_from_server`].
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::{error::{Error, ProtocolError}, protocol::{config::WebSocketConfig, message::Message, websocket::{OperationMode, WebSocket}}};
+    /// // "Hello" masked with the key from RFC 6455, section 5.7.
+    /// let frame = b"\x81\x85\x37\xfa\x21\x3d\x7f\x9f\x4d\x51\x58".to_vec();
+    ///
+    /// let mut socket = WebSocket::new(Cursor::new(frame.clone()), OperationMode::Client, None);
+    /// assert!(matches!(socket.read(), Err(Error::Protocol(ProtocolError::MaskedFrameFromServer))));
+    ///
+    /// let config = WebSocketConfig::default().accept_masked_frames_from_server(true);
+    /// let mut socket = WebSocket::new(Cursor::new(frame), OperationMode::Client, Some(config));
+    /// assert_eq!(socket.read().unwrap(), Message::Text("Hello".into()));
+    /// ```
+    pub fn accept_masked_frames_from_server(mut self, accept: bool) -> Self {
+        self.accept_masked_frames_from_server = accept;
+        self
+    }
+
+    /// Set [`Self::compression`].
+    ///
+    /// # Example
+    /// ```
+    /// # use blitz_ws::protocol::{compression::WebSocketCompressionConfig, config::WebSocketConfig};
+    /// let config = WebSocketConfig::default().compression(
+    ///     WebSocketCompressionConfig::default()
+    ///         .client_no_context_takeover(true)
+    ///         .server_max_window_bits(Some(10)),
+    /// );
+    ///
+    /// assert!(config.compression.client_no_context_takeover);
+    /// assert_eq!(config.compression.server_max_window_bits, Some(10));
+    /// ```
+    pub fn compression(mut self, compression: WebSocketCompressionConfig) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Set [`Self::control_events`].
+    pub fn control_events(mut self, control_events: bool) -> Self {
+        self.control_events = control_events;
+        self
+    }
+
+    /// Set [`Self::defer_control_during_fragment`].
+    pub fn defer_control_during_fragment(mut self, defer: bool) -> Self {
+        self.defer_control_during_fragment = defer;
+        self
+    }
+
+    /// Set [`Self::auto_pong`].
+    pub fn auto_pong(mut self, auto_pong: bool) -> Self {
+        self.auto_pong = auto_pong;
+        self
+    }
+
+    /// Set [`Self::max_pending_pongs`].
+    pub fn max_pending_pongs(mut self, max_pending_pongs: usize) -> Self {
+        assert!(max_pending_pongs > 0);
+        self.max_pending_pongs = max_pending_pongs;
+        self
+    }
+
+    /// Set [`Self::user_agent`].
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::{self, Cursor, Read, Write};
+    /// # use blitz_ws::{
+    /// #     accept_with_config, client::client_with_config, protocol::config::WebSocketConfig,
+    /// # };
+    /// # struct Stream { input: Cursor<Vec<u8>>, output: Vec<u8> }
+    /// # impl Read for Stream {
+    /// #     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.input.read(buf) }
+    /// # }
+    /// # impl Write for Stream {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.output.write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// # let request = "GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+    /// #     Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+    /// #     Sec-WebSocket-Version: 13\r\n\r\n";
+    /// let config = WebSocketConfig::default()
+    ///     .user_agent(Some("my-client/1.0"))
+    ///     .server_header(Some("my-server/1.0"));
+    ///
+    /// let mut client = Stream { input: Cursor::new(Vec::new()), output: Vec::new() };
+    /// let _ = client_with_config("ws://localhost/", &mut client, Some(config));
+    /// let sent = String::from_utf8(client.output).unwrap();
+    /// assert!(sent.contains("\r\nUser-Agent: my-client/1.0\r\n"));
+    ///
+    /// let mut server = Stream { input: Cursor::new(request.into()), output: Vec::new() };
+    /// accept_with_config(&mut server, Some(config)).unwrap();
+    /// let sent = String::from_utf8(server.output).unwrap();
+    /// assert!(sent.contains("\r\nserver: my-server/1.0\r\n"));
+    /// ```
+    pub fn user_agent(mut self, user_agent: Option<&'static str>) -> Self {
+        self.user_agent = user_agent;
+        self
+    }
+
+    /// Set [`Self::server_header`].
+    pub fn server_header(mut self, server_header: Option<&'static str>) -> Self {
+        self.server_header = server_header;
+        self
+    }
+
+    /// Set [`Self::date_header`].
+    pub fn date_header(mut self, date_header: bool) -> Self {
+        self.date_header = date_header;
+        self
+    }
+
+    /// Set [`Self::retain_request_headers`].
+    pub fn retain_request_headers(mut self, retain: bool) -> Self {
+        self.retain_request_headers = retain;
+        self
+    }
+
+    /// Set [`Self::passive`].
+    pub fn passive(mut self, passive: bool) -> Self {
+        self.passive = passive;
+        self
+    }
+
+    /// Set [`Self::handshake_max_bytes`].
+    pub fn handshake_max_bytes(mut self, size: usize) -> Self {
+        self.handshake_max_bytes = size;
+        self
+    }
+
+    /// Set [`Self::handshake_max_packets`].
+    pub fn handshake_max_packets(mut self, packets: usize) -> Self {
+        self.handshake_max_packets = packets;
+        self
+    }
+
+    /// Set [`Self::handshake_min_packet_size`].
+    pub fn handshake_min_packet_size(mut self, size: usize) -> Self {
+        self.handshake_min_packet_size = size;
+        self
+    }
+
+    /// Set [`Self::max_headers`].
+    pub fn max_headers(mut self, max_headers: usize) -> Self {
+        self.max_headers = max_headers;
+        self
+    }
+
+    /// Set [`Self::max_messages`].
+    pub fn max_messages(mut self, max_messages: Option<usize>) -> Self {
+        self.max_messages = max_messages;
+        self
+    }
+
+    /// Set [`Self::max_total_bytes`].
+    pub fn max_total_bytes(mut self, max_total_bytes: Option<usize>) -> Self {
+        self.max_total_bytes = max_total_bytes;
+        self
+    }
+
     /// Panic if values are invalid.
     pub(crate) fn asset_valid(&self) {
         assert!(
@@ -0,0 +1,128 @@
+//! WebSocket extensions negotiated with the `Sec-WebSocket-Extensions` header
+
+use std::fmt::Debug;
+
+use crate::{error::Result, protocol::frame::Frame};
+
+/// A WebSocket extension transforming the data frames of a connection, e.g.
+/// [`PerMessageDeflate`](crate::protocol::compression::PerMessageDeflate).
+///
+/// A client offers the extension in its handshake request with [`offer`](Self::offer) and
+/// configures it from the server's reply with [`accept_response`](Self::accept_response), see
+/// `ClientHandshake::start_with_extension`. A server answers an offer with
+/// [`accept_offer`](Self::accept_offer), see `MidHandshake::extension`. Once negotiated, the
+/// extension is installed on the connection, it can also be installed directly with
+/// [`WebSocket::set_extension`](crate::protocol::websocket::WebSocket::set_extension).
+///
+/// Only data frames are passed to the hooks, control frames are sent and received as they are.
+/// An extension using a reserved bit must clear it from the incoming frames it handles, frames
+/// with a reserved bit still set are rejected with
+/// [`ProtocolError::NonZeroReservedBits`](crate::error::ProtocolError::NonZeroReservedBits).
+///
+/// # Example
+/// A trivial extension XOR-ing every payload byte, negotiated over a loopback connection:
+/// ```
+/// # use std::{net::{TcpListener, TcpStream}, thread};
+/// # use blitz_ws::{
+/// #     client::IntoClientRequest,
+/// #     error::Result,
+/// #     handshake::server::NoCallback,
+/// #     protocol::{extension::WebSocketExtension, frame::Frame, message::Message},
+/// #     Bytes, ClientHandshake, ServerHandshake,
+/// # };
+/// #[derive(Debug)]
+/// struct Xor(u8);
+///
+/// impl WebSocketExtension for Xor {
+///     fn name(&self) -> &str {
+///         "x-xor"
+///     }
+///
+///     fn transform_outgoing(&mut self, frame: &mut Frame) {
+///         let payload: Vec<u8> = frame.payload().iter().map(|b| b ^ self.0).collect();
+///         frame.set_payload(payload.into());
+///     }
+///
+///     fn transform_incoming(&mut self, frame: &mut Frame, _max_size: Option<usize>) -> Result<()> {
+///         self.transform_outgoing(frame);
+///         Ok(())
+///     }
+/// }
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let addr = listener.local_addr().unwrap();
+///
+/// let server = thread::spawn(move || {
+///     let (stream, _) = listener.accept().unwrap();
+///     let mut ws =
+///         ServerHandshake::start(stream, NoCallback, None).extension(Xor(0x5a)).handshake().unwrap();
+///
+///     // Echo the first message, then return the second one as decoded by the extension.
+///     let msg = ws.read().unwrap();
+///     ws.send(msg).unwrap();
+///     ws.read().unwrap()
+/// });
+///
+/// let request = format!("ws://{addr}/").into_client_request().unwrap();
+/// let stream = TcpStream::connect(addr).unwrap();
+/// let (mut ws, response) = ClientHandshake::start_with_extension(stream, request, None, Xor(0x5a))
+///     .unwrap()
+///     .handshake()
+///     .unwrap();
+/// assert_eq!(response.headers()["Sec-WebSocket-Extensions"], "x-xor");
+/// assert_eq!(ws.extension().unwrap().name(), "x-xor");
+///
+/// ws.send(Message::Binary(Bytes::from_static(b"round trip"))).unwrap();
+/// assert_eq!(ws.read().unwrap(), Message::Binary(Bytes::from_static(b"round trip")));
+///
+/// // Without the extension, the server decodes what we send into something else.
+/// ws.set_extension(None);
+/// ws.send(Message::Binary(Bytes::from_static(b"\x5a\x5a"))).unwrap();
+/// assert_eq!(server.join().unwrap(), Message::Binary(Bytes::from_static(b"\0\0")));
+/// ```
+pub trait WebSocketExtension: Debug + Send {
+    /// The extension token, e.g. `permessage-deflate`.
+    fn name(&self) -> &str;
+
+    /// The offer a client sends in its `Sec-WebSocket-Extensions` header, parameters included.
+    ///
+    /// Defaults to the bare [`name`](Self::name).
+    fn offer(&self) -> String {
+        self.name().to_owned()
+    }
+
+    /// Answer an offer of this extension made by a client with the given parameters.
+    ///
+    /// Returns the value of the `Sec-WebSocket-Extensions` response header accepting the offer,
+    /// or `None` to decline it. Defaults to accepting any offer with the bare
+    /// [`name`](Self::name).
+    fn accept_offer(&mut self, params: &[(String, Option<String>)]) -> Option<String> {
+        let _ = params;
+        Some(self.name().to_owned())
+    }
+
+    /// Configure the extension from the parameters the server accepted it with.
+    ///
+    /// Returning an error fails the client handshake. Defaults to ignoring the parameters.
+    fn accept_response(&mut self, params: &[(String, Option<String>)]) -> Result<()> {
+        let _ = params;
+        Ok(())
+    }
+
+    /// Transform a data frame before it is sent.
+    fn transform_outgoing(&mut self, frame: &mut Frame);
+
+    /// Transform a data frame after it is received, before it is checked and assembled into a
+    /// message.
+    ///
+    /// Returning an error fails the connection like a protocol violation of the peer. An
+    /// extension expanding the payload must not let it grow past `max_size` bytes, the room
+    /// left under [`WebSocketConfig::max_message_size`] and
+    /// [`WebSocketConfig::max_frame_size`], and fail with
+    /// [`CapacityError::MessageTooLarge`](crate::error::CapacityError::MessageTooLarge)
+    /// instead. Otherwise a small frame could expand until memory is exhausted.
+    ///
+    /// [`WebSocketConfig::max_message_size`]: crate::protocol::config::WebSocketConfig::max_message_size
+    /// [`WebSocketConfig::max_frame_size`]: crate::protocol::config::WebSocketConfig::max_frame_size
+    fn transform_incoming(&mut self, frame: &mut Frame, max_size: Option<usize>) -> Result<()>;
+}
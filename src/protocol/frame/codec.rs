@@ -103,6 +103,23 @@ impl From<u8> for OpCode {
 }
 
 /// Status code used to indicate why an endpoint is closing the WebSocket connection.
+///
+/// Converts from and into the `u16` sent on the wire. Codes without a named variant are kept
+/// as they are, e.g. the codes registered with IANA in the `3000..=3999` range.
+///
+/// # Example
+/// ```
+/// # use blitz_ws::protocol::frame::codec::CloseCode;
+/// for (code, named) in [(1012, CloseCode::Restart), (1013, CloseCode::Again), (1014, CloseCode::BadGateway)] {
+///     assert_eq!(CloseCode::from(code), named);
+///     assert_eq!(u16::from(named), code);
+///     assert!(named.allowed());
+/// }
+///
+/// let registered = CloseCode::from(3000);
+/// assert_eq!(registered.description(), "registered with IANA");
+/// assert_eq!(u16::from(registered), 3000);
+/// ```
 #[repr(u16)]
 #[non_exhaustive]
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -125,16 +142,16 @@ pub enum CloseCode {
     /// receives a binary message).
     Unsupported = 0x3EB,
 
-    /// Indicates that no status code was included in a closing frame. This
-    /// close code makes it possible to use a single method, `on_close` to
-    /// handle even cases where no close code was provided.
+    /// Indicates that no status code was included in a closing frame.
+    ///
+    /// This is a pseudo-code for reporting such a close locally, it is never sent on the wire.
+    /// [`Frame::new_close`](super::Frame::new_close) sends a close frame without payload instead.
     Status = 0x3ED,
 
-    /// Indicates an abnormal closure. If the abnormal closure was due to an
-    /// error, this close code will not be used. Instead, the `on_error` method
-    /// of the handler will be called with the error. However, if the connection
-    /// is simply dropped, without an error, this close code will be sent to the
-    /// handler.
+    /// Indicates that the connection was closed abnormally, without a close frame.
+    ///
+    /// This is a pseudo-code for reporting such a close locally, it is never sent on the wire.
+    /// [`Frame::new_close`](super::Frame::new_close) sends a close frame without payload instead.
     Abnormal = 0x3EE,
 
     /// Indicates that an endpoint is terminating the connection
@@ -175,10 +192,17 @@ pub enum CloseCode {
 
     /// Indicates that the server is overloaded and the client should either connect
     /// to a different IP (when multiple targets exist), or reconnect to the same IP
-    /// when a user has performed an action.
+    /// when a user has performed an action. Registered as "Try Again Later".
     Again = 0x3F5,
 
-    #[doc(hidden)]
+    /// Indicates that the server was acting as a gateway or proxy and received an
+    /// invalid response from the upstream server.
+    BadGateway = 0x3F6,
+
+    /// Indicates that the connection was closed because the TLS handshake failed.
+    ///
+    /// This is a pseudo-code for reporting such a close locally, it is never sent on the wire.
+    /// [`Frame::new_close`](super::Frame::new_close) sends a close frame without payload instead.
     Tls = 0x3F7,
 
     #[doc(hidden)]
@@ -202,6 +226,54 @@ impl CloseCode {
             Self::Bad(_) | Self::Reserved(_) | Self::Status | Self::Abnormal | Self::Tls
         )
     }
+
+    /// Check if RFC 6455 reserves this code, either for future use or for signaling a close
+    /// without a code locally. Reserved codes are never sent on the wire.
+    pub fn is_reserved(self) -> bool {
+        matches!(self, Self::Reserved(_) | Self::Status | Self::Abnormal | Self::Tls)
+    }
+
+    /// Check if this is one of the codes that only report a close locally: [`Self::Status`],
+    /// [`Self::Abnormal`] and [`Self::Tls`].
+    pub fn is_pseudo(self) -> bool {
+        matches!(self, Self::Status | Self::Abnormal | Self::Tls)
+    }
+
+    /// Returns the meaning of this code, e.g. for logging why a peer disconnected.
+    ///
+    /// # Example
+    /// ```
+    /// # use blitz_ws::protocol::frame::codec::CloseCode;
+    /// assert_eq!(CloseCode::Normal.description(), "normal closure");
+    /// assert_eq!(CloseCode::from(1001).description(), "going away");
+    /// assert_eq!(CloseCode::from(1008).description(), "policy violation");
+    /// assert_eq!(CloseCode::from(4000).description(), "private use");
+    /// assert!(CloseCode::from(1005).is_reserved());
+    /// assert!(!CloseCode::from(3000).is_reserved());
+    /// ```
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal closure",
+            Self::Away => "going away",
+            Self::Protocol => "protocol error",
+            Self::Unsupported => "unsupported data",
+            Self::Status => "no status received",
+            Self::Abnormal => "abnormal closure",
+            Self::Invalid => "invalid frame payload data",
+            Self::Policy => "policy violation",
+            Self::Size => "message too big",
+            Self::Extension => "mandatory extension",
+            Self::Error => "internal error",
+            Self::Restart => "service restart",
+            Self::Again => "try again later",
+            Self::BadGateway => "bad gateway",
+            Self::Tls => "TLS handshake failure",
+            Self::Reserved(_) => "reserved",
+            Self::Iana(_) => "registered with IANA",
+            Self::Library(_) => "private use",
+            Self::Bad(_) => "invalid close code",
+        }
+    }
 }
 
 impl Display for CloseCode {
@@ -227,6 +299,7 @@ impl From<CloseCode> for u16 {
             self::CloseCode::Error => 0x3F3,
             self::CloseCode::Restart => 0x3F4,
             self::CloseCode::Again => 0x3F5,
+            self::CloseCode::BadGateway => 0x3F6,
             self::CloseCode::Tls => 0x3F7,
             self::CloseCode::Bad(other) => other,
             self::CloseCode::Reserved(other) => other,
@@ -258,6 +331,7 @@ impl From<u16> for CloseCode {
             0x3F3 => Self::Error,
             0x3F4 => Self::Restart,
             0x3F5 => Self::Again,
+            0x3F6 => Self::BadGateway,
             0x3F7 => Self::Tls,
             0x1..=0x3E7 => Self::Bad(value),
             0x3F8..=0xBB7 => Self::Reserved(value),
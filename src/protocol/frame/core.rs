@@ -1,18 +1,33 @@
 //! Utilities to work with raw WebSocket frames.
 
-use std::io::{self, Cursor, Read, Write};
+use std::{
+    collections::VecDeque,
+    io::{self, Cursor, IoSlice, Read, Write},
+    mem::take,
+};
 
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
 
 use crate::{
     error::{CapacityError, Error, ProtocolError, Result},
     protocol::frame::{
+        codec::{Data, OpCode},
         frame::{Frame, FrameHeader},
         mask::apply_mask,
     },
+    MAX_CONTROL_FRAME_PAYLOAD,
 };
 
 const READ_BUFFER_LENGTH: usize = 128 * 1024;
+/// Unmasked payloads from this size on are written from their own buffer instead of being
+/// copied into the out buffer.
+const MIN_ZERO_COPY_PAYLOAD: usize = 4 * 1024;
+/// Maximum number of buffers passed to a single vectored write.
+const MAX_IO_SLICES: usize = 64;
+/// Maximum number of payload bytes reserved before they are received. The buffer grows as the
+/// rest of a larger payload arrives, so the length in a header alone cannot cause a huge
+/// allocation.
+const MAX_PAYLOAD_RESERVE: usize = 16 * 1024 * 1024;
 
 /// Read buffer size used for `FrameSocket`.
 #[derive(Debug)]
@@ -21,22 +36,28 @@ pub struct FrameSocket<T> {
     stream: T,
     /// Codec for reading/writing frames.
     codec: FrameCodec,
+    /// Data frames of the message being reassembled by `read_message`.
+    incomplete: Option<(Data, BytesMut)>,
 }
 
 impl<T: Read + Write> FrameSocket<T> {
     /// Create a new frame socket.
     pub fn new(stream: T) -> Self {
-        FrameSocket { stream, codec: FrameCodec::new(READ_BUFFER_LENGTH) }
+        FrameSocket { stream, codec: FrameCodec::new(READ_BUFFER_LENGTH), incomplete: None }
     }
 
     /// Create a new frame socket from partially read data.
     pub fn from_partially_read(stream: T, part: Vec<u8>) -> Self {
-        FrameSocket { stream, codec: FrameCodec::from_partially_read(part, READ_BUFFER_LENGTH) }
+        FrameSocket {
+            stream,
+            codec: FrameCodec::from_partially_read(part, READ_BUFFER_LENGTH),
+            incomplete: None,
+        }
     }
 
-    /// Extract a stream from the socket.
+    /// Extract a stream from the socket, along with the data read but not parsed yet.
     pub fn into_inner(self) -> (T, BytesMut) {
-        (self.stream, self.codec.in_buffer)
+        (self.stream, self.codec.into_unparsed())
     }
 
     /// Returns a shared reference to the inner stream.
@@ -50,10 +71,76 @@ impl<T: Read + Write> FrameSocket<T> {
     }
 
     /// Read a frame from stream.
+    ///
+    /// A frame with a payload larger than `max` fails with [`CapacityError::MessageTooLarge`]
+    /// as soon as its header is read. Without `max`, memory is only allocated as the payload
+    /// arrives, whatever length the header claims.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::{error::{CapacityError, Error}, protocol::frame::core::FrameSocket};
+    /// // The header of a binary frame claiming a 2 GiB payload.
+    /// let header = [&[0x82, 0x7f][..], &(2u64 << 30).to_be_bytes()].concat();
+    ///
+    /// let mut socket = FrameSocket::new(Cursor::new(header.clone()));
+    /// assert!(matches!(
+    ///     socket.read(Some(64 << 20)),
+    ///     Err(Error::Capacity(CapacityError::MessageTooLarge { size: 2147483648, .. }))
+    /// ));
+    ///
+    /// let mut socket = FrameSocket::new(Cursor::new(header));
+    /// assert!(socket.read(None).unwrap().is_none());
+    /// ```
     pub fn read(&mut self, max: Option<usize>) -> Result<Option<Frame>> {
         self.codec.read(&mut self.stream, max, false, true)
     }
 
+    /// Read a complete message from stream, reassembling fragmented data frames.
+    ///
+    /// Returns the opcode of the first frame of the message along with the whole payload.
+    /// Control frames are returned as soon as they are read, also in between the fragments
+    /// of a message, which the next call then resumes. `max` limits the size of every frame as
+    /// well as of the reassembled message.
+    ///
+    /// Payloads are returned as they are, e.g. text is not validated as UTF-8.
+    pub fn read_message(&mut self, max: Option<usize>) -> Result<Option<(OpCode, Bytes)>> {
+        loop {
+            let frame = match self.read(max)? {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+
+            let fin = frame.header().fin;
+            let opcode = frame.header().opcode;
+            let payload = frame.into_payload();
+
+            match (opcode, self.incomplete.as_mut()) {
+                (OpCode::Control(_), _) => return Ok(Some((opcode, payload))),
+                (OpCode::Data(Data::Continuation), Some((_, buf))) => {
+                    check_message_size(buf.len() + payload.len(), max)?;
+                    buf.extend_from_slice(&payload);
+                }
+                (OpCode::Data(Data::Continuation), None) => {
+                    return Err(Error::Protocol(ProtocolError::UnexpectedContinue))
+                }
+                (OpCode::Data(data), Some(_)) => {
+                    return Err(Error::Protocol(ProtocolError::ExpectedFragment(data)))
+                }
+                (OpCode::Data(_), None) if fin => return Ok(Some((opcode, payload))),
+                (OpCode::Data(data), None) => {
+                    self.incomplete = Some((data, BytesMut::from(&payload[..])));
+                }
+            }
+
+            if fin {
+                if let Some((data, buf)) = self.incomplete.take() {
+                    return Ok(Some((OpCode::Data(data), buf.freeze())));
+                }
+            }
+        }
+    }
+
     /// Writes and immediately flushes a frame.
     /// Equivalent to calling [`write`](Self::write) then [`flush`](Self::flush).
     pub fn send(&mut self, frame: Frame) -> Result<()> {
@@ -72,6 +159,74 @@ impl<T: Read + Write> FrameSocket<T> {
         self.codec.write(&mut self.stream, frame)
     }
 
+    /// Write a message, split into frames of at most `max_frame` payload bytes.
+    ///
+    /// The first frame carries `opcode`, the following ones are continuation frames and only
+    /// the last one has `fin` set. A message that fits into one frame, or any message if
+    /// `max_frame` is `None`, is written as a single frame. Control messages cannot be
+    /// fragmented: one with a payload over 125 bytes fails with
+    /// [`ProtocolError::ControlFrameTooBig`] whatever `max_frame` is, and one that does not fit
+    /// into `max_frame` with [`ProtocolError::FragmentedControlFrame`].
+    ///
+    /// Like [`write`](Self::write), a subsequent call should be made to [`flush`](Self::flush).
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::{
+    /// #     error::{Error, ProtocolError},
+    /// #     protocol::frame::{codec::{Control, Data, OpCode}, core::FrameSocket},
+    /// # };
+    /// let mut socket = FrameSocket::new(Cursor::new(Vec::new()));
+    /// socket.write_message(OpCode::Data(Data::Text), "Hello!".into(), Some(4)).unwrap();
+    /// socket.flush().unwrap();
+    ///
+    /// let mut socket = FrameSocket::new(Cursor::new(socket.into_inner().0.into_inner()));
+    ///
+    /// let first = socket.read(None).unwrap().unwrap();
+    /// assert_eq!(first.header().opcode, OpCode::Data(Data::Text));
+    /// assert!(!first.header().fin);
+    /// assert_eq!(first.payload(), b"Hell");
+    ///
+    /// let last = socket.read(None).unwrap().unwrap();
+    /// assert_eq!(last.header().opcode, OpCode::Data(Data::Continuation));
+    /// assert!(last.header().fin);
+    /// assert_eq!(last.payload(), b"o!");
+    ///
+    /// // Control frames are limited to 125 bytes, even if no frame size limit is given.
+    /// let err = socket.write_message(OpCode::Control(Control::Ping), vec![0; 200].into(), None);
+    /// assert!(matches!(err, Err(Error::Protocol(ProtocolError::ControlFrameTooBig))));
+    /// ```
+    pub fn write_message(
+        &mut self,
+        opcode: OpCode,
+        mut payload: Bytes,
+        max_frame: Option<usize>,
+    ) -> Result<()> {
+        let max_frame = max_frame.unwrap_or(usize::MAX).max(1);
+        if let OpCode::Control(_) = opcode {
+            if payload.len() > MAX_CONTROL_FRAME_PAYLOAD {
+                return Err(Error::Protocol(ProtocolError::ControlFrameTooBig));
+            }
+            if payload.len() > max_frame {
+                return Err(Error::Protocol(ProtocolError::FragmentedControlFrame));
+            }
+        }
+
+        let mut opcode = opcode;
+        loop {
+            let chunk = payload.split_to(payload.len().min(max_frame));
+            let fin = payload.is_empty();
+
+            self.write(Frame::new(FrameHeader { fin, opcode, ..Default::default() }, chunk))?;
+            if fin {
+                return Ok(());
+            }
+
+            opcode = OpCode::Data(Data::Continuation);
+        }
+    }
+
     /// Flush writes.
     pub fn flush(&mut self) -> Result<()> {
         self.codec.write_out(&mut self.stream)?;
@@ -79,6 +234,15 @@ impl<T: Read + Write> FrameSocket<T> {
     }
 }
 
+fn check_message_size(size: usize, max: Option<usize>) -> Result<()> {
+    match max {
+        Some(max) if size > max => {
+            Err(Error::Capacity(CapacityError::MessageTooLarge { size, max }))
+        }
+        _ => Ok(()),
+    }
+}
+
 /// A codec for WebSocket frames.
 #[derive(Debug)]
 pub(crate) struct FrameCodec {
@@ -87,6 +251,10 @@ pub(crate) struct FrameCodec {
     in_buffer_max_read: usize,
     /// Buffer to send packets to the network.
     out_buffer: Vec<u8>,
+    /// Buffers queued to be sent before `out_buffer`, for payloads that are not copied.
+    out_chunks: VecDeque<Bytes>,
+    /// Total length of `out_chunks`.
+    out_chunks_len: usize,
     /// Capacity limit for `out_buffer`.
     max_out_buffer_len: usize,
     /// Buffer target length to reach before writing to the stream
@@ -97,6 +265,8 @@ pub(crate) struct FrameCodec {
     out_buffer_write_len: usize,
     /// Header and remaining size of the incoming packet being processed.
     header: Option<(FrameHeader, u64)>,
+    /// Size on the wire (header and payload) of the last frame returned by `read`.
+    last_read_len: usize,
 }
 
 impl FrameCodec {
@@ -106,9 +276,12 @@ impl FrameCodec {
             in_buffer: BytesMut::with_capacity(len),
             in_buffer_max_read: len.max(FrameHeader::MAX_HEADER_SIZE),
             out_buffer: <_>::default(),
+            out_chunks: <_>::default(),
+            out_chunks_len: 0,
             max_out_buffer_len: usize::MAX,
             out_buffer_write_len: 0,
             header: None,
+            last_read_len: 0,
         }
     }
 
@@ -121,12 +294,37 @@ impl FrameCodec {
             in_buffer: buf,
             in_buffer_max_read: min_in_buffer_len.max(FrameHeader::MAX_HEADER_SIZE),
             out_buffer: <_>::default(),
+            out_chunks: <_>::default(),
+            out_chunks_len: 0,
             max_out_buffer_len: usize::MAX,
             out_buffer_write_len: 0,
             header: None,
+            last_read_len: 0,
+        }
+    }
+
+    /// Returns the data read but not returned as a frame yet, as received on the wire.
+    ///
+    /// The header of a frame whose payload is incomplete was already parsed, it is formatted
+    /// again in front of the payload.
+    pub(crate) fn into_unparsed(self) -> BytesMut {
+        match self.header {
+            Some((header, len)) => {
+                let mut buf = Vec::with_capacity(header.len(len) + self.in_buffer.len());
+                header.format(len, &mut buf).expect("Bug: can't write to vector");
+                buf.extend_from_slice(&self.in_buffer);
+                buf.as_slice().into()
+            }
+            None => self.in_buffer,
         }
     }
 
+    /// Sets the maximum number of bytes read from the stream at once.
+    pub(crate) fn in_buffer_max_read(&mut self, size: usize) {
+        self.in_buffer_max_read = size.max(FrameHeader::MAX_HEADER_SIZE);
+        self.in_buffer.reserve(self.in_buffer_max_read.saturating_sub(self.in_buffer.len()));
+    }
+
     /// Sets a maximum size for the out buffer.
     pub(crate) fn max_out_buffer_len(&mut self, size: usize) {
         self.max_out_buffer_len = size
@@ -156,7 +354,7 @@ impl FrameCodec {
                 Buf::advance(&mut self.in_buffer, n as _);
 
                 if let Some((_, len)) = &self.header {
-                    let len = *len as usize;
+                    let len = usize::try_from(*len).unwrap_or(usize::MAX);
 
                     if len > max {
                         return Err(Error::Capacity(CapacityError::MessageTooLarge {
@@ -165,15 +363,17 @@ impl FrameCodec {
                         }));
                     }
 
-                    self.in_buffer.reserve(len);
+                    self.in_buffer.reserve(len.min(MAX_PAYLOAD_RESERVE));
                 } else {
                     self.in_buffer.reserve(FrameHeader::MAX_HEADER_SIZE);
                 }
             }
 
             if let Some((_, len)) = &self.header {
-                let len = *len as usize;
+                let len = usize::try_from(*len).unwrap_or(usize::MAX);
                 if len <= self.in_buffer.len() {
+                    // Splitting keeps the payload in the allocation it was read into, so single
+                    // frame messages reach the caller without being copied.
                     break self.in_buffer.split_to(len);
                 }
             }
@@ -185,6 +385,15 @@ impl FrameCodec {
 
         let (mut header, length) = self.header.take().expect("Bug: no frame header");
         debug_assert_eq!(payload.len() as u64, length);
+        self.last_read_len = header.len(length) + payload.len();
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            opcode = %header.opcode,
+            payload_len = payload.len(),
+            masked = header.mask.is_some(),
+            "frame read"
+        );
 
         if unmask {
             if let Some(mask) = header.mask.take() {
@@ -198,6 +407,16 @@ impl FrameCodec {
         Ok(Some(frame))
     }
 
+    /// Check if part of a frame was read, its header or payload being incomplete.
+    pub(crate) fn has_partial_frame(&self) -> bool {
+        self.header.is_some() || !self.in_buffer.is_empty()
+    }
+
+    /// Size on the wire (header and payload) of the last frame returned by [`Self::read`].
+    pub(crate) fn last_read_len(&self) -> usize {
+        self.last_read_len
+    }
+
     /// Read into available `in_buffer` capacity.
     fn read_in<S: Read>(&mut self, stream: &mut S) -> io::Result<usize> {
         let len = self.in_buffer.len();
@@ -215,30 +434,75 @@ impl FrameCodec {
     /// If the out buffer size is over the `out_buffer_write_len` will also write
     /// the out buffer into the provided `stream`.
     ///
-    /// To ensure buffered frames are written call [`Self::write_out_buffer`].
+    /// Large unmasked payloads are not copied, they are queued as they are and written
+    /// along with the buffered headers using vectored writes.
+    ///
+    /// To ensure buffered frames are written call [`Self::write_out`].
     ///
     /// May write to the stream, will **not** flush.
     pub(crate) fn write<S: Write>(&mut self, stream: &mut S, frame: Frame) -> Result<()> {
-        if frame.len() + self.out_buffer.len() > self.max_out_buffer_len {
+        if frame.len() + self.out_len() > self.max_out_buffer_len {
             return Err(Error::WriteBufferFull);
         }
 
-        self.out_buffer.reserve(frame.len());
-        frame.into_buf(&mut self.out_buffer).expect("Bug: can't write to vector");
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            opcode = %frame.header().opcode,
+            payload_len = frame.payload().len(),
+            masked = frame.is_masked(),
+            "frame written"
+        );
+
+        if !frame.is_masked() && frame.payload().len() >= MIN_ZERO_COPY_PAYLOAD {
+            let len = frame.payload().len() as u64;
+            frame.header().format(len, &mut self.out_buffer)?;
+
+            let header = Bytes::from(take(&mut self.out_buffer));
+            self.queue_chunk(header);
+            self.queue_chunk(frame.into_payload());
+        } else {
+            self.out_buffer.reserve(frame.len());
+            frame.into_buf(&mut self.out_buffer).expect("Bug: can't write to vector");
+        }
 
-        if self.out_buffer.len() > self.out_buffer_write_len {
+        if self.out_len() > self.out_buffer_write_len {
             self.write_out(stream)
         } else {
             Ok(())
         }
     }
 
+    /// Total length of the data waiting to be written to the stream.
+    pub(crate) fn out_len(&self) -> usize {
+        self.out_chunks_len + self.out_buffer.len()
+    }
+
+    fn queue_chunk(&mut self, chunk: Bytes) {
+        if !chunk.is_empty() {
+            self.out_chunks_len += chunk.len();
+            self.out_chunks.push_back(chunk);
+        }
+    }
+
     /// Writes the out_buffer to the provided stream.
     ///
     /// Does **not** flush.
     pub(crate) fn write_out<S: Write>(&mut self, stream: &mut S) -> Result<()> {
-        while !self.out_buffer.is_empty() {
-            let len = stream.write(&self.out_buffer)?;
+        while self.out_len() > 0 {
+            let len = if self.out_chunks.is_empty() {
+                stream.write(&self.out_buffer)?
+            } else {
+                let mut slices = [IoSlice::new(&[]); MAX_IO_SLICES];
+                let chunks = self.out_chunks.iter().map(|chunk| &chunk[..]);
+                let mut count = 0;
+
+                for (slice, data) in slices.iter_mut().zip(chunks.chain([&self.out_buffer[..]])) {
+                    *slice = IoSlice::new(data);
+                    count += 1;
+                }
+
+                stream.write_vectored(&slices[..count])?
+            };
 
             if len == 0 {
                 return Err(io::Error::new(
@@ -248,9 +512,31 @@ impl FrameCodec {
                 .into());
             }
 
-            self.out_buffer.drain(0..len);
+            self.consume_out(len);
         }
 
         Ok(())
     }
+
+    /// Drop `len` written bytes from the front of the queued data.
+    fn consume_out(&mut self, mut len: usize) {
+        while len > 0 {
+            match self.out_chunks.front_mut() {
+                Some(chunk) if chunk.len() <= len => {
+                    len -= chunk.len();
+                    self.out_chunks_len -= chunk.len();
+                    self.out_chunks.pop_front();
+                }
+                Some(chunk) => {
+                    chunk.advance(len);
+                    self.out_chunks_len -= len;
+                    len = 0;
+                }
+                None => {
+                    self.out_buffer.drain(0..len);
+                    len = 0;
+                }
+            }
+        }
+    }
 }
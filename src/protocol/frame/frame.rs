@@ -5,7 +5,7 @@ use std::{
     io::{Cursor, ErrorKind, Read, Write},
     mem,
     result::Result as StdResult,
-    str::Utf8Error,
+    str::{FromStr, Utf8Error},
 };
 
 use bytes::{Bytes, BytesMut};
@@ -17,6 +17,7 @@ use super::{
 use crate::{
     error::{Error, ProtocolError, Result},
     protocol::frame::Utf8Bytes,
+    MAX_CONTROL_FRAME_PAYLOAD,
 };
 
 /// A struct representing the close command.
@@ -34,6 +35,52 @@ impl Display for CloseFrame {
     }
 }
 
+impl FromStr for CloseFrame {
+    type Err = Error;
+
+    /// Parse a close frame from `"code:reason"`, e.g. `"1000:bye"`. The reason may be left out
+    /// along with the colon.
+    ///
+    /// Fails with [`ProtocolError::InvalidCloseFrame`] if the code is not a number and with
+    /// [`ProtocolError::InvalidCloseCode`] if the code must not be sent.
+    ///
+    /// ```
+    /// # use blitz_ws::protocol::frame::{codec::CloseCode, CloseFrame};
+    /// let frame: CloseFrame = "1000:bye".parse().unwrap();
+    /// assert_eq!(frame.code, CloseCode::Normal);
+    /// assert_eq!(frame.reason, "bye");
+    ///
+    /// let frame: CloseFrame = "1001".parse().unwrap();
+    /// assert_eq!(frame.code, CloseCode::Away);
+    /// assert_eq!(frame.reason, "");
+    ///
+    /// assert!("bye".parse::<CloseFrame>().is_err());
+    /// assert!("1005:no status".parse::<CloseFrame>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self> {
+        let (code, reason) = s.split_once(':').unwrap_or((s, ""));
+        let code = code
+            .trim()
+            .parse::<u16>()
+            .map(CloseCode::from)
+            .map_err(|_| Error::Protocol(ProtocolError::InvalidCloseFrame))?;
+
+        if !code.allowed() {
+            return Err(Error::Protocol(ProtocolError::InvalidCloseCode(code)));
+        }
+
+        Ok(Self { code, reason: reason.into() })
+    }
+}
+
+impl TryFrom<&str> for CloseFrame {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        value.parse()
+    }
+}
+
 /// A struct representing a WebSocket frame header.
 #[allow(missing_copy_implementations)]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -55,7 +102,7 @@ pub struct FrameHeader {
 impl Default for FrameHeader {
     fn default() -> Self {
         FrameHeader {
-            fin: false,
+            fin: true,
             rsv1: false,
             rsv2: false,
             rsv3: false,
@@ -161,7 +208,7 @@ impl FrameHeader {
             if particular_len > 0 {
                 const SIZE: usize = mem::size_of::<u64>();
                 assert!(
-                    particular_len < SIZE,
+                    particular_len <= SIZE,
                     "Length exceeded max size of unsigned 64-bit integer"
                 );
 
@@ -241,12 +288,70 @@ impl Frame {
         &mut self.header
     }
 
+    /// Check if this is a text frame, i.e. the first frame of a text message.
+    ///
+    /// # Example
+    /// ```
+    /// # use blitz_ws::protocol::frame::{codec::{Data, OpCode}, Frame};
+    /// let text = Frame::new_data("Hello", OpCode::Data(Data::Text), false);
+    /// assert!(text.is_text() && !text.is_binary() && !text.is_continuation());
+    ///
+    /// let rest = Frame::new_data("!", OpCode::Data(Data::Continuation), true);
+    /// assert!(rest.is_continuation() && !rest.is_text());
+    ///
+    /// assert!(Frame::new_data(vec![0], OpCode::Data(Data::Binary), true).is_binary());
+    /// assert!(Frame::new_ping("").is_ping());
+    /// assert!(Frame::new_pong("").is_pong());
+    /// assert!(Frame::new_close(None).is_close() && !Frame::new_close(None).is_ping());
+    /// ```
+    #[inline]
+    pub fn is_text(&self) -> bool {
+        self.header.opcode == OpCode::Data(Data::Text)
+    }
+
+    /// Check if this is a binary frame, i.e. the first frame of a binary message.
+    #[inline]
+    pub fn is_binary(&self) -> bool {
+        self.header.opcode == OpCode::Data(Data::Binary)
+    }
+
+    /// Check if this is a continuation frame of a fragmented message.
+    #[inline]
+    pub fn is_continuation(&self) -> bool {
+        self.header.opcode == OpCode::Data(Data::Continuation)
+    }
+
+    /// Check if this is a ping frame.
+    #[inline]
+    pub fn is_ping(&self) -> bool {
+        self.header.opcode == OpCode::Control(Control::Ping)
+    }
+
+    /// Check if this is a pong frame.
+    #[inline]
+    pub fn is_pong(&self) -> bool {
+        self.header.opcode == OpCode::Control(Control::Pong)
+    }
+
+    /// Check if this is a close frame.
+    #[inline]
+    pub fn is_close(&self) -> bool {
+        self.header.opcode == OpCode::Control(Control::Close)
+    }
+
     /// Get a reference to the frame's payload.
     #[inline]
     pub fn payload(&self) -> &[u8] {
         &self.payload
     }
 
+    /// Replace the frame's payload, e.g. to transform it in a
+    /// [`WebSocketExtension`](crate::protocol::extension::WebSocketExtension).
+    #[inline]
+    pub fn set_payload(&mut self, payload: Bytes) {
+        self.payload = payload;
+    }
+
     /// Test whether the frame is masked.
     #[inline]
     pub(crate) fn is_masked(&self) -> bool {
@@ -322,12 +427,40 @@ impl Frame {
     }
 
     /// Create a new Close control frame.
+    ///
+    /// The payload is empty if there is no close frame or if its code is one of the pseudo-codes
+    /// that must not be sent on the wire (see [`CloseCode::is_pseudo`]).
+    ///
+    /// A reason that does not fit in a control frame is truncated to at most 123 bytes, on a
+    /// character boundary so the payload stays valid UTF-8.
+    ///
+    /// # Example
+    /// ```
+    /// # use blitz_ws::protocol::frame::{codec::CloseCode, CloseFrame, Frame};
+    /// let frame = Frame::new_close(Some(CloseFrame { code: CloseCode::from(1005), reason: "".into() }));
+    /// assert!(frame.payload().is_empty());
+    ///
+    /// let frame = Frame::new_close(Some(CloseFrame { code: CloseCode::Normal, reason: "".into() }));
+    /// assert_eq!(frame.payload(), [0x03, 0xe8]);
+    ///
+    /// // 100 two-byte characters.
+    /// let reason = "é".repeat(100);
+    /// let frame = Frame::new_close(Some(CloseFrame { code: CloseCode::Normal, reason: reason.into() }));
+    /// assert_eq!(frame.payload().len(), 124);
+    /// assert!(std::str::from_utf8(&frame.payload()[2..]).is_ok());
+    /// ```
     #[inline]
     pub fn new_close(msg: Option<CloseFrame>) -> Frame {
-        let payload = if let Some(CloseFrame { code, reason }) = msg {
-            let mut p = BytesMut::with_capacity(reason.len() + 2);
+        let payload = if let Some(CloseFrame { code, reason }) = msg.filter(|f| !f.code.is_pseudo())
+        {
+            let mut end = reason.len().min(MAX_CONTROL_FRAME_PAYLOAD - 2);
+            while !reason.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            let mut p = BytesMut::with_capacity(end + 2);
             p.extend(u16::from(code).to_be_bytes());
-            p.extend_from_slice(reason.as_bytes());
+            p.extend_from_slice(&reason.as_bytes()[..end]);
             p
         } else {
             <_>::default()
@@ -336,14 +469,150 @@ impl Frame {
         Frame { header: <_>::default(), payload: payload.into() }
     }
 
+    /// Number of payload bytes dumped by the `Display` implementation.
+    const DISPLAY_DUMP_BYTES: usize = 256;
+
+    /// Dump the payload in the format of `hexdump -C`, e.g. for logging unexpected frames.
+    ///
+    /// Each line shows the offset, up to 16 bytes in hex and the same bytes as ASCII, with
+    /// unprintable bytes replaced by `.`. At most `max_bytes` bytes are dumped, a last line
+    /// tells how many bytes were left out.
+    ///
+    /// # Example
+    /// ```
+    /// # use blitz_ws::protocol::frame::Frame;
+    /// let frame = Frame::new_ping("Hello,\nWebSocket world!");
+    ///
+    /// assert_eq!(
+    ///     frame.dump(20),
+    ///     "00000000  48 65 6c 6c 6f 2c 0a 57  65 62 53 6f 63 6b 65 74  |Hello,.WebSocket|\n\
+    ///      00000010  20 77 6f 72                                       | wor|\n\
+    ///      ... 3 more bytes\n"
+    /// );
+    /// ```
+    pub fn dump(&self, max_bytes: usize) -> String {
+        use std::fmt::Write;
+
+        let shown = &self.payload[..self.payload.len().min(max_bytes)];
+        let mut out = String::new();
+
+        for (line, chunk) in shown.chunks(16).enumerate() {
+            _ = write!(out, "{:08x}  ", line * 16);
+            for i in 0..16 {
+                if i == 8 {
+                    out.push(' ');
+                }
+                match chunk.get(i) {
+                    Some(byte) => _ = write!(out, "{byte:02x} "),
+                    None => out.push_str("   "),
+                }
+            }
+
+            out.push_str(" |");
+            out.extend(chunk.iter().map(|&b| {
+                if b == b' ' || b.is_ascii_graphic() {
+                    b as char
+                } else {
+                    '.'
+                }
+            }));
+            out.push_str("|\n");
+        }
+
+        if shown.len() < self.payload.len() {
+            _ = writeln!(out, "... {} more bytes", self.payload.len() - shown.len());
+        }
+
+        out
+    }
+
     /// Initializes a new frame
     pub fn new(header: FrameHeader, payload: Bytes) -> Self {
         Frame { header, payload }
     }
 
+    /// Parse a single frame from the start of `data`, e.g. to feed the parser from a fuzzer.
+    ///
+    /// Returns the frame along with the number of bytes it took, or `None` if `data` does not
+    /// hold a whole frame yet. The payload of a masked frame is unmasked, the mask is kept in
+    /// the header. Unlike reading from a connection, no size limit or masking rule applies.
+    ///
+    /// # Example
+    /// ```
+    /// # use blitz_ws::{error::{Error, ProtocolError}, protocol::frame::{codec::{Data, OpCode}, Frame}};
+    /// // The three length encodings, masked and unmasked.
+    /// for size in [0, 5, 126, 65536] {
+    ///     for mask in [None, Some([1, 2, 3, 4])] {
+    ///         let mut frame = Frame::new_data(vec![7; size], OpCode::Data(Data::Binary), true);
+    ///         frame.header_mut().mask = mask;
+    ///
+    ///         let mut data = Vec::new();
+    ///         frame.clone().format_to_buf(&mut data).unwrap();
+    ///         let len = data.len();
+    ///
+    ///         // Every truncation, whether in the header, the mask or the payload, is incomplete.
+    ///         for end in 0..len {
+    ///             assert_eq!(Frame::parse(&data[..end]).unwrap(), None);
+    ///         }
+    ///
+    ///         data.extend_from_slice(b"next frame");
+    ///         assert_eq!(Frame::parse(&data).unwrap(), Some((frame, len)));
+    ///     }
+    /// }
+    ///
+    /// assert!(matches!(Frame::parse(b"\x83\x00"), Err(Error::Protocol(ProtocolError::UnknownDataOpCode(3)))));
+    /// ```
+    pub fn parse(data: &[u8]) -> Result<Option<(Frame, usize)>> {
+        let mut cursor = Cursor::new(data);
+        let (header, length) = match FrameHeader::parse(&mut cursor)? {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        let start = cursor.position() as usize;
+        let end = match usize::try_from(length).ok().and_then(|len| start.checked_add(len)) {
+            Some(end) if end <= data.len() => end,
+            _ => return Ok(None),
+        };
+
+        let mut payload = data[start..end].to_vec();
+        if let Some(mask) = header.mask {
+            apply_mask(&mut payload, mask);
+        }
+
+        Ok(Some((Frame::new(header, payload.into()), end)))
+    }
+
     /// Write a frame out to a buffer
+    ///
+    /// Masked payloads are masked in place when the frame owns the only reference to them,
+    /// otherwise they are copied once.
+    ///
+    /// Exactly [`len`](Self::len) bytes are written.
+    ///
+    /// ```
+    /// # use blitz_ws::protocol::frame::{codec::{Data, OpCode}, Frame};
+    /// for size in [0, 125, 126, 65535, 65536] {
+    ///     for mask in [None, Some([1, 2, 3, 4])] {
+    ///         let mut frame = Frame::new_data(vec![0; size], OpCode::Data(Data::Binary), true);
+    ///         frame.header_mut().mask = mask;
+    ///
+    ///         let len = frame.len();
+    ///         let mut output = Vec::new();
+    ///         frame.format_to_buf(&mut output).unwrap();
+    ///         assert_eq!(output.len(), len);
+    ///     }
+    /// }
+    /// ```
     pub fn format_to_buf(mut self, output: &mut impl Write) -> Result<()> {
-        self.header.format(self.payload.len() as u64, output)?;
+        let expected_len = self.len();
+
+        let mut head = Cursor::new([0; FrameHeader::MAX_HEADER_SIZE]);
+        self.header.format(self.payload.len() as u64, &mut head)?;
+        let head = &head.get_ref()[..head.position() as usize];
+        debug_assert_eq!(head.len() + self.payload.len(), expected_len, "Bug: frame length drift");
+
+        output.write_all(head)?;
 
         if let Some(mask) = self.header.mask.take() {
             let mut data = Vec::from(mem::take(&mut self.payload));
@@ -358,6 +627,7 @@ impl Frame {
     }
 
     pub(crate) fn into_buf(mut self, buf: &mut Vec<u8>) -> Result<()> {
+        let (start, expected_len) = (buf.len(), self.len());
         self.header.format(self.payload.len() as u64, buf)?;
 
         let len = buf.len();
@@ -367,14 +637,14 @@ impl Frame {
             apply_mask(&mut buf[len..], mask);
         }
 
+        debug_assert_eq!(buf.len() - start, expected_len, "Bug: frame length drift");
+
         Ok(())
     }
 }
 
 impl Display for Frame {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        use std::fmt::Write;
-
         write!(
             f,
             "/
@@ -384,7 +654,8 @@ impl Display for Frame {
             opcode: {},
             length: {},
             payload-length: {},
-            payload: 0x{}
+            payload:
+{}
             ",
             self.header.fin,
             self.header.rsv1,
@@ -393,10 +664,7 @@ impl Display for Frame {
             self.header.opcode,
             self.len(),
             self.payload.len(),
-            self.payload.iter().fold(String::new(), |mut out, byte| {
-                _ = write!(out, "{byte:02x}");
-                out
-            })
+            self.dump(Self::DISPLAY_DUMP_BYTES)
         )
     }
 }
@@ -1,5 +1,10 @@
 use core::str;
-use std::{borrow::Borrow, fmt::Display, hash::Hash, ops::Deref};
+use std::{
+    borrow::Borrow,
+    fmt::Display,
+    hash::Hash,
+    ops::{Bound, Deref, RangeBounds},
+};
 
 use bytes::{Bytes, BytesMut};
 
@@ -28,6 +33,47 @@ impl Utf8Bytes {
     pub unsafe fn from_bytes_unchecked(bytes: Bytes) -> Self {
         Self(bytes)
     }
+
+    /// Returns a sub-slice sharing the same memory, without validating the encoding again.
+    ///
+    /// `None` if the range is out of bounds or does not start and end on char boundaries.
+    ///
+    /// # Example
+    /// ```
+    /// # use blitz_ws::protocol::frame::Utf8Bytes;
+    /// let text = Utf8Bytes::from_static("héllo wörld");
+    ///
+    /// assert_eq!(text.slice(..6).unwrap(), "héllo");
+    /// assert_eq!(text.slice(7..).unwrap(), "wörld");
+    /// assert_eq!(text.slice(..).unwrap(), text);
+    ///
+    /// // No copy is made.
+    /// assert_eq!(text.slice(7..).unwrap().as_ptr(), text[7..].as_ptr());
+    ///
+    /// // `é` and `ö` are two bytes long, a range cannot split them.
+    /// assert_eq!(text.slice(..2), None);
+    /// assert_eq!(text.slice(9..), None);
+    /// assert_eq!(text.slice(..20), None);
+    /// ```
+    pub fn slice(&self, range: impl RangeBounds<usize>) -> Option<Utf8Bytes> {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.checked_add(1)?,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.checked_add(1)?,
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.len(),
+        };
+
+        let text = self.as_str();
+        if start > end || !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+            return None;
+        }
+
+        Some(Self(self.0.slice(start..end)))
+    }
 }
 
 impl Deref for Utf8Bytes {
@@ -37,7 +83,7 @@ impl Deref for Utf8Bytes {
     /// /// Example fn that takes a str slice
     /// fn a(s: &str) {}
     ///
-    /// let data = tungstenite::Utf8Bytes::from_static("foo123");
+    /// let data = blitz_ws::protocol::frame::Utf8Bytes::from_static("foo123");
     ///
     /// // auto-deref as arg
     /// a(&data);
@@ -101,7 +147,7 @@ where
     for<'a> &'a str: PartialEq<T>,
 {
     /// ```
-    /// let payload = tungstenite::Utf8Bytes::from_static("foo123");
+    /// let payload = blitz_ws::protocol::frame::Utf8Bytes::from_static("foo123");
     /// assert_eq!(payload, "foo123");
     /// assert_eq!(payload, "foo123".to_string());
     /// assert_eq!(payload, &"foo123".to_string());
@@ -1,11 +1,16 @@
 //! WebSocket Message handler
 
+use std::str::Utf8Error;
+
 use bytes::Bytes;
 
 use crate::{
     error::{CapacityError, Error, Result},
     protocol::{
-        frame::{CloseFrame, Frame, Utf8Bytes},
+        frame::{
+            codec::{CloseCode, Control, Data, OpCode},
+            CloseFrame, Frame, Utf8Bytes,
+        },
         message::string_lib::StringCollector,
     },
 };
@@ -92,6 +97,7 @@ pub struct IncompleteMessage {
 #[derive(Debug)]
 enum IncompleteMessageCollector {
     Text(StringCollector),
+    /// Fragments are copied here, unlike single frame messages which keep the read buffer.
     Binary(Vec<u8>),
 }
 
@@ -165,12 +171,70 @@ impl IncompleteMessage {
     }
 }
 
+/// A control frame received from the peer, delivered separately from data messages when
+/// [`WebSocketConfig::control_events`](crate::protocol::config::WebSocketConfig::control_events)
+/// is enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlEvent {
+    /// A ping with its payload
+    Ping(Bytes),
+    /// A pong with its payload
+    Pong(Bytes),
+}
+
+/// The kind of a data message, see
+/// [`WebSocket::read_into`](crate::protocol::websocket::WebSocket::read_into).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MessageKind {
+    /// A text message
+    Text,
+    /// A binary message
+    Binary,
+}
+
+/// A single frame of a message, see
+/// [`WebSocket::read_fragment`](crate::protocol::websocket::WebSocket::read_fragment).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fragment {
+    /// The opcode of the frame: text or binary for the first fragment of a message,
+    /// continuation for the others, or that of a control frame.
+    pub opcode: OpCode,
+    /// The payload of the frame.
+    pub payload: Bytes,
+    /// This is the last fragment of the message.
+    pub is_final: bool,
+}
+
+impl From<Message> for Fragment {
+    fn from(msg: Message) -> Self {
+        let (opcode, payload) = match msg {
+            Message::Text(text) => (OpCode::Data(Data::Text), text.into()),
+            Message::Binary(data) => (OpCode::Data(Data::Binary), data),
+            Message::Ping(data) => (OpCode::Control(Control::Ping), data),
+            Message::Pong(data) => (OpCode::Control(Control::Pong), data),
+            Message::Close(close) => {
+                (OpCode::Control(Control::Close), Frame::new_close(close).into_payload())
+            }
+            Message::Frame(frame) => {
+                let header = frame.header();
+                let (opcode, is_final) = (header.opcode, header.fin);
+                return Fragment { opcode, payload: frame.into_payload(), is_final };
+            }
+        };
+
+        Fragment { opcode, payload, is_final: true }
+    }
+}
+
 /// A WebSocket message
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Message {
     /// A text message
     Text(Utf8Bytes),
     /// A binary message
+    ///
+    /// When received in a single frame, the payload shares the buffer it was read into and is
+    /// not copied. Fragmented messages are necessarily copied when reassembled.
     Binary(Bytes),
     /// A ping (control) message
     Ping(Bytes),
@@ -199,6 +263,32 @@ impl Message {
         Message::Binary(binary.into())
     }
 
+    /// Create a new text WebSocket message from `Bytes`, validating it as UTF-8.
+    ///
+    /// The bytes are wrapped as they are, without copying.
+    pub fn text_from_bytes(bytes: Bytes) -> Result<Message, Utf8Error> {
+        Utf8Bytes::try_from(bytes).map(Message::Text)
+    }
+
+    /// Create a close message with the given code and reason.
+    pub fn close_with<R>(code: CloseCode, reason: R) -> Message
+    where
+        R: Into<Utf8Bytes>,
+    {
+        Message::Close(Some(CloseFrame { code, reason: reason.into() }))
+    }
+
+    /// Create a close message with [`CloseCode::Normal`] and no reason.
+    pub fn close_normal() -> Message {
+        Message::close_with(CloseCode::Normal, Utf8Bytes::default())
+    }
+
+    /// Create a close message with [`CloseCode::Away`] and no reason, e.g. when a server is
+    /// shutting down.
+    pub fn close_going_away() -> Message {
+        Message::close_with(CloseCode::Away, Utf8Bytes::default())
+    }
+
     /// Indicates if the Message is of control protocol (`Ping`, `Pong`, `Close`)
     pub fn is_control(&self) -> bool {
         matches!(self, Message::Ping(_) | Message::Pong(_) | Message::Close(_))
@@ -245,6 +335,22 @@ impl Message {
             Self::Frame(frame) => frame.into_payload(),
         }
     }
+
+    /// Borrow the text of a `Text` message, `None` for any other message.
+    pub fn as_text(&self) -> Option<&str> {
+        match *self {
+            Message::Text(ref s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Borrow the payload of a `Binary`, `Ping` or `Pong` message, `None` for any other message.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Message::Binary(ref b) | Message::Ping(ref b) | Message::Pong(ref b) => Some(b),
+            _ => None,
+        }
+    }
 }
 
 impl From<String> for Message {
@@ -281,6 +387,34 @@ impl From<Vec<u8>> for Message {
     }
 }
 
+impl From<CloseFrame> for Message {
+    #[inline]
+    fn from(value: CloseFrame) -> Self {
+        Message::Close(Some(value))
+    }
+}
+
+/// A close message with an empty reason.
+///
+/// # Example
+/// ```
+/// # use blitz_ws::protocol::{
+/// #     frame::{codec::CloseCode, CloseFrame},
+/// #     message::Message,
+/// # };
+/// let close = CloseFrame { code: CloseCode::Away, reason: "restarting".into() };
+/// assert_eq!(Message::from(close.clone()), Message::Close(Some(close)));
+///
+/// let msg: Message = CloseCode::Normal.into();
+/// assert_eq!(msg, Message::Close(Some(CloseFrame { code: CloseCode::Normal, reason: "".into() })));
+/// ```
+impl From<CloseCode> for Message {
+    #[inline]
+    fn from(value: CloseCode) -> Self {
+        Message::close_with(value, Utf8Bytes::default())
+    }
+}
+
 impl From<Message> for Bytes {
     #[inline]
     fn from(value: Message) -> Self {
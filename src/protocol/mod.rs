@@ -2,6 +2,7 @@
 
 pub mod compression;
 pub mod config;
+pub mod extension;
 pub mod frame;
 pub mod message;
 pub mod websocket;
@@ -1,21 +1,34 @@
 //! WebSocket handler
 
 use std::{
+    collections::VecDeque,
+    fmt::{Debug, Formatter},
     io::{self, Read, Write},
-    mem::replace,
+    mem::{replace, take},
+    time::{Duration, Instant},
 };
 
+use bytes::Bytes;
+#[cfg(feature = "handshake")]
+use http::HeaderMap;
+
 use crate::{
     error::{CapacityError, Error, ProtocolError, Result},
     protocol::{
+        compression::{CompressionStats, NegotiatedCompression},
         config::WebSocketConfig,
+        extension::WebSocketExtension,
         frame::{
             codec::{CloseCode, Control, Data, OpCode},
             core::FrameCodec,
-            CloseFrame, Frame, Utf8Bytes,
+            CloseFrame, Frame, FrameHeader, Utf8Bytes,
+        },
+        message::{
+            ControlEvent, Fragment, IncompleteMessage, IncompleteMessageType, Message, MessageKind,
         },
-        message::{IncompleteMessage, IncompleteMessageType, Message},
     },
+    stream::ReadTimeout,
+    util::NonBlockingResult,
     MAX_CONTROL_FRAME_PAYLOAD,
 };
 
@@ -28,6 +41,36 @@ pub enum OperationMode {
     Server,
 }
 
+/// State of a WebSocket connection, see [`WebSocket::state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    /// The connection is open, messages can be sent and received.
+    Active,
+    /// We sent a close frame and wait for the peer to reply. Messages it sent in the meantime
+    /// can still be read.
+    ClosedByUs,
+    /// The peer sent a close frame, our reply is queued. The next read reports that the
+    /// connection is closed.
+    ClosedByPeer,
+    /// Both close frames were exchanged after we initiated the close handshake. The next read
+    /// reports that the connection is closed.
+    CloseAcknowledged,
+    /// The connection is closed, any further use fails with `Error::AlreadyClosed`.
+    Terminated,
+}
+
+impl From<WebSocketState> for ConnectionState {
+    fn from(state: WebSocketState) -> Self {
+        match state {
+            WebSocketState::Active => Self::Active,
+            WebSocketState::ClosedByUs => Self::ClosedByUs,
+            WebSocketState::ClosedByPeer => Self::ClosedByPeer,
+            WebSocketState::CloseAcknowledged => Self::CloseAcknowledged,
+            WebSocketState::Terminated => Self::Terminated,
+        }
+    }
+}
+
 /// WebSocket input-output stream.
 ///
 /// This is THE structure you want to create to be able to speak the WebSocket protocol.
@@ -85,12 +128,26 @@ impl<T: Read + Write> WebSocket<T> {
         self.stream
     }
 
+    /// Returns the stream along with the data read from it but not parsed yet, e.g. to move
+    /// the connection to another runtime.
+    ///
+    /// This is the inverse of [`from_partially_read`](Self::from_partially_read), which resumes
+    /// reading where this connection left off. Frames of a fragmented message that were already
+    /// read are not included and neither is unflushed data, so call this between messages
+    /// after a successful [`flush`](Self::flush).
+    pub fn into_parts(self) -> (T, Vec<u8>) {
+        (self.stream, self.context.into_unparsed())
+    }
+
     /// Change the configuration.
     ///
+    /// Changes apply from the next read or write on, buffer sizes included. The value returned
+    /// by `func` is passed through, e.g. to read out a previous value while replacing it.
+    ///
     /// # Panics
     /// Panics if config is invalid e.g. `max_write_buffer_size <= write_buffer_size`.
-    pub fn set_config(&mut self, func: impl FnOnce(&mut WebSocketConfig)) {
-        self.context.set_config(func);
+    pub fn set_config<R>(&mut self, func: impl FnOnce(&mut WebSocketConfig) -> R) -> R {
+        self.context.set_config(func)
     }
 
     /// Read the configuration.
@@ -98,6 +155,36 @@ impl<T: Read + Write> WebSocket<T> {
         self.context.get_config()
     }
 
+    /// The headers of the client's handshake request, e.g. to read `Authorization` or
+    /// `Cookie` once the connection is established.
+    ///
+    /// Only retained by a server with [`WebSocketConfig::retain_request_headers`] set,
+    /// `None` otherwise.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::{accept_with_config, protocol::config::WebSocketConfig};
+    /// # let request = "GET /chat HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+    /// #     Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+    /// #     Sec-WebSocket-Version: 13\r\nAuthorization: Bearer token\r\n\r\n";
+    /// # let stream = Cursor::new(request.as_bytes().to_vec());
+    /// let config = WebSocketConfig::default().retain_request_headers(true);
+    /// let ws = accept_with_config(stream, Some(config)).unwrap();
+    ///
+    /// let headers = ws.request_headers().unwrap();
+    /// assert_eq!(headers["Authorization"], "Bearer token");
+    /// ```
+    #[cfg(feature = "handshake")]
+    pub fn request_headers(&self) -> Option<&HeaderMap> {
+        self.context.request_headers()
+    }
+
+    #[cfg(feature = "handshake")]
+    pub(crate) fn set_request_headers(&mut self, headers: Option<HeaderMap>) {
+        self.context.request_headers = headers;
+    }
+
     /// Check if it is possible to read messages.
     ///
     /// Reading is impossible after receiving `Message::Close`. It is still possible after
@@ -113,14 +200,328 @@ impl<T: Read + Write> WebSocket<T> {
         self.context.can_write()
     }
 
-    /// Check if it is possible to read messages.
+    /// Whether this is the client or the server end of the connection.
     ///
-    /// Reading is impossible after receiving `Message::Close`. It is still possible after
-    /// sending close frame since the peer still may send some data before confirming close.
+    /// Clients mask every frame they send, servers never do.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::protocol::{message::Message, websocket::{OperationMode, WebSocket}};
+    /// for mode in [OperationMode::Client, OperationMode::Server] {
+    ///     let mut socket = WebSocket::new(Cursor::new(Vec::new()), mode, None);
+    ///     assert_eq!(socket.mode(), mode);
+    ///
+    ///     socket.send(Message::Text("Hello".into())).unwrap();
+    ///     let masked = socket.get_ref().get_ref()[1] & 0x80 != 0;
+    ///     assert_eq!(masked, mode == OperationMode::Client);
+    /// }
+    /// ```
+    pub fn mode(&self) -> OperationMode {
+        self.context.mode()
+    }
+
+    /// The state of the connection, e.g. to tell whether a close handshake is in progress.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::{self, Cursor, Read, Write};
+    /// # use blitz_ws::{error::Error, protocol::{message::Message, websocket::{ConnectionState, OperationMode, WebSocket}}};
+    /// # struct Stream { input: Cursor<Vec<u8>>, output: Vec<u8> }
+    /// # impl Read for Stream {
+    /// #     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.input.read(buf) }
+    /// # }
+    /// # impl Write for Stream {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.output.write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// // The server replies to our close frame with an empty close frame.
+    /// let stream = Stream { input: Cursor::new(vec![0x88, 0]), output: Vec::new() };
+    /// let mut socket = WebSocket::new(stream, OperationMode::Client, None);
+    /// assert_eq!(socket.state(), ConnectionState::Active);
+    ///
+    /// socket.close(None).unwrap();
+    /// assert_eq!(socket.state(), ConnectionState::ClosedByUs);
+    ///
+    /// assert_eq!(socket.read().unwrap(), Message::Close(None));
+    /// assert_eq!(socket.state(), ConnectionState::CloseAcknowledged);
+    ///
+    /// assert!(matches!(socket.read(), Err(Error::ConnectionClosed)));
+    /// assert_eq!(socket.state(), ConnectionState::Terminated);
+    /// ```
+    pub fn state(&self) -> ConnectionState {
+        self.context.state()
+    }
+
+    /// Number of bytes currently held in the write buffer, waiting to be written to the stream.
+    ///
+    /// Lets a caller apply backpressure before [`write`](Self::write) fails with
+    /// [`Error::WriteBufferFull`].
+    pub fn write_buffer_len(&self) -> usize {
+        self.context.write_buffer_len()
+    }
+
+    /// Tell if automatic control frames are waiting to be sent, i.e. pong replies, a heartbeat
+    /// or a close reply queued by [`read`](Self::read), or such frames written but not flushed.
+    ///
+    /// They go out with the next [`read`](Self::read), [`write`](Self::write) or
+    /// [`flush`](Self::flush).
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::{self, Cursor, Read, Write};
+    /// # use blitz_ws::{protocol::{message::Message, websocket::{OperationMode, WebSocket}}, Bytes};
+    /// # struct Stream { input: Cursor<Vec<u8>>, output: Vec<u8> }
+    /// # impl Read for Stream {
+    /// #     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.input.read(buf) }
+    /// # }
+    /// # impl Write for Stream {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.output.write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// // An unmasked ping from the server.
+    /// let stream = Stream { input: Cursor::new(b"\x89\x02hi".to_vec()), output: Vec::new() };
+    /// let mut socket = WebSocket::new(stream, OperationMode::Client, None);
+    /// assert!(!socket.has_pending_control());
+    ///
+    /// assert_eq!(socket.read().unwrap(), Message::Ping(Bytes::from_static(b"hi")));
+    /// assert!(socket.has_pending_control());
+    /// // A masked pong: 2 header bytes, a 4 byte mask and the payload.
+    /// assert_eq!(socket.pending_out_bytes(), 8);
+    /// assert!(socket.get_ref().output.is_empty());
+    ///
+    /// socket.flush().unwrap();
+    /// assert!(!socket.has_pending_control());
+    /// assert_eq!(socket.pending_out_bytes(), 0);
+    /// assert_eq!(socket.get_ref().output.len(), 8);
+    /// ```
+    pub fn has_pending_control(&self) -> bool {
+        self.context.has_pending_control()
+    }
+
+    /// Number of bytes waiting to be written to the stream.
+    ///
+    /// Unlike [`write_buffer_len`](Self::write_buffer_len), this includes the encoded size of
+    /// the automatic control frames not moved into the write buffer yet, see
+    /// [`has_pending_control`](Self::has_pending_control).
+    pub fn pending_out_bytes(&self) -> usize {
+        self.context.pending_out_bytes()
+    }
+
+    /// The configured [`WebSocketConfig::max_write_buffer_size`].
+    pub fn write_buffer_capacity(&self) -> usize {
+        self.context.write_buffer_capacity()
+    }
+
+    /// Read a message from stream, if possible.
+    ///
+    /// This will also queue responses to ping and close messages. These responses
+    /// will be written and flushed on the next call to [`read`](Self::read),
+    /// [`write`](Self::write) or [`flush`](Self::flush).
+    ///
+    /// # Closing the connection
+    /// When the close handshake is complete, i.e. close frames were both sent and received,
+    /// a server returns [`Error::ConnectionClosed`] right away, as RFC 6455 leaves it to the
+    /// server to close the TCP connection. A client keeps reading until the server does so and
+    /// returns [`Error::ConnectionClosed`] on EOF. Either way the underlying stream can be
+    /// dropped at that point.
+    ///
+    /// # Fragmented messages
+    /// Fragments are reassembled into a single message. Any fragment may be empty, including
+    /// the first one and the final continuation.
+    ///
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::protocol::{message::Message, websocket::{OperationMode, WebSocket}};
+    /// // "abc" terminated by an empty final continuation.
+    /// let frames = b"\x01\x03abc\x80\x00".to_vec();
+    /// let mut socket = WebSocket::new(Cursor::new(frames), OperationMode::Client, None);
+    /// assert_eq!(socket.read().unwrap(), Message::Text("abc".into()));
+    ///
+    /// // "de" started by an empty text fragment.
+    /// let frames = b"\x01\x00\x80\x02de".to_vec();
+    /// let mut socket = WebSocket::new(Cursor::new(frames), OperationMode::Client, None);
+    /// assert_eq!(socket.read().unwrap(), Message::Text("de".into()));
+    ///
+    /// // Nothing but empty fragments.
+    /// let frames = b"\x02\x00\x00\x00\x80\x00".to_vec();
+    /// let mut socket = WebSocket::new(Cursor::new(frames), OperationMode::Client, None);
+    /// assert_eq!(socket.read().unwrap(), Message::Binary(Default::default()));
+    /// ```
     pub fn read(&mut self) -> Result<Message> {
         self.context.read(&mut self.stream)
     }
 
+    /// Read a message from a non-blocking stream.
+    ///
+    /// Returns `Ok(None)` if the stream would block before a complete message was read, instead
+    /// of [`Error::Io`] with [`io::ErrorKind::WouldBlock`]. The part of the message read so far
+    /// is kept, call again once the stream is readable. Otherwise the same as
+    /// [`read`](Self::read).
+    pub fn try_read(&mut self) -> Result<Option<Message>> {
+        self.context.try_read(&mut self.stream)
+    }
+
+    /// Read a message along with its size on the wire.
+    ///
+    /// The size is the sum of the headers and payloads of all frames making up the message,
+    /// which is what the message cost in bandwidth, unlike [`Message::len`].
+    /// Otherwise the same as [`read`](Self::read).
+    pub fn read_with_size(&mut self) -> Result<(Message, usize)> {
+        self.context.read_with_size(&mut self.stream)
+    }
+
+    /// Read a single frame, without reassembling fragmented messages, e.g. to process media
+    /// as it arrives.
+    ///
+    /// Data frames are returned as they are, the message is complete with the fragment that
+    /// has [`Fragment::is_final`] set. The order of fragments is still checked, as is
+    /// [`WebSocketConfig::max_message_size`], but text is not validated as UTF-8 since a
+    /// fragment may end in the middle of a character. Control frames are handled the same as
+    /// by [`read`](Self::read) and returned as final fragments.
+    ///
+    /// Reading fragments and whole messages can be mixed, but a fragmented message must be
+    /// read completely by the same method it was started with.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::protocol::{frame::codec::{Data, OpCode}, websocket::{OperationMode, WebSocket}};
+    /// let frames = b"\x02\x03abc\x00\x02de\x80\x01f".to_vec();
+    /// let mut socket = WebSocket::new(Cursor::new(frames), OperationMode::Client, None);
+    ///
+    /// let first = socket.read_fragment().unwrap();
+    /// assert_eq!((first.opcode, &first.payload[..], first.is_final), (OpCode::Data(Data::Binary), &b"abc"[..], false));
+    ///
+    /// let second = socket.read_fragment().unwrap();
+    /// assert_eq!((second.opcode, &second.payload[..], second.is_final), (OpCode::Data(Data::Continuation), &b"de"[..], false));
+    ///
+    /// let last = socket.read_fragment().unwrap();
+    /// assert_eq!((last.opcode, &last.payload[..], last.is_final), (OpCode::Data(Data::Continuation), &b"f"[..], true));
+    /// ```
+    pub fn read_fragment(&mut self) -> Result<Fragment> {
+        self.context.read_fragment(&mut self.stream)
+    }
+
+    /// Read the next data message, appending its payload to `buf` instead of allocating it.
+    ///
+    /// Meant for hot loops processing and discarding messages: the same buffer can be cleared
+    /// and reused for every message, keeping its capacity. Fragments are appended as they are
+    /// read, text is validated as UTF-8 once the message is complete. Control frames are
+    /// handled the same as by [`read`](Self::read) but not returned, a close frame from the
+    /// peer is answered and reading goes on until [`Error::ConnectionClosed`].
+    ///
+    /// If an error interrupts a fragmented message, e.g. [`io::ErrorKind::WouldBlock`], the
+    /// fragments read so far stay in `buf` and the next call resumes the message, so it must be
+    /// made with the same buffer. Otherwise the same as [`read_fragment`](Self::read_fragment),
+    /// with which it must not be mixed in the middle of a message.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::protocol::{message::MessageKind, websocket::{OperationMode, WebSocket}};
+    /// let frames = b"\x82\x03abc\x89\x00\x01\x02de\x80\x01f".to_vec();
+    /// let mut socket = WebSocket::new(Cursor::new(frames), OperationMode::Client, None);
+    /// let mut buf = Vec::with_capacity(64);
+    ///
+    /// assert_eq!(socket.read_into(&mut buf).unwrap(), MessageKind::Binary);
+    /// assert_eq!(buf, b"abc");
+    ///
+    /// let capacity = (buf.as_ptr(), buf.capacity());
+    /// buf.clear();
+    ///
+    /// // The ping in between is answered, the fragments are appended to the same buffer.
+    /// assert_eq!(socket.read_into(&mut buf).unwrap(), MessageKind::Text);
+    /// assert_eq!(buf, b"def");
+    /// assert_eq!((buf.as_ptr(), buf.capacity()), capacity);
+    /// ```
+    pub fn read_into(&mut self, buf: &mut Vec<u8>) -> Result<MessageKind> {
+        self.context.read_into(&mut self.stream, buf)
+    }
+
+    /// Take the control frames received since the last call.
+    ///
+    /// Only populated when [`WebSocketConfig::control_events`] is enabled, otherwise pings and
+    /// pongs are returned from [`read`](Self::read) as messages.
+    pub fn take_control_events(&mut self) -> Vec<ControlEvent> {
+        self.context.take_control_events()
+    }
+
+    /// Set a time limit for every call to [`read`](Self::read) to return a whole message.
+    ///
+    /// The deadline is checked before each read of the underlying stream, so a peer trickling
+    /// a message in a few bytes at a time cannot hold a read forever: [`Error::Io`] with
+    /// [`io::ErrorKind::TimedOut`] is returned once the time is up. The part of the message
+    /// received so far is kept and the next read resumes it. `None`, the default, means no limit.
+    ///
+    /// A read of the stream that is already blocked is not interrupted, so a peer sending
+    /// nothing at all still needs a read timeout on the stream itself. For streams implementing
+    /// [`ReadTimeout`], [`read_deadline`](Self::read_deadline) takes care of both.
+    pub fn read_timeout(&mut self, timeout: Option<Duration>) {
+        self.context.read_timeout(timeout)
+    }
+
+    /// Returns the compression counters of this connection.
+    ///
+    /// `None` if `permessage-deflate` is not in use on this connection. Only the data frames
+    /// passed through the extension are counted, not those sent with
+    /// [`send_uncompressed`](Self::send_uncompressed).
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::protocol::{
+    /// #     compression::{PerMessageDeflate, WebSocketCompressionConfig},
+    /// #     message::Message,
+    /// #     websocket::{OperationMode, WebSocket},
+    /// # };
+    /// let mut socket = WebSocket::new(Cursor::new(Vec::new()), OperationMode::Server, None);
+    /// assert_eq!(socket.compression_stats(), None);
+    ///
+    /// let deflate = PerMessageDeflate::new(WebSocketCompressionConfig::default());
+    /// socket.set_extension(Some(Box::new(deflate)));
+    /// socket.send(Message::Text("a".repeat(1000).into())).unwrap();
+    ///
+    /// let stats = socket.compression_stats().unwrap();
+    /// assert_eq!(stats.sent_uncompressed, 1000);
+    /// assert!(stats.sent_ratio().unwrap() < 0.1);
+    /// ```
+    pub fn compression_stats(&self) -> Option<CompressionStats> {
+        self.context.compression_stats()
+    }
+
+    /// Reset the compression counters of this connection, if any.
+    pub fn reset_compression_stats(&mut self) {
+        self.context.reset_compression_stats()
+    }
+
+    /// Set a callback invoked with the header and payload length of every frame read or written,
+    /// e.g. to count ping/pong traffic or measure fragment sizes.
+    ///
+    /// Outgoing frames are reported before they are masked, so the mask is never set in the
+    /// header passed to the callback. Incoming frames are reported as soon as they are read,
+    /// before they are validated. Replaces any previously set callback.
+    pub fn on_frame<F>(&mut self, hook: F)
+    where
+        F: FnMut(&FrameHeader, usize) + Send + 'static,
+    {
+        self.context.on_frame(hook)
+    }
+
+    /// Install an extension transforming the data frames of this connection, replacing any
+    /// previous one, or remove it with `None`.
+    ///
+    /// Extensions are normally installed by the handshake once negotiated, this is for
+    /// connections set up by other means, both ends must agree on the extension.
+    pub fn set_extension(&mut self, extension: Option<Box<dyn WebSocketExtension>>) {
+        self.context.set_extension(extension)
+    }
+
+    /// Returns the extension in use on this connection, if any.
+    pub fn extension(&self) -> Option<&dyn WebSocketExtension> {
+        self.context.extension()
+    }
+
     /// Writes and immediately flushes a message.
     /// Equivalent to calling [`write`](Self::write) then [`flush`](Self::flush).
     pub fn send(&mut self, msg: Message) -> Result<()> {
@@ -128,6 +529,56 @@ impl<T: Read + Write> WebSocket<T> {
         self.flush()
     }
 
+    /// Send a message received on another connection, e.g. when relaying between peers.
+    ///
+    /// Only the `Bytes` handle of the payload is cloned, not the data itself. The message is
+    /// framed for this connection: a fresh mask is applied if this endpoint is a client and
+    /// none if it is a server, regardless of how the message was received.
+    ///
+    /// Equivalent to calling [`send`](Self::send) with a clone of `msg`.
+    pub fn forward(&mut self, msg: &Message) -> Result<()> {
+        self.send(msg.clone())
+    }
+
+    /// Writes and immediately flushes a message without passing it through the extension in
+    /// use, e.g. to skip `permessage-deflate` for a payload that is already compressed.
+    ///
+    /// The frames of the message are sent with `RSV1` clear, which
+    /// [`PerMessageDeflate`](crate::protocol::compression::PerMessageDeflate) receivers accept as
+    /// an uncompressed message. Other extensions may not allow messages to skip them. Without an
+    /// extension, this is the same as [`send`](Self::send).
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::protocol::{compression::{PerMessageDeflate, WebSocketCompressionConfig}, message::Message, websocket::{OperationMode, WebSocket}};
+    /// let mut socket = WebSocket::new(Cursor::new(Vec::new()), OperationMode::Server, None);
+    /// socket.set_extension(Some(Box::new(PerMessageDeflate::new(WebSocketCompressionConfig::default()))));
+    ///
+    /// let text = "a".repeat(100);
+    /// socket.send(Message::Text(text.clone().into())).unwrap();
+    /// let compressed = socket.get_ref().get_ref().clone();
+    /// assert_eq!(compressed[0], 0xC1, "FIN, RSV1 and the text opcode");
+    /// assert!(compressed.len() < 100);
+    ///
+    /// socket.send_uncompressed(Message::Text(text.clone().into())).unwrap();
+    /// let uncompressed = &socket.get_ref().get_ref()[compressed.len()..];
+    /// assert_eq!(uncompressed[0], 0x81, "FIN and the text opcode");
+    /// assert_eq!(&uncompressed[2..], text.as_bytes());
+    /// ```
+    pub fn send_uncompressed(&mut self, msg: Message) -> Result<()> {
+        self.context.send_uncompressed(&mut self.stream, msg)
+    }
+
+    /// Send an unsolicited pong as a unidirectional heartbeat.
+    ///
+    /// Unlike passing a [`Message::Pong`] to [`write`](Self::write), the heartbeat is queued
+    /// separately from the automatic replies to pings, so it never replaces a pending reply
+    /// and both are sent. A heartbeat that has not been written yet is replaced by the next one.
+    pub fn send_heartbeat_pong(&mut self, data: Bytes) -> Result<()> {
+        self.context.send_heartbeat_pong(&mut self.stream, data)
+    }
+
     /// Write a message to the provided stream, if possible.
     ///
     /// A subsequent call should be made to [`flush`](Self::flush) to flush writes.
@@ -144,13 +595,18 @@ impl<T: Read + Write> WebSocket<T> {
     ///
     /// For example, upon receiving ping messages tungstenite queues pong replies automatically.
     /// The next call to [`read`](Self::read), [`write`](Self::write) or [`flush`](Self::flush)
-    /// will write & flush the pong reply. This means you should not respond to ping frames manually.
+    /// will write & flush the pong reply. This means you should not respond to ping frames manually,
+    /// unless [`WebSocketConfig::auto_pong`] is disabled.
     ///
     /// You can however send pong frames manually in order to indicate a unidirectional heartbeat
     /// as described in [RFC 6455](https://tools.ietf.org/html/rfc6455#section-5.5.3). Note that
     /// if [`read`](Self::read) returns a ping, you should [`flush`](Self::flush) before passing
     /// a custom pong to [`write`](Self::write), otherwise the automatic queued response to the
     /// ping will not be sent as it will be replaced by your custom pong message.
+    /// [`send_heartbeat_pong`](Self::send_heartbeat_pong) does not have this problem.
+    ///
+    /// A close frame passed as [`Message::Frame`] starts the close handshake, as
+    /// [`close`](Self::close) does.
     ///
     /// # Errors
     /// - If the WebSocket's write buffer is full, [`Error::WriteBufferFull`] will be returned
@@ -162,10 +618,119 @@ impl<T: Read + Write> WebSocket<T> {
     /// - [`Error::Io`] is returned if the underlying connection returns an error
     ///   (consider these fatal except for WouldBlock).
     /// - [`Error::Capacity`] if your message size is bigger than the configured max message size.
+    /// - [`ProtocolError::InvalidCloseCode`] if a `Message::Close` carries a code that must not be
+    ///   sent on the wire (see [`CloseCode::allowed`]).
+    /// - [`Error::Protocol`] if a [`Message::Frame`] is a fragmented control frame
+    ///   ([`ProtocolError::FragmentedControlFrame`]), a control frame with a payload over 125 bytes
+    ///   ([`ProtocolError::ControlFrameTooBig`]) or has a reserved opcode. Nothing is sent then.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::{error::{Error, ProtocolError}, protocol::{frame::Frame, message::Message, websocket::{OperationMode, WebSocket}}};
+    /// let mut socket = WebSocket::new(Cursor::new(Vec::new()), OperationMode::Server, None);
+    ///
+    /// let mut close = Frame::new_close(None);
+    /// close.header_mut().fin = false;
+    /// assert!(matches!(
+    ///     socket.write(Message::Frame(close)),
+    ///     Err(Error::Protocol(ProtocolError::FragmentedControlFrame))
+    /// ));
+    ///
+    /// assert!(matches!(
+    ///     socket.write(Message::Frame(Frame::new_ping(vec![0; 126]))),
+    ///     Err(Error::Protocol(ProtocolError::ControlFrameTooBig))
+    /// ));
+    ///
+    /// socket.flush().unwrap();
+    /// assert!(socket.get_ref().get_ref().is_empty());
+    ///
+    /// // A valid close frame starts the close handshake, nothing can be sent after it.
+    /// socket.write(Message::Frame(Frame::new_close(None))).unwrap();
+    /// assert!(matches!(
+    ///     socket.write(Message::Text("late".into())),
+    ///     Err(Error::Protocol(ProtocolError::SendAfterClose))
+    /// ));
+    /// ```
     pub fn write(&mut self, msg: Message) -> Result<()> {
         self.context.write(&mut self.stream, msg)
     }
 
+    /// Send a pre-built frame and flush, e.g. to fragment messages in a custom way.
+    ///
+    /// The frame is validated like a [`Message::Frame`] passed to [`write`](Self::write):
+    /// control frames must not be fragmented ([`ProtocolError::FragmentedControlFrame`]), their
+    /// payload must not exceed 125 bytes ([`ProtocolError::ControlFrameTooBig`]) and reserved
+    /// opcodes are rejected. The frame is masked as required by the mode of this endpoint, whatever mask it has.
+    /// Sending a close frame starts the close handshake, as [`close`](Self::close) does.
+    ///
+    /// Everything else is up to the caller. Misuse, e.g. a continuation frame without a
+    /// preceding fragment or a text frame with invalid UTF-8, violates the protocol and makes
+    /// the peer fail the connection.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::protocol::{
+    /// #     frame::{codec::{Data, OpCode}, Frame},
+    /// #     websocket::{OperationMode, WebSocket},
+    /// # };
+    /// let mut socket = WebSocket::new(Cursor::new(Vec::new()), OperationMode::Server, None);
+    ///
+    /// socket.send_frame(Frame::new_data("Hello, ", OpCode::Data(Data::Text), false)).unwrap();
+    /// socket.send_frame(Frame::new_data("world!", OpCode::Data(Data::Continuation), true)).unwrap();
+    /// assert_eq!(socket.get_ref().get_ref(), b"\x01\x07Hello, \x80\x06world!");
+    ///
+    /// assert!(socket.send_frame(Frame::new_ping(vec![0; 126])).is_err());
+    /// ```
+    pub fn send_frame(&mut self, frame: Frame) -> Result<()> {
+        self.context.send_frame(&mut self.stream, frame)
+    }
+
+    /// Send everything `reader` yields as a single text or binary message and flush, without
+    /// holding the whole message in memory. Returns the size of the message.
+    ///
+    /// The reader is read in chunks of [`WebSocketConfig::write_buffer_size`] (or
+    /// [`WebSocketConfig::max_frame_size`], if smaller), each sent as a fragment of the message.
+    /// Text is not validated, the reader must yield valid UTF-8 for a [`Data::Text`] message.
+    ///
+    /// The message cannot be taken back once its first fragment is sent. If the reader or the
+    /// stream fails, or the message exceeds [`WebSocketConfig::max_message_size`] after that,
+    /// the connection is closed with [`CloseCode::Error`] or [`CloseCode::Size`] and the error
+    /// is returned. The one exception is a failure to flush the last fragment: the message is
+    /// complete then, and flushing may be retried. For the same reason, the stream should be
+    /// blocking.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::Cursor;
+    /// # use blitz_ws::protocol::{frame::codec::Data, websocket::{OperationMode, WebSocket}};
+    /// let mut socket = WebSocket::new(Cursor::new(Vec::new()), OperationMode::Server, None);
+    ///
+    /// let file = Cursor::new(vec![7; 1024 * 1024]);
+    /// assert_eq!(socket.send_from_reader(file, Data::Binary).unwrap(), 1024 * 1024);
+    /// ```
+    pub fn send_from_reader(&mut self, reader: impl Read, kind: Data) -> Result<u64> {
+        self.context.send_from_reader(&mut self.stream, reader, kind)
+    }
+
+    /// Write several messages and flush once at the end.
+    ///
+    /// Cheaper than [`send`](Self::send) per message when broadcasting, as the frames are
+    /// gathered in the write buffer and the stream is flushed a single time.
+    ///
+    /// # Errors
+    /// Returns the error together with the messages that did not make it into the write buffer.
+    /// This is how [`Error::WriteBufferFull`] reports backpressure: the returned messages may be
+    /// retried after a successful [`flush`](Self::flush). If the final flush fails, all messages
+    /// are buffered and the returned list is empty.
+    pub fn write_batch<I>(&mut self, msgs: I) -> std::result::Result<(), (Error, Vec<Message>)>
+    where
+        I: IntoIterator<Item = Message>,
+    {
+        self.context.write_batch(&mut self.stream, msgs)
+    }
+
     /// Flush writes.
     ///
     /// Ensures all messages previously passed to [`write`](Self::write) and automatic
@@ -174,6 +739,15 @@ impl<T: Read + Write> WebSocket<T> {
         self.context.flush(&mut self.stream)
     }
 
+    /// Flush writes to a non-blocking stream.
+    ///
+    /// Returns `Ok(false)` if the stream would block before everything was written, instead of
+    /// [`Error::Io`] with [`io::ErrorKind::WouldBlock`]. The rest stays buffered, call again once
+    /// the stream is writable. Returns `Ok(true)` once everything is flushed.
+    pub fn try_flush(&mut self) -> Result<bool> {
+        self.context.try_flush(&mut self.stream)
+    }
+
     /// Close the connection.
     ///
     /// This function guarantees that the close frame will be queued.
@@ -191,13 +765,186 @@ impl<T: Read + Write> WebSocket<T> {
     /// [Error::ConnectionClosed] if this endpoint is the server.
     ///
     /// If this endpoint is a client, [Error::ConnectionClosed] will only be
-    /// returned after the server has closed the underlying connection.
+    /// returned after the server has closed the underlying connection. Once the close
+    /// handshake is done, the end of the stream, a reset or an abort of the connection
+    /// and [`io::ErrorKind::UnexpectedEof`] (e.g. TLS without `close_notify`) all count.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::{self, Cursor, Read, Write};
+    /// # use blitz_ws::{error::Error, protocol::{message::Message, websocket::{OperationMode, WebSocket}}};
+    /// # struct Stream { input: Cursor<Vec<u8>>, output: Vec<u8> }
+    /// # impl Read for Stream {
+    /// #     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    /// #         match self.input.read(buf)? {
+    /// #             0 => Err(io::ErrorKind::UnexpectedEof.into()),
+    /// #             n => Ok(n),
+    /// #         }
+    /// #     }
+    /// # }
+    /// # impl Write for Stream {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.output.write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// // The server acknowledges our close frame, then shuts the connection down.
+    /// let ack = vec![0x88, 0x02, 0x03, 0xe8];
+    /// let stream = Stream { input: Cursor::new(ack), output: Vec::new() };
+    /// let mut ws = WebSocket::new(stream, OperationMode::Client, None);
+    ///
+    /// ws.close(None).unwrap();
+    /// assert!(matches!(ws.read().unwrap(), Message::Close(Some(_))));
+    ///
+    /// // This stream reports the end as `UnexpectedEof`, a plain `Ok(0)` works the same.
+    /// assert!(matches!(ws.read(), Err(Error::ConnectionClosed)));
+    /// ```
     ///
     /// It is thus safe to drop the underlying connection as soon as [Error::ConnectionClosed]
     /// is returned from [`read`](Self::read) or [`flush`](Self::flush).
+    ///
+    /// Returns [`ProtocolError::InvalidCloseCode`] without sending anything if the code must not
+    /// be sent on the wire (see [`CloseCode::allowed`]).
     pub fn close(&mut self, code: Option<CloseFrame>) -> Result<()> {
         self.context.close(&mut self.stream, code)
     }
+
+    /// Stop sending, but keep receiving until the peer acknowledges the close.
+    ///
+    /// The close frame is sent and flushed, moving the connection to
+    /// [`ConnectionState::ClosedByUs`]. Writing fails from then on, while [`read`](Self::read)
+    /// keeps returning the messages the peer sent before it received the close frame. The
+    /// peer's close frame is returned as [`Message::Close`], the next read returns
+    /// [`Error::ConnectionClosed`] once the connection is done.
+    ///
+    /// Unlike [`close`](Self::close), which does nothing if a close frame was already sent or
+    /// received, this fails with [`ProtocolError::SendAfterClose`] then.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::io::{self, Cursor, Read, Write};
+    /// # use blitz_ws::{error::Error, protocol::{message::Message, websocket::{OperationMode, WebSocket}}};
+    /// # struct Stream { input: Cursor<Vec<u8>>, output: Vec<u8> }
+    /// # impl Read for Stream {
+    /// #     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.input.read(buf) }
+    /// # }
+    /// # impl Write for Stream {
+    /// #     fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.output.write(buf) }
+    /// #     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// # }
+    /// // The server sent two more messages before replying to our close frame.
+    /// let input = b"\x81\x03one\x81\x03two\x88\x00".to_vec();
+    /// let stream = Stream { input: Cursor::new(input), output: Vec::new() };
+    /// let mut socket = WebSocket::new(stream, OperationMode::Client, None);
+    ///
+    /// socket.send_close_and_continue(None).unwrap();
+    /// assert!(socket.can_read() && !socket.can_write());
+    ///
+    /// assert_eq!(socket.read().unwrap(), Message::Text("one".into()));
+    /// assert_eq!(socket.read().unwrap(), Message::Text("two".into()));
+    /// assert_eq!(socket.read().unwrap(), Message::Close(None));
+    /// assert!(matches!(socket.read(), Err(Error::ConnectionClosed)));
+    /// ```
+    pub fn send_close_and_continue(&mut self, code: Option<CloseFrame>) -> Result<()> {
+        self.context.send_close_and_continue(&mut self.stream, code)
+    }
+}
+
+impl<T: Read + Write + ReadTimeout> WebSocket<T> {
+    /// Read a message, giving up once `deadline` has passed.
+    ///
+    /// Unlike a read timeout on the stream, which applies to every single read, the deadline
+    /// bounds the whole message: the remaining time is recomputed before each read of the
+    /// underlying stream, so a message trickling in over many frames cannot outlive it.
+    ///
+    /// Returns [`Error::Io`] with [`io::ErrorKind::TimedOut`] if the message is not complete
+    /// by the deadline. The partially received message is kept and a subsequent read resumes
+    /// it. The stream's previous read timeout is restored before returning.
+    pub fn read_deadline(&mut self, deadline: Instant) -> Result<Message> {
+        let timeout = self.stream.read_timeout()?;
+        let result = self.context.read(&mut DeadlineStream { stream: &mut self.stream, deadline });
+        self.stream.set_read_timeout(timeout)?;
+        result
+    }
+
+    /// Close the connection and drive the close handshake to completion.
+    ///
+    /// Sends a close frame with `code`, then reads and discards incoming messages until the
+    /// peer acknowledges the close or the connection is closed. Saves looping on
+    /// [`read`](Self::read) until [`Error::ConnectionClosed`] by hand.
+    ///
+    /// With a `timeout`, gives up with [`Error::Io`] of kind [`io::ErrorKind::TimedOut`] if the
+    /// peer has not replied in time (see [`read_deadline`](Self::read_deadline)). The timeout
+    /// only bounds reading, writing the close frame may still block.
+    pub fn close_gracefully(
+        &mut self,
+        code: Option<CloseFrame>,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+        let result = self.close(code).and_then(|()| loop {
+            let msg = match deadline {
+                Some(deadline) => self.read_deadline(deadline)?,
+                None => self.read()?,
+            };
+
+            if let Message::Close(_) = msg {
+                break self.flush();
+            }
+        });
+
+        match result {
+            Err(Error::ConnectionClosed) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Read and discard incoming messages until the connection is closed, e.g. to let the peer
+    /// finish its close handshake when tearing down after an application error.
+    ///
+    /// Control frames are handled the same as by [`read`](Self::read), so pings and the close
+    /// frame of the peer are answered. Returns the close frame of the peer, `None` if its close
+    /// frame had no payload or the close handshake was completed before.
+    ///
+    /// With a `deadline`, gives up with [`Error::Io`] of kind [`io::ErrorKind::TimedOut`] once
+    /// it has passed (see [`read_deadline`](Self::read_deadline)). This complements
+    /// [`close_gracefully`](Self::close_gracefully), which starts the close handshake itself.
+    ///
+    /// # Example
+    /// ```
+    /// # use std::{io::Write, net::{TcpListener, TcpStream}, thread, time::{Duration, Instant}};
+    /// # use blitz_ws::protocol::{frame::codec::CloseCode, websocket::{OperationMode, WebSocket}};
+    /// # let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// # let addr = listener.local_addr().unwrap();
+    /// // The server sends two texts, then closes with 1001 "bye".
+    /// let server = thread::spawn(move || {
+    ///     let (mut stream, _) = listener.accept().unwrap();
+    ///     stream.write_all(b"\x81\x03one\x81\x03two\x88\x05\x03\xe9bye").unwrap();
+    /// });
+    ///
+    /// let stream = TcpStream::connect(addr).unwrap();
+    /// let mut socket = WebSocket::new(stream, OperationMode::Client, None);
+    ///
+    /// let close = socket.drain(Some(Instant::now() + Duration::from_secs(5))).unwrap().unwrap();
+    /// assert_eq!((close.code, close.reason.as_str()), (CloseCode::Away, "bye"));
+    /// # server.join().unwrap();
+    /// ```
+    pub fn drain(&mut self, deadline: Option<Instant>) -> Result<Option<CloseFrame>> {
+        let mut close = None;
+
+        loop {
+            let msg = match deadline {
+                Some(deadline) => self.read_deadline(deadline),
+                None => self.read(),
+            };
+
+            match msg {
+                Ok(Message::Close(frame)) => close = frame,
+                Ok(_) => {}
+                Err(Error::ConnectionClosed) => return Ok(close),
+                Err(e) => return Err(e),
+            }
+        }
+    }
 }
 
 /// A context for managing WebSocket stream.
@@ -211,11 +958,42 @@ pub struct WebSocketContext {
     state: WebSocketState,
     /// Receive: an incomplete message being processed.
     incomplete: Option<IncompleteMessage>,
+    /// Receive: wire size of the data frames of the message being processed.
+    incomplete_wire_len: usize,
+    /// Receive: pings and pongs held back until the message being processed is complete.
+    deferred_control: VecDeque<(Message, usize)>,
+    /// Receive: data frames are returned as they are, see [`WebSocket::read_fragment`].
+    read_fragments: bool,
+    /// Receive: payload size so far of the message being read by fragments.
+    fragmented_len: Option<usize>,
+    /// Receive: kind and size so far of the message being read by [`WebSocket::read_into`].
+    reading_into: Option<(MessageKind, usize)>,
     /// Send in addition to regular messages E.g. "pong" or "close".
-    additional_send: Option<Frame>,
+    additional_send: VecDeque<Frame>,
+    /// Send: a heartbeat pong, kept apart from the automatic replies in `additional_send`.
+    heartbeat: Option<Frame>,
     /// True indicates there is an additional message (like a pong)
     /// that failed to flush previously and we should try again.
     unflushed_additional: bool,
+    /// Received control frames, if they are delivered separately from messages.
+    control_events: Vec<ControlEvent>,
+    /// Compression counters, present only if `permessage-deflate` is in use.
+    compression_stats: Option<CompressionStats>,
+    /// Observer of every frame read or written.
+    frame_hook: Option<FrameHook>,
+    /// Negotiated extension transforming data frames.
+    extension: Option<Box<dyn WebSocketExtension>>,
+    /// Send: the message being written skips the extension, see [`WebSocket::send_uncompressed`].
+    bypass_extension: bool,
+    /// Receive: time limit for reading a whole message.
+    read_timeout: Option<Duration>,
+    /// Receive: number of messages received so far, see [`WebSocketConfig::max_messages`].
+    received_messages: usize,
+    /// Receive: number of bytes received so far, see [`WebSocketConfig::max_total_bytes`].
+    received_bytes: usize,
+    /// Headers of the handshake request, see [`WebSocketConfig::retain_request_headers`].
+    #[cfg(feature = "handshake")]
+    request_headers: Option<HeaderMap>,
     /// The configuration for the websocket session.
     config: WebSocketConfig,
 }
@@ -247,6 +1025,13 @@ impl WebSocketContext {
         )
     }
 
+    /// Returns the data read from the stream but not parsed yet.
+    ///
+    /// See [`WebSocket::into_parts`].
+    pub fn into_unparsed(self) -> Vec<u8> {
+        self.frame.into_unparsed().into()
+    }
+
     fn _new(mode: OperationMode, mut frame: FrameCodec, config: WebSocketConfig) -> Self {
         config.asset_valid();
 
@@ -258,22 +1043,41 @@ impl WebSocketContext {
             frame,
             state: WebSocketState::Active,
             incomplete: None,
-            additional_send: None,
+            incomplete_wire_len: 0,
+            deferred_control: VecDeque::new(),
+            read_fragments: false,
+            fragmented_len: None,
+            reading_into: None,
+            additional_send: VecDeque::new(),
+            heartbeat: None,
             unflushed_additional: false,
+            control_events: Vec::new(),
+            compression_stats: None,
+            frame_hook: None,
+            extension: None,
+            bypass_extension: false,
+            read_timeout: None,
+            received_messages: 0,
+            received_bytes: 0,
+            #[cfg(feature = "handshake")]
+            request_headers: None,
             config,
         }
     }
 
-    /// Change the configuration.
+    /// Change the configuration, returning the value returned by `func`.
     ///
     /// # Panics
     /// Panics if config is invalid e.g. `max_write_buffer_size <= write_buffer_size`.
-    pub fn set_config(&mut self, func: impl FnOnce(&mut WebSocketConfig)) {
-        func(&mut self.config);
+    pub fn set_config<R>(&mut self, func: impl FnOnce(&mut WebSocketConfig) -> R) -> R {
+        let result = func(&mut self.config);
 
         self.config.asset_valid();
+        self.frame.in_buffer_max_read(self.config.read_buffer_size);
         self.frame.max_out_buffer_len(self.config.max_write_buffer_size);
         self.frame.out_buffer_write_len(self.config.write_buffer_size);
+
+        result
     }
 
     /// Read the configuration.
@@ -281,6 +1085,12 @@ impl WebSocketContext {
         &self.config
     }
 
+    /// See [`WebSocket::request_headers`].
+    #[cfg(feature = "handshake")]
+    pub fn request_headers(&self) -> Option<&HeaderMap> {
+        self.request_headers.as_ref()
+    }
+
     /// Check if it is possible to read messages.
     ///
     /// Reading is impossible after receiving `Message::Close`. It is still possible after
@@ -289,6 +1099,34 @@ impl WebSocketContext {
         self.state.can_read()
     }
 
+    /// Number of bytes currently held in the write buffer, waiting to be written to the stream.
+    ///
+    /// Writes fail with [`Error::WriteBufferFull`] once a frame would take this over
+    /// [`write_buffer_capacity`](Self::write_buffer_capacity).
+    pub fn write_buffer_len(&self) -> usize {
+        self.frame.out_len()
+    }
+
+    /// See [`WebSocket::has_pending_control`].
+    pub fn has_pending_control(&self) -> bool {
+        !self.additional_send.is_empty() || self.heartbeat.is_some() || self.unflushed_additional
+    }
+
+    /// See [`WebSocket::pending_out_bytes`].
+    pub fn pending_out_bytes(&self) -> usize {
+        // Queued frames are not masked yet, a client adds the mask when buffering them.
+        let mask_len = if self.mode == OperationMode::Client { 4 } else { 0 };
+        let queued: usize =
+            self.additional_send.iter().chain(&self.heartbeat).map(|f| f.len() + mask_len).sum();
+
+        self.frame.out_len() + queued
+    }
+
+    /// The configured [`WebSocketConfig::max_write_buffer_size`].
+    pub fn write_buffer_capacity(&self) -> usize {
+        self.config.max_write_buffer_size
+    }
+
     /// Check if it is possible to write messages.
     ///
     /// Writing gets impossible immediately after sending or receiving `Message::Close`.
@@ -296,15 +1134,162 @@ impl WebSocketContext {
         self.state.is_active()
     }
 
+    /// See [`WebSocket::mode`].
+    pub fn mode(&self) -> OperationMode {
+        self.mode
+    }
+
+    /// See [`WebSocket::state`].
+    pub fn state(&self) -> ConnectionState {
+        self.state.into()
+    }
+
+    /// Take the control frames received since the last call.
+    ///
+    /// Only populated when [`WebSocketConfig::control_events`] is enabled.
+    pub fn take_control_events(&mut self) -> Vec<ControlEvent> {
+        take(&mut self.control_events)
+    }
+
+    /// Returns the compression counters of this connection.
+    ///
+    /// `None` if `permessage-deflate` is not in use on this connection.
+    pub fn compression_stats(&self) -> Option<CompressionStats> {
+        self.compression_stats
+    }
+
+    /// Reset the compression counters of this connection, if any.
+    pub fn reset_compression_stats(&mut self) {
+        if let Some(stats) = self.compression_stats.as_mut() {
+            stats.reset();
+        }
+    }
+
+    /// Set a callback invoked with the header and payload length of every frame read or written.
+    ///
+    /// See [`WebSocket::on_frame`].
+    pub fn on_frame<F>(&mut self, hook: F)
+    where
+        F: FnMut(&FrameHeader, usize) + Send + 'static,
+    {
+        self.frame_hook = Some(FrameHook(Box::new(hook)));
+    }
+
+    /// See [`WebSocket::set_extension`].
+    pub fn set_extension(&mut self, extension: Option<Box<dyn WebSocketExtension>>) {
+        let deflate = extension
+            .as_ref()
+            .map_or(false, |ext| ext.name() == NegotiatedCompression::EXTENSION_NAME);
+        self.compression_stats = deflate.then(CompressionStats::default);
+        self.extension = extension;
+    }
+
+    /// See [`WebSocket::extension`].
+    pub fn extension(&self) -> Option<&dyn WebSocketExtension> {
+        self.extension.as_deref()
+    }
+
     /// Read a message from the provided stream, if possible.
     ///
     /// This function sends pong and close responses automatically.
     /// However, it never blocks on write.
+    ///
+    /// If the peer violates the protocol, a close frame with [`CloseCode::Protocol`] is sent
+    /// before the [`Error::Protocol`] is returned.
     pub fn read<T: Read + Write>(&mut self, stream: &mut T) -> Result<Message> {
+        self.read_with_size(stream).map(|(msg, _)| msg)
+    }
+
+    /// Read a message from a non-blocking stream, `None` if the stream would block.
+    ///
+    /// See [`WebSocket::try_read`].
+    pub fn try_read<T: Read + Write>(&mut self, stream: &mut T) -> Result<Option<Message>> {
+        self.read(stream).no_block()
+    }
+
+    /// Read a message from the provided stream along with its size on the wire.
+    ///
+    /// The size is the sum of the headers and payloads of all frames making up the message,
+    /// as opposed to [`Message::len`] which is the size of the reassembled payload.
+    /// Otherwise the same as [`read`](Self::read).
+    pub fn read_with_size<T: Read + Write>(&mut self, stream: &mut T) -> Result<(Message, usize)> {
+        match self.read_timeout {
+            Some(timeout) => {
+                let deadline = Instant::now() + timeout;
+                self.read_message(&mut DeadlineCheckStream { stream, deadline })
+            }
+            None => self.read_message(stream),
+        }
+    }
+
+    /// Read a single frame from the provided stream.
+    ///
+    /// See [`WebSocket::read_fragment`].
+    pub fn read_fragment<T: Read + Write>(&mut self, stream: &mut T) -> Result<Fragment> {
+        self.read_fragments = true;
+        let result = self.read_with_size(stream);
+        self.read_fragments = false;
+
+        result.map(|(msg, _)| msg.into())
+    }
+
+    /// Read the next data message, appending its payload to `buf`.
+    ///
+    /// See [`WebSocket::read_into`].
+    pub fn read_into<T: Read + Write>(
+        &mut self,
+        stream: &mut T,
+        buf: &mut Vec<u8>,
+    ) -> Result<MessageKind> {
+        loop {
+            let fragment = self.read_fragment(stream)?;
+
+            let (kind, len) = match (fragment.opcode, self.reading_into) {
+                (OpCode::Data(Data::Text), _) => (MessageKind::Text, 0),
+                (OpCode::Data(Data::Binary), _) => (MessageKind::Binary, 0),
+                (OpCode::Data(_), Some(reading)) => reading,
+                // Continuations of a message started by `read_fragment` end up here too.
+                (OpCode::Data(_), None) | (OpCode::Control(_), _) => continue,
+            };
+
+            let len = len + fragment.payload.len();
+            buf.extend_from_slice(&fragment.payload);
+
+            if !fragment.is_final {
+                self.reading_into = Some((kind, len));
+                continue;
+            }
+
+            self.reading_into = None;
+            if kind == MessageKind::Text {
+                std::str::from_utf8(&buf[buf.len() - len..])?;
+            }
+
+            return Ok(kind);
+        }
+    }
+
+    /// Set a time limit for every read of a whole message.
+    ///
+    /// See [`WebSocket::read_timeout`].
+    pub fn read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    fn read_message<T: Read + Write>(&mut self, stream: &mut T) -> Result<(Message, usize)> {
         self.state.check_if_terminated()?;
 
+        if self.incomplete.is_none() {
+            if let Some(deferred) = self.deferred_control.pop_front() {
+                return Ok(deferred);
+            }
+        }
+
         loop {
-            if self.additional_send.is_some() || self.unflushed_additional {
+            if !self.additional_send.is_empty()
+                || self.heartbeat.is_some()
+                || self.unflushed_additional
+            {
                 match self.flush(stream) {
                     Ok(_) => {}
                     Err(Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {
@@ -312,13 +1297,47 @@ impl WebSocketContext {
                     }
                     Err(e) => return Err(e),
                 }
-            } else if self.mode == OperationMode::Server && !self.state.can_read() {
+            } else if self.closes_transport() {
                 self.state = WebSocketState::Terminated;
                 return Err(Error::ConnectionClosed);
             }
 
-            if let Some(msg) = self._read(stream)? {
-                return Ok(msg);
+            match self._read(stream) {
+                Ok(Some(msg)) => {
+                    let size = if msg.is_data() {
+                        take(&mut self.incomplete_wire_len)
+                    } else {
+                        self.frame.last_read_len()
+                    };
+
+                    let defer = self.config.defer_control_during_fragment
+                        && self.incomplete.is_some()
+                        && matches!(msg, Message::Ping(_) | Message::Pong(_));
+                    if defer {
+                        self.deferred_control.push_back((msg, size));
+                        continue;
+                    }
+
+                    return Ok((msg, size));
+                }
+                Ok(None) => {}
+                Err(e @ Error::Protocol(_)) => {
+                    // The peer violated the protocol: fail the connection with 1002.
+                    if self.state.is_active() && !self.config.passive {
+                        self.state = WebSocketState::ClosedByUs;
+                        self.set_additional(Frame::new_close(Some(CloseFrame {
+                            code: CloseCode::Protocol,
+                            reason: Utf8Bytes::default(),
+                        })));
+                    }
+
+                    // Best effort to get a queued close frame out before giving up.
+                    if !self.additional_send.is_empty() {
+                        let _ = self.flush(stream);
+                    }
+                    return Err(e);
+                }
+                Err(e) => return Err(e),
             }
         }
     }
@@ -336,20 +1355,29 @@ impl WebSocketContext {
     pub fn write<T: Read + Write>(&mut self, stream: &mut T, msg: Message) -> Result<()> {
         self.state.check_if_terminated()?;
 
-        if !self.state.is_active() {
+        let passive_close_reply = self.config.passive
+            && self.state == WebSocketState::ClosedByPeer
+            && matches!(msg, Message::Close(_));
+        if !self.state.is_active() && !passive_close_reply {
             return Err(Error::Protocol(ProtocolError::SendAfterClose));
         }
 
         let frame = match msg {
-            Message::Text(data) => Frame::new_data(data, OpCode::Data(Data::Text), true),
-            Message::Binary(data) => Frame::new_data(data, OpCode::Data(Data::Binary), true),
+            Message::Text(data) => return self.write_data(stream, data.into(), Data::Text),
+            Message::Binary(data) => return self.write_data(stream, data, Data::Binary),
             Message::Ping(data) => Frame::new_ping(data),
             Message::Pong(data) => {
                 self.set_additional(Frame::new_pong(data));
                 return self._write(stream, None).map(|_| ());
             }
             Message::Close(code) => return self.close(stream, code),
-            Message::Frame(f) => f,
+            Message::Frame(f) => {
+                check_outgoing_frame(&f)?;
+                if f.is_close() {
+                    self.state = WebSocketState::ClosedByUs;
+                }
+                f
+            }
         };
 
         let should_flush = self._write(stream, Some(frame))?;
@@ -360,6 +1388,231 @@ impl WebSocketContext {
         Ok(())
     }
 
+    /// Send a pre-built frame to the provided stream and flush.
+    ///
+    /// See [`WebSocket::send_frame`].
+    pub fn send_frame<T: Read + Write>(&mut self, stream: &mut T, frame: Frame) -> Result<()> {
+        self.state.check_if_terminated()?;
+        if !self.state.is_active() {
+            return Err(Error::Protocol(ProtocolError::SendAfterClose));
+        }
+
+        check_outgoing_frame(&frame)?;
+        if frame.is_close() {
+            self.state = WebSocketState::ClosedByUs;
+        }
+
+        self._write(stream, Some(frame))?;
+        self.flush(stream)
+    }
+
+    /// Write a message to the provided stream without passing it through the extension, and
+    /// flush.
+    ///
+    /// See [`WebSocket::send_uncompressed`].
+    pub fn send_uncompressed<T: Read + Write>(
+        &mut self,
+        stream: &mut T,
+        msg: Message,
+    ) -> Result<()> {
+        self.bypass_extension = true;
+        let result = self.write(stream, msg);
+        self.bypass_extension = false;
+
+        result?;
+        self.flush(stream)
+    }
+
+    /// Write a text or binary message, fragmented according to [`WebSocketConfig::max_frame_size`].
+    fn write_data<T: Read + Write>(
+        &mut self,
+        stream: &mut T,
+        data: Bytes,
+        kind: Data,
+    ) -> Result<()> {
+        check_max_size(data.len(), self.config.max_message_size)?;
+
+        let max = match self.config.max_frame_size {
+            Some(max) if data.len() > max => max.max(1),
+            _ => {
+                let frame = Frame::new_data(data, OpCode::Data(kind), true);
+                if self._write(stream, Some(frame))? {
+                    self.flush(stream)?;
+                }
+                return Ok(());
+            }
+        };
+
+        // Either all fragments are buffered or none, a partial message would corrupt the stream.
+        let frames = (data.len() + max - 1) / max;
+        let size = data.len() + frames * FrameHeader::MAX_HEADER_SIZE;
+        if self.frame.out_len() + size > self.config.max_write_buffer_size {
+            return Err(Error::WriteBufferFull);
+        }
+
+        let mut opcode = OpCode::Data(kind);
+        let mut rest = data;
+        let mut result = Ok(());
+
+        while !rest.is_empty() {
+            let fragment = rest.split_to(rest.len().min(max));
+            let frame = Frame::new_data(fragment, opcode, rest.is_empty());
+            opcode = OpCode::Data(Data::Continuation);
+
+            // The frame is buffered even if writing the buffer out failed, keep going.
+            match self.buffer_frame(stream, frame) {
+                Err(e @ Error::Io(_)) if result.is_ok() => result = Err(e),
+                Err(Error::Io(_)) | Ok(()) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        result?;
+        if self._write(stream, None)? {
+            self.flush(stream)?;
+        }
+
+        Ok(())
+    }
+
+    /// Send a message read from `reader` to the provided stream and flush.
+    ///
+    /// See [`WebSocket::send_from_reader`].
+    pub fn send_from_reader<T: Read + Write>(
+        &mut self,
+        stream: &mut T,
+        mut reader: impl Read,
+        kind: Data,
+    ) -> Result<u64> {
+        self.state.check_if_terminated()?;
+        if !self.state.is_active() {
+            return Err(Error::Protocol(ProtocolError::SendAfterClose));
+        }
+
+        let chunk_size = match self.config.max_frame_size {
+            Some(max) => max.min(self.config.write_buffer_size),
+            None => self.config.write_buffer_size,
+        }
+        .max(1);
+        let read_chunk = |reader: &mut dyn Read| -> io::Result<Vec<u8>> {
+            let mut chunk = Vec::with_capacity(chunk_size);
+            reader.take(chunk_size as u64).read_to_end(&mut chunk)?;
+            Ok(chunk)
+        };
+
+        let mut chunk = read_chunk(&mut reader)?;
+        let mut opcode = OpCode::Data(kind);
+        let mut size = 0;
+        // Some fragments of the message were buffered, so it cannot be taken back.
+        let mut started = false;
+
+        loop {
+            size += chunk.len();
+            if let Err(e) = check_max_size(size, self.config.max_message_size) {
+                return self.abort_message(stream, started, CloseCode::Size, e);
+            }
+
+            // Read ahead, the last fragment must be marked as such.
+            let next =
+                if chunk.len() < chunk_size { Ok(Vec::new()) } else { read_chunk(&mut reader) };
+            let fin = matches!(&next, Ok(next) if next.is_empty());
+
+            // Any error but a full write buffer comes from the stream, after buffering the frame.
+            let written = self._write(stream, Some(Frame::new_data(chunk, opcode, fin)));
+            let buffered = !matches!(written, Err(Error::WriteBufferFull));
+            let written = match written {
+                Ok(true) => self.flush(stream),
+                Ok(false) => Ok(()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = written {
+                // With the last fragment buffered the message is complete, flushing may resume.
+                if fin && buffered {
+                    return Err(e);
+                }
+                return self.abort_message(stream, started || buffered, CloseCode::Error, e);
+            }
+            started = true;
+            opcode = OpCode::Data(Data::Continuation);
+
+            match next {
+                Ok(_) if fin => break,
+                Ok(next) => chunk = next,
+                Err(e) => return self.abort_message(stream, started, CloseCode::Error, e.into()),
+            }
+        }
+
+        self.flush(stream)?;
+        Ok(size as u64)
+    }
+
+    /// Give up on a message of which fragments may have been sent already, see
+    /// [`send_from_reader`](Self::send_from_reader).
+    ///
+    /// Once `started`, the connection is closed so that nothing else is sent in the middle of
+    /// the message. `error` is returned either way.
+    fn abort_message<T: Read + Write, R>(
+        &mut self,
+        stream: &mut T,
+        started: bool,
+        code: CloseCode,
+        error: Error,
+    ) -> Result<R> {
+        // Nothing was sent yet, the connection is still usable.
+        if !started {
+            return Err(error);
+        }
+
+        // Closing stops later writes even if the close frame cannot be sent, e.g. because the
+        // stream failed, and that failure tells nothing more than `error`.
+        let close = CloseFrame { code, reason: Utf8Bytes::default() };
+        let _ = self.close(stream, Some(close));
+        Err(error)
+    }
+
+    /// Write several messages to the provided stream and flush once at the end.
+    ///
+    /// On error the messages that were not written are returned along with it,
+    /// see [`WebSocket::write_batch`].
+    pub fn write_batch<T, I>(
+        &mut self,
+        stream: &mut T,
+        msgs: I,
+    ) -> std::result::Result<(), (Error, Vec<Message>)>
+    where
+        T: Read + Write,
+        I: IntoIterator<Item = Message>,
+    {
+        let mut msgs = msgs.into_iter();
+
+        while let Some(msg) = msgs.next() {
+            if let Err(e) = self.write(stream, msg.clone()) {
+                // I/O errors happen once the frame is already in the write buffer.
+                let buffered = matches!(e, Error::Io(_) | Error::ConnectionClosed);
+                let failed = if buffered { None } else { Some(msg) };
+                return Err((e, failed.into_iter().chain(msgs).collect()));
+            }
+        }
+
+        self.flush(stream).map_err(|e| (e, Vec::new()))
+    }
+
+    /// Send an unsolicited pong to the provided stream, see [`WebSocket::send_heartbeat_pong`].
+    pub fn send_heartbeat_pong<T: Read + Write>(
+        &mut self,
+        stream: &mut T,
+        data: Bytes,
+    ) -> Result<()> {
+        self.state.check_if_terminated()?;
+
+        if !self.state.is_active() {
+            return Err(Error::Protocol(ProtocolError::SendAfterClose));
+        }
+
+        self.heartbeat = Some(Frame::new_pong(data));
+        self.flush(stream)
+    }
+
     /// Flush writes.
     ///
     /// Ensures all messages previously passed to [`write`](Self::write) and automatically
@@ -376,6 +1629,29 @@ impl WebSocketContext {
         Ok(())
     }
 
+    /// Flush writes to a non-blocking stream, `false` if the stream would block.
+    ///
+    /// See [`WebSocket::try_flush`].
+    pub fn try_flush<T: Read + Write>(&mut self, stream: &mut T) -> Result<bool> {
+        self.flush(stream).no_block().map(|flushed| flushed.is_some())
+    }
+
+    /// Send a close frame to the provided stream and keep reading.
+    ///
+    /// See [`WebSocket::send_close_and_continue`].
+    pub fn send_close_and_continue<T: Read + Write>(
+        &mut self,
+        stream: &mut T,
+        code: Option<CloseFrame>,
+    ) -> Result<()> {
+        self.state.check_if_terminated()?;
+        if !self.state.is_active() {
+            return Err(Error::Protocol(ProtocolError::SendAfterClose));
+        }
+
+        self.close(stream, code)
+    }
+
     /// Close the connection.
     ///
     /// This function guarantees that the close frame will be queued.
@@ -386,32 +1662,82 @@ impl WebSocketContext {
         stream: &mut T,
         code: Option<CloseFrame>,
     ) -> Result<()> {
-        if let WebSocketState::Active = self.state {
-            self.state = WebSocketState::ClosedByServer;
+        if let Some(CloseFrame { code, .. }) = code {
+            if !code.allowed() {
+                return Err(Error::Protocol(ProtocolError::InvalidCloseCode(code)));
+            }
+        }
+
+        match self.state {
+            WebSocketState::Active => {
+                self.state = WebSocketState::ClosedByUs;
+
+                let frame = Frame::new_close(code);
 
-            let frame = Frame::new_close(code);
+                self._write(stream, Some(frame))?;
+            }
+            WebSocketState::ClosedByPeer if self.config.passive => {
+                self.state = WebSocketState::CloseAcknowledged;
 
-            self._write(stream, Some(frame))?;
+                let frame = Frame::new_close(code);
+
+                self._write(stream, Some(frame))?;
+            }
+            _ => {}
         }
 
         self.flush(stream)
     }
 
     fn _read<T: Read>(&mut self, stream: &mut T) -> Result<Option<Message>> {
-        if let Some(frame) = self
+        // A client unmasks frames only if it tolerates masking servers, which may still send
+        // unmasked frames as they should.
+        let (unmask, accept_unmasked) = match self.mode {
+            OperationMode::Server => (true, self.config.accept_unmasked_frames),
+            OperationMode::Client => (self.config.accept_masked_frames_from_server, true),
+        };
+
+        if let Some(mut frame) = self
             .frame
-            .read(
-                stream,
-                self.config.max_frame_size,
-                matches!(self.mode, OperationMode::Server),
-                self.config.accept_unmasked_frames,
-            )
+            .read(stream, self.config.max_frame_size, unmask, accept_unmasked)
             .check_connection_reset(self.state)?
         {
+            if let Some(FrameHook(hook)) = self.frame_hook.as_mut() {
+                hook(frame.header(), frame.payload().len());
+            }
+
+            self.check_connection_limits(&frame)?;
+
             if !self.state.can_read() {
                 return Err(Error::Protocol(ProtocolError::ReceiveAfterClose));
             }
 
+            if let Some(extension) = self.extension.as_mut() {
+                if let OpCode::Data(_) = frame.header().opcode {
+                    // Bound the expanded payload before it is produced, not after.
+                    let received = self
+                        .fragmented_len
+                        .or_else(|| self.incomplete.as_ref().map(IncompleteMessage::len))
+                        .unwrap_or(0);
+                    let max_size = match (self.config.max_message_size, self.config.max_frame_size)
+                    {
+                        (Some(message), frame) => {
+                            let left = message.saturating_sub(received);
+                            Some(frame.map_or(left, |frame| frame.min(left)))
+                        }
+                        (None, frame) => frame,
+                    };
+
+                    let compressed = frame.payload().len();
+                    extension.transform_incoming(&mut frame, max_size)?;
+
+                    if let Some(stats) = self.compression_stats.as_mut() {
+                        stats.received_compressed += compressed as u64;
+                        stats.received_uncompressed += frame.payload().len() as u64;
+                    }
+                }
+            }
+
             let header = frame.header();
             if header.rsv1 || header.rsv2 || header.rsv3 {
                 return Err(Error::Protocol(ProtocolError::NonZeroReservedBits));
@@ -429,22 +1755,69 @@ impl WebSocketContext {
                     _ if frame.payload().len() > MAX_CONTROL_FRAME_PAYLOAD => {
                         Err(Error::Protocol(ProtocolError::ControlFrameTooBig))
                     }
-                    Control::Close => Ok(self.try_close(frame.into_close()?).map(Message::Close)),
+                    Control::Close => match frame.into_close() {
+                        Ok(Some(CloseFrame { code, .. })) if !code.allowed() => Err(self
+                            .reject_close(
+                                CloseCode::Protocol,
+                                ProtocolError::InvalidCloseCode(code),
+                            )),
+                        Ok(close) => Ok(self.try_close(close).map(Message::Close)),
+                        Err(Error::Utf8(_)) => {
+                            Err(self
+                                .reject_close(CloseCode::Invalid, ProtocolError::InvalidCloseFrame))
+                        }
+                        Err(Error::Protocol(e)) => Err(self.reject_close(CloseCode::Protocol, e)),
+                        Err(e) => Err(e),
+                    },
                     Control::Reserved(code) => {
                         Err(Error::Protocol(ProtocolError::UnknownControlOpCode(code)))
                     }
                     Control::Ping => {
                         let data = frame.into_payload();
-                        if self.state.is_active() {
+                        if self.state.is_active() && self.config.auto_pong && !self.config.passive {
                             self.set_additional(Frame::new_pong(data.clone()));
                         }
 
-                        Ok(Some(Message::Ping(data)))
+                        if self.config.control_events {
+                            self.control_events.push(ControlEvent::Ping(data));
+                            Ok(None)
+                        } else {
+                            Ok(Some(Message::Ping(data)))
+                        }
+                    }
+                    Control::Pong => {
+                        let data = frame.into_payload();
+
+                        if self.config.control_events {
+                            self.control_events.push(ControlEvent::Pong(data));
+                            Ok(None)
+                        } else {
+                            Ok(Some(Message::Pong(data)))
+                        }
                     }
-                    Control::Pong => Ok(Some(Message::Pong(frame.into_payload()))),
                 },
+                OpCode::Data(data) if self.read_fragments => {
+                    let len = match (data, self.fragmented_len) {
+                        (Data::Continuation, Some(len)) => len,
+                        (Data::Text | Data::Binary, None) if self.incomplete.is_none() => 0,
+                        (Data::Continuation, None) => {
+                            return Err(Error::Protocol(ProtocolError::UnexpectedContinue))
+                        }
+                        (Data::Reserved(code), _) => {
+                            return Err(Error::Protocol(ProtocolError::UnknownDataOpCode(code)))
+                        }
+                        (data, _) => {
+                            return Err(Error::Protocol(ProtocolError::ExpectedFragment(data)))
+                        }
+                    } + frame.payload().len();
+                    check_max_size(len, self.config.max_message_size)?;
+
+                    self.fragmented_len = if frame.header().fin { None } else { Some(len) };
+                    Ok(Some(Message::Frame(frame)))
+                }
                 OpCode::Data(data) => {
                     let fin = frame.header().fin;
+                    self.incomplete_wire_len += self.frame.last_read_len();
 
                     match data {
                         Data::Continuation => {
@@ -497,6 +1870,9 @@ impl WebSocketContext {
                 WebSocketState::ClosedByPeer | WebSocketState::CloseAcknowledged => {
                     Err(Error::ConnectionClosed)
                 }
+                _ if self.frame.has_partial_frame() => {
+                    Err(Error::Protocol(ProtocolError::TruncatedFrame))
+                }
                 _ => Err(Error::Protocol(ProtocolError::ResetWithoutClosing)),
             }
         }
@@ -507,20 +1883,29 @@ impl WebSocketContext {
             self.buffer_frame(stream, data)?;
         }
 
-        let should_flush = if let Some(msg) = self.additional_send.take() {
+        let mut should_flush = self.unflushed_additional;
+
+        // Never send a heartbeat after a close frame.
+        if let Some(heartbeat) = self.heartbeat.take().filter(|_| self.state.is_active()) {
+            match self.buffer_frame(stream, heartbeat.clone()) {
+                Err(Error::WriteBufferFull) => self.heartbeat = Some(heartbeat),
+                Err(e) => return Err(e),
+                Ok(_) => should_flush = true,
+            }
+        }
+
+        while let Some(msg) = self.additional_send.pop_front() {
             match self.buffer_frame(stream, msg.clone()) {
                 Err(Error::WriteBufferFull) => {
-                    self.set_additional(msg);
-                    false
+                    self.additional_send.push_front(msg);
+                    break;
                 }
                 Err(e) => return Err(e),
-                Ok(_) => true,
+                Ok(_) => should_flush = true,
             }
-        } else {
-            self.unflushed_additional
-        };
+        }
 
-        if self.mode == OperationMode::Server && !self.state.can_read() {
+        if self.closes_transport() {
             self.frame.write_out(stream)?;
             self.state = WebSocketState::Terminated;
 
@@ -530,6 +1915,13 @@ impl WebSocketContext {
         }
     }
 
+    /// Tell if the close handshake is complete and this endpoint is the one to drop the
+    /// connection. That is the server, a client waits for the server to close the stream and
+    /// terminates on EOF instead.
+    fn closes_transport(&self) -> bool {
+        self.mode == OperationMode::Server && !self.state.can_read()
+    }
+
     /// Received a close frame. Tells if we need to return a close frame to the user.
     #[allow(clippy::option_option)]
     fn try_close(&mut self, close: Option<CloseFrame>) -> Option<Option<CloseFrame>> {
@@ -537,24 +1929,15 @@ impl WebSocketContext {
             WebSocketState::Active => {
                 self.state = WebSocketState::ClosedByPeer;
 
-                let close = close.map(|frame| {
-                    if !frame.code.allowed() {
-                        CloseFrame {
-                            code: CloseCode::Protocol,
-                            reason: Utf8Bytes::from_static("Protocol violatoin"),
-                        }
-                    } else {
-                        frame
-                    }
-                });
-
-                let reply = Frame::new_close(close.clone());
-                self.set_additional(reply);
+                if !self.config.passive {
+                    let reply = Frame::new_close(close.clone());
+                    self.set_additional(reply);
+                }
 
                 Some(close)
             }
             WebSocketState::ClosedByPeer | WebSocketState::CloseAcknowledged => None,
-            WebSocketState::ClosedByServer => {
+            WebSocketState::ClosedByUs => {
                 self.state = WebSocketState::CloseAcknowledged;
                 Some(close)
             }
@@ -562,28 +1945,108 @@ impl WebSocketContext {
         }
     }
 
+    /// Handle a close frame from the peer that could not be accepted.
+    ///
+    /// Replies with a close frame carrying `code` unless we have already sent one,
+    /// and returns the error to report to the caller.
+    fn reject_close(&mut self, code: CloseCode, error: ProtocolError) -> Error {
+        match self.state {
+            WebSocketState::Active => {
+                self.state = WebSocketState::ClosedByPeer;
+                if !self.config.passive {
+                    self.set_additional(Frame::new_close(Some(CloseFrame {
+                        code,
+                        reason: Utf8Bytes::default(),
+                    })));
+                }
+            }
+            WebSocketState::ClosedByUs => self.state = WebSocketState::CloseAcknowledged,
+            _ => {}
+        }
+
+        Error::Protocol(error)
+    }
+
+    /// Count a received frame against the connection limits.
+    ///
+    /// Closes the connection with [`CloseCode::Policy`] once a limit is exceeded. The close
+    /// handshake itself is not limited.
+    fn check_connection_limits(&mut self, frame: &Frame) -> Result<()> {
+        // Control frames are never fragmented, so every final frame ends a message.
+        if frame.header().fin {
+            self.received_messages += 1;
+        }
+        self.received_bytes = self.received_bytes.saturating_add(self.frame.last_read_len());
+
+        let exceeded = self.config.max_messages.map_or(false, |max| self.received_messages > max)
+            || self.config.max_total_bytes.map_or(false, |max| self.received_bytes > max);
+
+        if exceeded && self.state.is_active() {
+            self.state = WebSocketState::ClosedByUs;
+            if !self.config.passive {
+                self.set_additional(Frame::new_close(Some(CloseFrame {
+                    code: CloseCode::Policy,
+                    reason: Utf8Bytes::default(),
+                })));
+            }
+
+            return Err(Error::Protocol(ProtocolError::ConnectionLimitExceeded));
+        }
+
+        Ok(())
+    }
+
     /// Write a single frame into the write-buffer.
     fn buffer_frame<T>(&mut self, stream: &mut T, mut frame: Frame) -> Result<()>
     where
         T: Read + Write,
     {
+        if let Some(extension) = self.extension.as_mut().filter(|_| !self.bypass_extension) {
+            if let OpCode::Data(_) = frame.header().opcode {
+                let uncompressed = frame.payload().len();
+                extension.transform_outgoing(&mut frame);
+
+                if let Some(stats) = self.compression_stats.as_mut() {
+                    stats.sent_uncompressed += uncompressed as u64;
+                    stats.sent_compressed += frame.payload().len() as u64;
+                }
+            }
+        }
+
+        if let Some(FrameHook(hook)) = self.frame_hook.as_mut() {
+            hook(frame.header(), frame.payload().len());
+        }
+
         match self.mode {
-            OperationMode::Server => {}
+            OperationMode::Server => frame.header_mut().mask = None,
             OperationMode::Client => frame.set_random_mask(),
         }
+        debug_assert_eq!(frame.is_masked(), self.mode == OperationMode::Client);
 
         self.frame.write(stream, frame).check_connection_reset(self.state)
     }
 
-    /// Replace `additional_send` if it is currently a `Pong` message.
+    /// Queue an additional frame to be sent with the next write.
+    ///
+    /// Pongs are queued up to [`WebSocketConfig::max_pending_pongs`], dropping the oldest ones
+    /// first. Any other frame (i.e. a close reply) replaces the queued pongs, and nothing is
+    /// queued after it.
     fn set_additional(&mut self, additional: Frame) {
-        let empty_or_pong = self
-            .additional_send
-            .as_ref()
-            .map_or(true, |f| f.header().opcode == OpCode::Control(Control::Pong));
+        let is_pong = |f: &Frame| f.header().opcode == OpCode::Control(Control::Pong);
+
+        if !self.additional_send.iter().all(is_pong) {
+            return;
+        }
 
-        if empty_or_pong {
-            self.additional_send.replace(additional);
+        if is_pong(&additional) {
+            self.additional_send.push_back(additional);
+
+            while self.additional_send.len() > self.config.max_pending_pongs {
+                self.additional_send.pop_front();
+            }
+        } else {
+            self.additional_send.clear();
+            self.additional_send.push_back(additional);
         }
     }
 }
@@ -598,13 +2061,44 @@ fn check_max_size(size: usize, max: Option<usize>) -> Result<()> {
     Ok(())
 }
 
+/// Check a frame built by the user against the rules the read path enforces on the peer.
+fn check_outgoing_frame(frame: &Frame) -> Result<()> {
+    let header = frame.header();
+    match header.opcode {
+        OpCode::Control(Control::Reserved(code)) => {
+            Err(Error::Protocol(ProtocolError::UnknownControlOpCode(code)))
+        }
+        OpCode::Data(Data::Reserved(code)) => {
+            Err(Error::Protocol(ProtocolError::UnknownDataOpCode(code)))
+        }
+        OpCode::Control(_) if !header.fin => {
+            Err(Error::Protocol(ProtocolError::FragmentedControlFrame))
+        }
+        OpCode::Control(_) if frame.payload().len() > MAX_CONTROL_FRAME_PAYLOAD => {
+            Err(Error::Protocol(ProtocolError::ControlFrameTooBig))
+        }
+        _ => Ok(()),
+    }
+}
+
+type FrameCallback = Box<dyn FnMut(&FrameHeader, usize) + Send>;
+
+/// Callback observing frames, see [`WebSocket::on_frame`].
+struct FrameHook(FrameCallback);
+
+impl Debug for FrameHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameHook").finish_non_exhaustive()
+    }
+}
+
 /// The current connection state.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum WebSocketState {
     /// The connection is active.
     Active,
     /// We initiated a close handshake.
-    ClosedByServer,
+    ClosedByUs,
     /// The peer initiated a close handshake.
     ClosedByPeer,
     /// The peer replied to our close handshake.
@@ -623,7 +2117,7 @@ impl WebSocketState {
     /// but the remote hasn't confirmed, they might have sent data before they receive our
     /// close frame, so we should still pass those to client code, hence ClosedByUs is valid.
     fn can_read(self) -> bool {
-        matches!(self, Self::Active | Self::ClosedByServer)
+        matches!(self, Self::Active | Self::ClosedByUs)
     }
 
     /// Check if the state is active, return error if not.
@@ -635,16 +2129,86 @@ impl WebSocketState {
     }
 }
 
+/// Stream adapter failing reads started after the deadline.
+///
+/// Unlike [`DeadlineStream`] it works with any stream, but cannot interrupt a read in progress.
+struct DeadlineCheckStream<'s, T> {
+    stream: &'s mut T,
+    deadline: Instant,
+}
+
+impl<T: Read> Read for DeadlineCheckStream<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if Instant::now() >= self.deadline {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        self.stream.read(buf)
+    }
+}
+
+impl<T: Write> Write for DeadlineCheckStream<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Stream adapter limiting every read to the time left until the deadline.
+struct DeadlineStream<'s, T> {
+    stream: &'s mut T,
+    deadline: Instant,
+}
+
+impl<T: Read + ReadTimeout> Read for DeadlineStream<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::WouldBlock => io::ErrorKind::TimedOut.into(),
+            _ => e,
+        })
+    }
+}
+
+impl<T: Write> Write for DeadlineStream<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
 /// Translate "Connection reset by peer" into `ConnectionClosed` if appropriate.
 trait CheckConnectionReset {
     fn check_connection_reset(self, state: WebSocketState) -> Self;
 }
 
+/// Whether an I/O error is how the peer closing the connection shows up on some platforms or
+/// streams, instead of a clean end of the stream.
+fn is_closed_by_peer(kind: io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::UnexpectedEof
+    )
+}
+
 impl<T> CheckConnectionReset for Result<T> {
     fn check_connection_reset(self, state: WebSocketState) -> Self {
         match self {
             Err(Error::Io(e)) => Err({
-                if !state.can_read() && e.kind() == io::ErrorKind::ConnectionReset {
+                if !state.can_read() && is_closed_by_peer(e.kind()) {
                     Error::ConnectionClosed
                 } else {
                     Error::Io(e)
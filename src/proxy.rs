@@ -0,0 +1,143 @@
+//! HTTP `CONNECT` proxy tunneling
+
+use std::io::{Read, Write};
+
+use base64::Engine;
+use bytes::Buf;
+use http::{HeaderMap, StatusCode, Uri, Version};
+use httparse::{Status, EMPTY_HEADER};
+
+use crate::{
+    client::uri_host,
+    error::{Error, ProtocolError, Result, UrlError},
+    handshake::{
+        client::Response,
+        headers::{FromHttparse, MAX_HEADERS},
+        machine::AttackCheck,
+    },
+    protocol::config::WebSocketConfig,
+    ReadBuffer,
+};
+
+/// Returns the host and port to open the TCP connection to for the given proxy URI.
+pub(crate) fn proxy_addr(proxy: &Uri) -> Result<(&str, u16)> {
+    match proxy.scheme_str() {
+        Some("http") | None => {}
+        _ => return Err(Error::Url(UrlError::UnsupportedScheme)),
+    }
+
+    Ok((uri_host(proxy)?, proxy.port_u16().unwrap_or(80)))
+}
+
+/// Establishes a tunnel to `host:port` through the HTTP proxy the `stream` is connected to.
+/// An IPv6 `host` is given without brackets.
+///
+/// Sends a `CONNECT` request (with `Proxy-Authorization` if the proxy URI carries credentials)
+/// and waits for a successful response. Any non-2xx response is returned as [`Error::Http`].
+/// The response is read within the same limits as a WebSocket handshake, see
+/// [`WebSocketConfig::handshake_max_bytes`].
+pub(crate) fn tunnel<S: Read + Write>(
+    stream: &mut S,
+    proxy: &Uri,
+    host: &str,
+    port: u16,
+    config: Option<&WebSocketConfig>,
+) -> Result<()> {
+    let authority =
+        if host.contains(':') { format!("[{host}]:{port}") } else { format!("{host}:{port}") };
+    let mut req = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+    if let Some(credentials) = proxy_credentials(proxy) {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+        req.push_str(&format!("Proxy-Authorization: Basic {encoded}\r\n"));
+    }
+    req.push_str("\r\n");
+
+    stream.write_all(req.as_bytes())?;
+    stream.flush()?;
+
+    let mut buf = ReadBuffer::new();
+    let mut attack_check = AttackCheck::new(config.unwrap_or(&WebSocketConfig::default()));
+    loop {
+        let size = buf.read_from(stream)?;
+        if size == 0 {
+            return Err(Error::Protocol(ProtocolError::IncompleteHandshake));
+        }
+        attack_check.check_incoming_packet(size)?;
+
+        if let Some((size, res)) = parse_response(Buf::chunk(&buf))? {
+            buf.advance(size);
+
+            if !res.status().is_success() {
+                return Err(Error::Http(res));
+            }
+
+            if buf.has_remaining() {
+                return Err(Error::Protocol(ProtocolError::JunkAfterRequest));
+            }
+
+            return Ok(());
+        }
+    }
+}
+
+/// Returns the `user:password` part of the proxy URI authority, if any, with the user and
+/// password percent-decoded.
+fn proxy_credentials(proxy: &Uri) -> Option<Vec<u8>> {
+    let authority = proxy.authority()?.as_str();
+    let (credentials, _) = authority.rsplit_once('@')?;
+
+    let mut decoded = Vec::with_capacity(credentials.len());
+    match credentials.split_once(':') {
+        Some((user, password)) => {
+            percent_decode(user, &mut decoded);
+            decoded.push(b':');
+            percent_decode(password, &mut decoded);
+        }
+        None => percent_decode(credentials, &mut decoded),
+    }
+
+    Some(decoded)
+}
+
+/// Appends `input` to `out`, replacing `%XX` escapes by the byte they encode. Malformed escapes
+/// are kept as they are.
+fn percent_decode(input: &str, out: &mut Vec<u8>) {
+    fn hex(byte: u8) -> Option<u8> {
+        (byte as char).to_digit(16).map(|d| d as u8)
+    }
+
+    let input = input.as_bytes();
+    let mut i = 0;
+    while i < input.len() {
+        if input[i] == b'%' {
+            if let (Some(hi), Some(lo)) =
+                (input.get(i + 1).and_then(|&b| hex(b)), input.get(i + 2).and_then(|&b| hex(b)))
+            {
+                out.push(hi << 4 | lo);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(input[i]);
+        i += 1;
+    }
+}
+
+/// Parses a proxy response. Unlike the WebSocket handshake, proxies commonly reply with HTTP/1.0.
+fn parse_response(data: &[u8]) -> Result<Option<(usize, Response)>> {
+    let mut hbuffer = [EMPTY_HEADER; MAX_HEADERS];
+    let mut raw = httparse::Response::new(&mut hbuffer);
+
+    Ok(match raw.parse(data)? {
+        Status::Partial => None,
+        Status::Complete(size) => {
+            let mut res = Response::new(None);
+            *res.status_mut() = StatusCode::from_u16(raw.code.expect("Bug: no HTTP status code"))?;
+            *res.headers_mut() = HeaderMap::from_httparse(raw.headers)?;
+            *res.version_mut() =
+                if raw.version == Some(0) { Version::HTTP_10 } else { Version::HTTP_11 };
+
+            Some((size, res))
+        }
+    })
+}
@@ -0,0 +1,305 @@
+//! A client re-establishing its connection when it is lost
+
+use std::{
+    fmt::{Debug, Formatter},
+    io::{self, Read, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    client::{connect_with_options, ConnectOptions, IntoClientRequest},
+    error::{Error, ProtocolError, Result, UrlError},
+    handshake::client::Response,
+    protocol::{
+        config::WebSocketConfig, frame::CloseFrame, message::Message, websocket::WebSocket,
+    },
+    stream::SimplifiedStream,
+};
+
+type ConnectFn<S> = Box<dyn FnMut() -> Result<(WebSocket<S>, Response)> + Send>;
+
+/// A blocking client that connects again, with the same request and configuration, when the
+/// connection is lost.
+///
+/// Nothing happens until the first [`read`](Self::read) or [`send`](Self::send), which connect as
+/// needed. When a connection attempt fails, or the connection is lost while reading or sending,
+/// the client waits and connects again. The delay starts at [`initial_delay`](Self::initial_delay)
+/// and doubles after each consecutive failure up to [`max_delay`](Self::max_delay), reduced by a
+/// random amount of up to half so that clients dropped together do not reconnect together. After
+/// [`max_attempts`](Self::max_attempts) consecutive failures, the last error is returned. The
+/// count starts over with the next successful read or send.
+///
+/// Connection errors are I/O errors other than timeouts, [`Error::ConnectionClosed`], a reset
+/// without closing handshake, a connection closed during the handshake, an unreachable host and
+/// HTTP server errors. Any other error, e.g.
+/// an `401 Unauthorized` reply to the handshake, is returned right away.
+///
+/// Messages received in between are lost and a message being sent when the connection is lost
+/// is sent again on the new connection, so the peer may receive it twice. Once
+/// [`close`](Self::close) was called, the client no longer reconnects.
+///
+/// # Example
+/// ```
+/// # use std::{net::TcpListener, thread, time::Duration};
+/// # use blitz_ws::{accept, protocol::message::Message, ConnectOptions, ReconnectingClient};
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let url = format!("ws://{}/", listener.local_addr().unwrap());
+///
+/// let server = thread::spawn(move || {
+///     // Drop the first connection before the handshake.
+///     drop(listener.accept().unwrap());
+///
+///     let (stream, _) = listener.accept().unwrap();
+///     let mut ws = accept(stream).unwrap();
+///     let msg = ws.read().unwrap();
+///     ws.send(msg).unwrap();
+/// });
+///
+/// let mut client = ReconnectingClient::new(url, None, ConnectOptions::default())
+///     .unwrap()
+///     .initial_delay(Duration::from_millis(10));
+///
+/// client.send(Message::Text("Hello".into())).unwrap();
+/// assert_eq!(client.read().unwrap(), Message::Text("Hello".into()));
+/// assert!(client.response().is_some());
+/// server.join().unwrap();
+/// ```
+pub struct ReconnectingClient<S = SimplifiedStream<TcpStream>> {
+    connect: ConnectFn<S>,
+    socket: Option<WebSocket<S>>,
+    response: Option<Response>,
+    initial_delay: Duration,
+    max_delay: Duration,
+    max_attempts: u32,
+    /// Consecutive connection failures so far.
+    failures: u32,
+    /// Set by [`ReconnectingClient::close`], no more connections are made.
+    closing: bool,
+}
+
+impl ReconnectingClient {
+    /// Create a client connecting with [`connect_with_options`], following up to 3 redirects.
+    ///
+    /// Fails if `req` is not a valid request, nothing is connected yet.
+    pub fn new<Req: IntoClientRequest>(
+        req: Req,
+        config: Option<WebSocketConfig>,
+        options: ConnectOptions,
+    ) -> Result<Self> {
+        let request = req.into_client_request()?;
+
+        Ok(Self::from_fn(move || connect_with_options(request.clone(), config, 3, options.clone())))
+    }
+
+    /// Create a client connecting over TLS with the given connector, e.g. one built with
+    /// [`Connector::rustls_default`](crate::Connector::rustls_default).
+    ///
+    /// Fails if `req` is not a valid request, nothing is connected yet.
+    #[cfg(any(feature = "native-tls", feature = "__rustls-tls"))]
+    pub fn with_connector<Req: IntoClientRequest>(
+        req: Req,
+        config: Option<WebSocketConfig>,
+        options: ConnectOptions,
+        connector: crate::tls::Connector,
+    ) -> Result<Self> {
+        use crate::handshake::core::HandshakeError;
+
+        let request = req.into_client_request()?;
+
+        Ok(Self::from_fn(move || {
+            let stream = crate::client::connect_stream(request.uri(), &options, config.as_ref())?;
+            crate::tls::client_tls_with_config(
+                request.clone(),
+                stream,
+                config,
+                Some(connector.clone()),
+            )
+            .map_err(|e| match e {
+                HandshakeError::Failure(f) => f,
+                HandshakeError::Interrupted(_) => panic!("Bug: blocking handshake not blocked"),
+            })
+        }))
+    }
+}
+
+impl<S> ReconnectingClient<S> {
+    /// Create a client calling `connect` for every connection, e.g. to set up the stream in a
+    /// custom way.
+    pub fn from_fn<F>(connect: F) -> Self
+    where
+        F: FnMut() -> Result<(WebSocket<S>, Response)> + Send + 'static,
+    {
+        Self {
+            connect: Box::new(connect),
+            socket: None,
+            response: None,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_attempts: 10,
+            failures: 0,
+            closing: false,
+        }
+    }
+
+    /// Set the delay before connecting again after the first failure. The default value is
+    /// 500 milliseconds.
+    pub fn initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    /// Set the longest delay between two connection attempts. The default value is 30 seconds.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Set the number of consecutive failures after which the error is returned. The default
+    /// value is `10`.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// The current connection, if any.
+    pub fn get_ref(&self) -> Option<&WebSocket<S>> {
+        self.socket.as_ref()
+    }
+
+    /// The current connection, if any.
+    pub fn get_mut(&mut self) -> Option<&mut WebSocket<S>> {
+        self.socket.as_mut()
+    }
+
+    /// The handshake response of the last connection, if any.
+    pub fn response(&self) -> Option<&Response> {
+        self.response.as_ref()
+    }
+
+    /// The delay before the next connection attempt.
+    fn delay(&self) -> Duration {
+        let exponent = self.failures.saturating_sub(1).min(31);
+        let delay = self.initial_delay.saturating_mul(1 << exponent).min(self.max_delay);
+
+        delay.mul_f64(1.0 - rand::random::<f64>() / 2.0)
+    }
+
+    /// Count a failure, returning `err` if it is not a connection error or too many failed.
+    fn on_error(&mut self, err: Error) -> Result<()> {
+        if self.closing || !is_connection_error(&err) {
+            return Err(err);
+        }
+
+        self.socket = None;
+        self.failures += 1;
+        if self.failures >= self.max_attempts {
+            self.failures = 0;
+            return Err(err);
+        }
+
+        Ok(())
+    }
+}
+
+impl<S: Read + Write> ReconnectingClient<S> {
+    /// Returns the current connection, connecting first if there is none.
+    pub fn connect(&mut self) -> Result<&mut WebSocket<S>> {
+        if self.socket.is_none() {
+            if self.closing {
+                return Err(Error::AlreadyClosed);
+            }
+
+            loop {
+                if self.failures > 0 {
+                    thread::sleep(self.delay());
+                }
+
+                match (self.connect)() {
+                    Ok((socket, response)) => {
+                        self.socket = Some(socket);
+                        self.response = Some(response);
+                        break;
+                    }
+                    Err(e) => self.on_error(e)?,
+                }
+            }
+        }
+
+        Ok(self.socket.as_mut().expect("Bug: not connected"))
+    }
+
+    /// Read a message, connecting again if the connection is lost.
+    ///
+    /// See [`WebSocket::read`].
+    pub fn read(&mut self) -> Result<Message> {
+        loop {
+            match self.connect()?.read() {
+                Ok(msg) => {
+                    self.failures = 0;
+                    return Ok(msg);
+                }
+                Err(e) => self.on_error(e)?,
+            }
+        }
+    }
+
+    /// Send a message and flush, connecting again if the connection is lost.
+    ///
+    /// See [`WebSocket::send`].
+    pub fn send(&mut self, msg: Message) -> Result<()> {
+        loop {
+            match self.connect()?.send(msg.clone()) {
+                Ok(()) => {
+                    self.failures = 0;
+                    return Ok(());
+                }
+                Err(e) => self.on_error(e)?,
+            }
+        }
+    }
+
+    /// Start the close handshake of the current connection, and stop reconnecting.
+    ///
+    /// Keep calling [`read`](Self::read) until it returns [`Error::ConnectionClosed`] to complete
+    /// the handshake. See [`WebSocket::close`].
+    pub fn close(&mut self, code: Option<CloseFrame>) -> Result<()> {
+        self.closing = true;
+
+        match self.socket.as_mut() {
+            Some(socket) => socket.close(code),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S: Debug> Debug for ReconnectingClient<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReconnectingClient")
+            .field("socket", &self.socket)
+            .field("initial_delay", &self.initial_delay)
+            .field("max_delay", &self.max_delay)
+            .field("max_attempts", &self.max_attempts)
+            .field("failures", &self.failures)
+            .field("closing", &self.closing)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Tell if `err` means the connection was lost or could not be established.
+fn is_connection_error(err: &Error) -> bool {
+    match err {
+        Error::ConnectionClosed => true,
+        Error::Io(e) => !matches!(
+            e.kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+        ),
+        Error::Protocol(
+            ProtocolError::ResetWithoutClosing | ProtocolError::IncompleteHandshake,
+        ) => true,
+        Error::Url(UrlError::UnableToConnect(_)) => true,
+        Error::Http(res) => res.status().is_server_error(),
+        _ => false,
+    }
+}
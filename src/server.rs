@@ -66,3 +66,67 @@ pub fn accept_header<S: Read + Write, C: Callback>(
 ) -> Result<WebSocket<S>, HandshakeError<ServerHandshake<S, C>>> {
     accept_header_with_config(stream, callback, None)
 }
+
+/// Builder for accepting WebSocket connections, as an alternative to picking one of the
+/// `accept*` functions.
+///
+/// Without a callback or a configuration, [`NoCallback`] and the default configuration are used.
+///
+/// # Example
+/// ```
+/// # use std::io::Cursor;
+/// # use blitz_ws::{handshake::server::{Request, Response}, protocol::config::WebSocketConfig, Acceptor};
+/// # let request = "GET /chat HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+/// #     Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+/// #     Sec-WebSocket-Version: 13\r\n\r\n";
+/// # let stream = Cursor::new(request.as_bytes().to_vec());
+/// let ws = Acceptor::new()
+///     .config(WebSocketConfig::default().max_message_size(Some(1 << 20)))
+///     .callback(|req: &Request, res: Response| {
+///         assert_eq!(req.uri().path(), "/chat");
+///         Ok(res)
+///     })
+///     .accept(stream)
+///     .unwrap();
+///
+/// assert_eq!(ws.get_config().max_message_size, Some(1 << 20));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Acceptor<C = NoCallback> {
+    callback: C,
+    config: Option<WebSocketConfig>,
+}
+
+impl Acceptor {
+    /// Start building an acceptor with the default callback and configuration.
+    pub fn new() -> Self {
+        Acceptor { callback: NoCallback, config: None }
+    }
+}
+
+impl Default for Acceptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: Callback> Acceptor<C> {
+    /// Use the given configuration for the accepted connections.
+    pub fn config(mut self, config: WebSocketConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Call `callback` with the request of the client, see [`accept_header`].
+    pub fn callback<C2: Callback>(self, callback: C2) -> Acceptor<C2> {
+        Acceptor { callback, config: self.config }
+    }
+
+    /// Accept the given stream as a WebSocket.
+    pub fn accept<S: Read + Write>(
+        self,
+        stream: S,
+    ) -> Result<WebSocket<S>, HandshakeError<ServerHandshake<S, C>>> {
+        accept_header_with_config(stream, self.callback, self.config)
+    }
+}
@@ -9,7 +9,8 @@ use std::ops::Deref;
 use std::{
     fmt::Debug,
     io::{Read, Result as IoResult, Write},
-    net::TcpStream,
+    net::{SocketAddr, TcpStream},
+    time::Duration,
 };
 
 #[cfg(feature = "native-tls")]
@@ -57,6 +58,98 @@ where
     }
 }
 
+/// Trait to get and set the read timeout of a blocking stream.
+pub trait ReadTimeout {
+    /// Returns the read timeout of the stream.
+    fn read_timeout(&self) -> IoResult<Option<Duration>>;
+
+    /// Set the read timeout of the stream. `None` means reads block indefinitely.
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> IoResult<()>;
+}
+
+impl ReadTimeout for TcpStream {
+    fn read_timeout(&self) -> IoResult<Option<Duration>> {
+        TcpStream::read_timeout(self)
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> IoResult<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(feature = "native-tls")]
+impl<S: Read + Write + ReadTimeout> ReadTimeout for TlsStream<S> {
+    fn read_timeout(&self) -> IoResult<Option<Duration>> {
+        self.get_ref().read_timeout()
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> IoResult<()> {
+        self.get_mut().set_read_timeout(timeout)
+    }
+}
+
+#[cfg(feature = "__rustls-tls")]
+impl<S, SD, T> ReadTimeout for StreamOwned<S, T>
+where
+    S: Deref<Target = rustls::ConnectionCommon<SD>>,
+    SD: rustls::SideData,
+    T: Read + Write + ReadTimeout,
+{
+    fn read_timeout(&self) -> IoResult<Option<Duration>> {
+        self.sock.read_timeout()
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> IoResult<()> {
+        self.sock.set_read_timeout(timeout)
+    }
+}
+
+/// Trait to get the socket addresses of a stream, reaching through TLS to the TCP socket.
+pub trait SocketAddrs {
+    /// Returns the address of the remote peer of the stream.
+    fn peer_addr(&self) -> IoResult<SocketAddr>;
+
+    /// Returns the local address of the stream.
+    fn local_addr(&self) -> IoResult<SocketAddr>;
+}
+
+impl SocketAddrs for TcpStream {
+    fn peer_addr(&self) -> IoResult<SocketAddr> {
+        TcpStream::peer_addr(self)
+    }
+
+    fn local_addr(&self) -> IoResult<SocketAddr> {
+        TcpStream::local_addr(self)
+    }
+}
+
+#[cfg(feature = "native-tls")]
+impl<S: Read + Write + SocketAddrs> SocketAddrs for TlsStream<S> {
+    fn peer_addr(&self) -> IoResult<SocketAddr> {
+        self.get_ref().peer_addr()
+    }
+
+    fn local_addr(&self) -> IoResult<SocketAddr> {
+        self.get_ref().local_addr()
+    }
+}
+
+#[cfg(feature = "__rustls-tls")]
+impl<S, SD, T> SocketAddrs for StreamOwned<S, T>
+where
+    S: Deref<Target = rustls::ConnectionCommon<SD>>,
+    SD: rustls::SideData,
+    T: Read + Write + SocketAddrs,
+{
+    fn peer_addr(&self) -> IoResult<SocketAddr> {
+        self.sock.peer_addr()
+    }
+
+    fn local_addr(&self) -> IoResult<SocketAddr> {
+        self.sock.local_addr()
+    }
+}
+
 /// A simplified stream abstraction that might be protected with TLS.
 #[non_exhaustive]
 #[allow(clippy::large_enum_variant)]
@@ -147,3 +240,47 @@ impl<S: Read + Write + NoDelay> NoDelay for SimplifiedStream<S> {
         }
     }
 }
+
+impl<S: Read + Write + ReadTimeout> ReadTimeout for SimplifiedStream<S> {
+    fn read_timeout(&self) -> IoResult<Option<Duration>> {
+        match self {
+            Self::Plain(ref s) => s.read_timeout(),
+            #[cfg(feature = "native-tls")]
+            Self::NativeTls(ref s) => s.read_timeout(),
+            #[cfg(feature = "__rustls-tls")]
+            Self::Rustls(ref s) => s.read_timeout(),
+        }
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> IoResult<()> {
+        match self {
+            Self::Plain(ref mut s) => s.set_read_timeout(timeout),
+            #[cfg(feature = "native-tls")]
+            Self::NativeTls(ref mut s) => s.set_read_timeout(timeout),
+            #[cfg(feature = "__rustls-tls")]
+            Self::Rustls(ref mut s) => s.set_read_timeout(timeout),
+        }
+    }
+}
+
+impl<S: Read + Write + SocketAddrs> SocketAddrs for SimplifiedStream<S> {
+    fn peer_addr(&self) -> IoResult<SocketAddr> {
+        match self {
+            Self::Plain(ref s) => s.peer_addr(),
+            #[cfg(feature = "native-tls")]
+            Self::NativeTls(ref s) => s.peer_addr(),
+            #[cfg(feature = "__rustls-tls")]
+            Self::Rustls(ref s) => s.peer_addr(),
+        }
+    }
+
+    fn local_addr(&self) -> IoResult<SocketAddr> {
+        match self {
+            Self::Plain(ref s) => s.local_addr(),
+            #[cfg(feature = "native-tls")]
+            Self::NativeTls(ref s) => s.local_addr(),
+            #[cfg(feature = "__rustls-tls")]
+            Self::Rustls(ref s) => s.local_addr(),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! Stream wrappers for deterministically exercising partial I/O paths.
+//!
+//! Only available with the `test-util` feature. The wrappers here are meant for testing code
+//! built on top of blitz (and blitz itself): they let you script exactly how many bytes each
+//! `read`/`write` call transfers, when `WouldBlock` is returned and when the stream hits EOF.
+
+use std::{
+    collections::VecDeque,
+    io::{Error as IoError, ErrorKind as IoErrorKind, Read, Result as IoResult, Write},
+    thread::sleep,
+    time::Duration,
+};
+
+/// A single scripted behavior of a [`ThrottledStream`] operation.
+///
+/// Each `read` or `write` call on the stream consumes one step from the corresponding script.
+/// Once a script is exhausted, calls are passed through to the inner stream unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Transfer at most the given number of bytes in this call.
+    Limit(usize),
+    /// Sleep for the given duration, then transfer normally.
+    Delay(Duration),
+    /// Return `WouldBlock` without transferring anything.
+    WouldBlock,
+    /// Silently drop up to the given number of bytes.
+    ///
+    /// When reading, the bytes are read from the inner stream and discarded. When writing,
+    /// the bytes are reported as written without reaching the inner stream.
+    Lose(usize),
+    /// Report end of stream: reads return `Ok(0)`, writes return `Ok(0)`.
+    Eof,
+}
+
+/// A `Read + Write` wrapper that injects artificial limits, latency, loss and `WouldBlock`.
+///
+/// Build one with [`ThrottledStream::builder`].
+///
+/// # Example
+/// ```
+/// # use std::io::{Cursor, Read};
+/// # use blitz_ws::test_util::ThrottledStream;
+/// let mut stream = ThrottledStream::builder()
+///     .read_limit(2)
+///     .read_would_block()
+///     .build(Cursor::new(vec![1, 2, 3, 4]));
+///
+/// let mut buf = [0; 4];
+/// assert_eq!(stream.read(&mut buf).unwrap(), 2);
+/// assert!(stream.read(&mut buf).is_err());
+/// assert_eq!(stream.read(&mut buf).unwrap(), 2);
+/// ```
+#[derive(Debug)]
+pub struct ThrottledStream<S> {
+    inner: S,
+    reads: VecDeque<Step>,
+    writes: VecDeque<Step>,
+    max_read: Option<usize>,
+    max_write: Option<usize>,
+    read_budget: Option<usize>,
+    write_budget: Option<usize>,
+}
+
+impl ThrottledStream<()> {
+    /// Start scripting a new throttled stream.
+    pub fn builder() -> ThrottledStreamBuilder {
+        ThrottledStreamBuilder::default()
+    }
+}
+
+impl<S> ThrottledStream<S> {
+    /// Returns a shared reference to the inner stream.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Returns the inner stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Check if all scripted read and write steps were consumed.
+    pub fn is_script_done(&self) -> bool {
+        self.reads.is_empty() && self.writes.is_empty()
+    }
+}
+
+/// Builder for [`ThrottledStream`].
+#[derive(Debug, Clone, Default)]
+pub struct ThrottledStreamBuilder {
+    reads: VecDeque<Step>,
+    writes: VecDeque<Step>,
+    max_read: Option<usize>,
+    max_write: Option<usize>,
+    read_budget: Option<usize>,
+    write_budget: Option<usize>,
+}
+
+impl ThrottledStreamBuilder {
+    /// Append a raw step to the read script.
+    pub fn read_step(mut self, step: Step) -> Self {
+        self.reads.push_back(step);
+        self
+    }
+
+    /// Append a raw step to the write script.
+    pub fn write_step(mut self, step: Step) -> Self {
+        self.writes.push_back(step);
+        self
+    }
+
+    /// The next scripted read transfers at most `size` bytes.
+    pub fn read_limit(self, size: usize) -> Self {
+        self.read_step(Step::Limit(size))
+    }
+
+    /// The next scripted write transfers at most `size` bytes.
+    pub fn write_limit(self, size: usize) -> Self {
+        self.write_step(Step::Limit(size))
+    }
+
+    /// The next scripted read returns `WouldBlock`.
+    pub fn read_would_block(self) -> Self {
+        self.read_step(Step::WouldBlock)
+    }
+
+    /// The next scripted write returns `WouldBlock`.
+    pub fn write_would_block(self) -> Self {
+        self.write_step(Step::WouldBlock)
+    }
+
+    /// The next scripted read reports EOF.
+    pub fn read_eof(self) -> Self {
+        self.read_step(Step::Eof)
+    }
+
+    /// The next scripted write reports that nothing could be written.
+    pub fn write_eof(self) -> Self {
+        self.write_step(Step::Eof)
+    }
+
+    /// Cap every unscripted read to at most `size` bytes.
+    pub fn max_read(mut self, size: usize) -> Self {
+        self.max_read = Some(size);
+        self
+    }
+
+    /// Cap every unscripted write to at most `size` bytes.
+    pub fn max_write(mut self, size: usize) -> Self {
+        self.max_write = Some(size);
+        self
+    }
+
+    /// Once the script is exhausted, return `WouldBlock` for every read after `size` bytes
+    /// in total were read.
+    pub fn read_would_block_after(mut self, size: usize) -> Self {
+        self.read_budget = Some(size);
+        self
+    }
+
+    /// Once the script is exhausted, return `WouldBlock` for every write after `size` bytes
+    /// in total were written.
+    pub fn write_would_block_after(mut self, size: usize) -> Self {
+        self.write_budget = Some(size);
+        self
+    }
+
+    /// Wrap `inner` into a stream following the script.
+    pub fn build<S>(self, inner: S) -> ThrottledStream<S> {
+        ThrottledStream {
+            inner,
+            reads: self.reads,
+            writes: self.writes,
+            max_read: self.max_read,
+            max_write: self.max_write,
+            read_budget: self.read_budget,
+            write_budget: self.write_budget,
+        }
+    }
+}
+
+/// Length to transfer for an unscripted operation, or `None` if it should block.
+fn unscripted_len(len: usize, max: Option<usize>, budget: Option<usize>) -> Option<usize> {
+    let len = len.min(max.unwrap_or(usize::MAX));
+
+    match budget {
+        Some(0) if len > 0 => None,
+        Some(budget) => Some(len.min(budget)),
+        None => Some(len),
+    }
+}
+
+fn would_block() -> IoError {
+    IoError::new(IoErrorKind::WouldBlock, "ThrottledStream: scripted WouldBlock")
+}
+
+impl<S: Read> Read for ThrottledStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let len = match self.reads.pop_front() {
+            Some(Step::Limit(size)) => buf.len().min(size),
+            Some(Step::Delay(delay)) => {
+                sleep(delay);
+                buf.len()
+            }
+            Some(Step::WouldBlock) => return Err(would_block()),
+            Some(Step::Lose(size)) => {
+                let mut lost = vec![0; size];
+                self.inner.read(&mut lost)?;
+                buf.len()
+            }
+            Some(Step::Eof) => return Ok(0),
+            None => {
+                let len = unscripted_len(buf.len(), self.max_read, self.read_budget)
+                    .ok_or_else(would_block)?;
+                let size = self.inner.read(&mut buf[..len])?;
+
+                if let Some(budget) = self.read_budget.as_mut() {
+                    *budget -= size;
+                }
+
+                return Ok(size);
+            }
+        };
+
+        self.inner.read(&mut buf[..len])
+    }
+}
+
+impl<S: Write> Write for ThrottledStream<S> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let len = match self.writes.pop_front() {
+            Some(Step::Limit(size)) => buf.len().min(size),
+            Some(Step::Delay(delay)) => {
+                sleep(delay);
+                buf.len()
+            }
+            Some(Step::WouldBlock) => return Err(would_block()),
+            Some(Step::Lose(size)) => return Ok(size.min(buf.len())),
+            Some(Step::Eof) => return Ok(0),
+            None => {
+                let len = unscripted_len(buf.len(), self.max_write, self.write_budget)
+                    .ok_or_else(would_block)?;
+                let size = self.inner.write(&buf[..len])?;
+
+                if let Some(budget) = self.write_budget.as_mut() {
+                    *budget -= size;
+                }
+
+                return Ok(size);
+            }
+        };
+
+        self.inner.write(&buf[..len])
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
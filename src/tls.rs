@@ -2,17 +2,16 @@
 
 use std::io::{Read, Write};
 
-#[cfg(any(feature = "native-tls", feature = "__rustls-tls"))]
-use crate::error::{Error, UrlError};
 use crate::{
-    client::{client_with_config, uri_mode, IntoClientRequest},
-    error::Result,
+    client::{client_with_config, uri_addr, uri_mode, ConnectOptions, IntoClientRequest},
+    error::{Error, Result, UrlError},
     handshake::{
         client::{ClientHandshake, Response},
         core::HandshakeError,
     },
     protocol::{config::WebSocketConfig, websocket::WebSocket},
-    stream::SimplifiedStream,
+    proxy,
+    stream::{Mode, SimplifiedStream},
 };
 
 /// A connector that can be used when establishing connections, allowing to control whether
@@ -20,8 +19,11 @@ use crate::{
 /// `Plain` variant.
 #[non_exhaustive]
 #[allow(missing_debug_implementations)]
+#[derive(Clone)]
 pub enum Connector {
     /// Plain (non-TLS) connector.
+    ///
+    /// Using it for a `wss://` URL fails with [`UrlError::PlainConnectorForTlsUrl`].
     Plain,
 
     /// `native-tls` TLS connector.
@@ -33,6 +35,35 @@ pub enum Connector {
     Rustls(std::sync::Arc<rustls::ClientConfig>),
 }
 
+impl Connector {
+    /// A `rustls` connector trusting the root certificates of the enabled
+    /// `rustls-tls-native-roots` and `rustls-tls-webpki-roots` features.
+    ///
+    /// Connecting without a connector builds such a configuration every time, and loading the
+    /// root certificates is expensive. Build the connector once instead and clone it for every
+    /// connection, the clones share the same configuration.
+    ///
+    /// Like any `rustls` configuration, this requires a process-level `CryptoProvider`, see
+    /// `rustls::crypto::CryptoProvider::install_default`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::net::TcpStream;
+    /// # use blitz_ws::{client_tls_with_config, Connector};
+    /// let connector = Connector::rustls_default().unwrap();
+    ///
+    /// for _ in 0..2 {
+    ///     let stream = TcpStream::connect("example.com:443").unwrap();
+    ///     let connector = Some(connector.clone());
+    ///     let (ws, _) = client_tls_with_config("wss://example.com/", stream, None, connector).unwrap();
+    /// }
+    /// ```
+    #[cfg(feature = "__rustls-tls")]
+    pub fn rustls_default() -> Result<Self> {
+        encryption::rustls::default_config().map(Connector::Rustls)
+    }
+}
+
 mod encryption {
     #[cfg(feature = "native-tls")]
     pub mod native_tls {
@@ -100,39 +131,7 @@ mod encryption {
                 Mode::Tls => {
                     let config = match tls_connector {
                         Some(config) => config,
-                        None => {
-                            #[allow(unused_mut)]
-                            let mut root_store = RootCertStore::empty();
-
-                            #[cfg(feature = "rustls-tls-native-roots")]
-                            {
-                                let rustls_native_certs::CertificateResult {
-                                    certs, errors, ..
-                                } = rustls_native_certs::load_native_certs();
-
-                                // #[cfg(not(feature = "rustls-tls-webpki-roots"))]
-                                if certs.is_empty() {
-                                    return Err(std::io::Error::new(
-                                        std::io::ErrorKind::NotFound,
-                                        format!("No native root CA certificates found (errors: {errors:?})")
-                                    ).into());
-                                }
-
-                                // let total = certs.len();
-                                // let (num_added, num_ignored) = root_store.add_parsable_certificates(certs);
-                            }
-
-                            #[cfg(feature = "rustls-tls-webpki-roots")]
-                            {
-                                root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
-                            }
-
-                            Arc::new(
-                                ClientConfig::builder()
-                                    .with_root_certificates(root_store)
-                                    .with_no_client_auth(),
-                            )
-                        }
+                        None => default_config()?,
                     };
 
                     let domain = ServerName::try_from(domain)
@@ -146,6 +145,37 @@ mod encryption {
                 }
             }
         }
+
+        /// Build a configuration trusting the root certificates of the enabled features.
+        pub fn default_config() -> Result<Arc<ClientConfig>> {
+            #[allow(unused_mut)]
+            let mut root_store = RootCertStore::empty();
+
+            #[cfg(feature = "rustls-tls-native-roots")]
+            {
+                let rustls_native_certs::CertificateResult { certs, errors, .. } =
+                    rustls_native_certs::load_native_certs();
+
+                if certs.is_empty() {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("No native root CA certificates found (errors: {errors:?})"),
+                    )
+                    .into());
+                }
+
+                root_store.add_parsable_certificates(certs);
+            }
+
+            #[cfg(feature = "rustls-tls-webpki-roots")]
+            {
+                root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            }
+
+            Ok(Arc::new(
+                ClientConfig::builder().with_root_certificates(root_store).with_no_client_auth(),
+            ))
+        }
     }
 
     pub mod plain {
@@ -187,25 +217,58 @@ where
 /// be created.
 ///
 /// Please refer to [`client_tls()`] for more details.
+///
+/// Passing [`Connector::Plain`] for a `wss://` URL fails rather than connecting without TLS:
+///
+/// ```
+/// # use std::io::Cursor;
+/// # use blitz_ws::{client_tls_with_config, error::{Error, UrlError}, handshake::HandshakeError, Connector};
+/// let stream = Cursor::new(Vec::new());
+/// match client_tls_with_config("wss://localhost/", stream, None, Some(Connector::Plain)) {
+///     Err(HandshakeError::Failure(Error::Url(UrlError::PlainConnectorForTlsUrl))) => {}
+///     _ => panic!("expected PlainConnectorForTlsUrl"),
+/// }
+/// ```
 pub fn client_tls_with_config<R, S>(
     request: R,
     stream: S,
     config: Option<WebSocketConfig>,
     connector: Option<Connector>,
 ) -> Result<(WebSocket<SimplifiedStream<S>>, Response), TlsErrorHandshake<S>>
+where
+    R: IntoClientRequest,
+    S: Read + Write,
+{
+    client_tls_with_options(request, stream, config, connector, ConnectOptions::default())
+}
+
+/// The same as [`client_tls_with_config()`] but the stream is set up according to the given
+/// [`ConnectOptions`] before the TLS and WebSocket handshakes.
+///
+/// If [`ConnectOptions::proxy`] is set, `stream` must be connected to the proxy. A `CONNECT`
+/// tunnel to the request host is established over it first.
+pub fn client_tls_with_options<R, S>(
+    request: R,
+    mut stream: S,
+    config: Option<WebSocketConfig>,
+    connector: Option<Connector>,
+    options: ConnectOptions,
+) -> Result<(WebSocket<SimplifiedStream<S>>, Response), TlsErrorHandshake<S>>
 where
     R: IntoClientRequest,
     S: Read + Write,
 {
     let request = request.into_client_request()?;
 
+    let mode = uri_mode(request.uri())?;
+    let (host, port) = uri_addr(request.uri())?;
+
     #[cfg(any(feature = "native-tls", feature = "__rustls-tls"))]
-    let domain = match request.uri().host() {
-        Some(d) => Ok(d.to_string()),
-        None => Err(Error::Url(UrlError::MissingHost)),
-    }?;
+    let domain = host.to_string();
 
-    let mode = uri_mode(request.uri())?;
+    if let Some(proxy) = &options.proxy {
+        proxy::tunnel(&mut stream, proxy, host, port, config.as_ref())?;
+    }
 
     let stream = match connector {
         Some(conn) => match conn {
@@ -219,7 +282,10 @@ where
                 self::encryption::rustls::wrap_stream(stream, &domain, mode, Some(conn))
             }
 
-            Connector::Plain => self::encryption::plain::wrap_stream(stream, mode),
+            Connector::Plain => match mode {
+                Mode::Plain => self::encryption::plain::wrap_stream(stream, mode),
+                Mode::Tls => Err(Error::Url(UrlError::PlainConnectorForTlsUrl)),
+            },
         },
         None => {
             #[cfg(feature = "native-tls")]
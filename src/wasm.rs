@@ -0,0 +1,235 @@
+//! WebSocket client for the browser, backed by the JavaScript `WebSocket` API.
+//!
+//! The browser performs the handshake and frames the messages itself, so neither the handshake
+//! nor the frame codec of this crate are involved. Only [`Message`] and [`CloseFrame`] are shared
+//! with the rest of the crate.
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    fmt::{Debug, Formatter},
+    io,
+    mem::{replace, take},
+    rc::Rc,
+};
+
+use js_sys::{ArrayBuffer, Uint8Array};
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{BinaryType, CloseEvent, Event, MessageEvent, WebSocket as JsWebSocket};
+
+use crate::{
+    error::{Error, ProtocolError, Result},
+    protocol::{
+        frame::{codec::CloseCode, CloseFrame},
+        message::Message,
+    },
+};
+
+/// A WebSocket client connection in the browser.
+///
+/// The browser receives messages in between turns of its event loop. They are queued until
+/// read with [`read`](Self::read), which never blocks. Messages sent before the connection is
+/// open are queued as well and sent as soon as it is, unless it is closed before that.
+///
+/// Browsers neither expose ping/pong frames nor raw frames, sending those fails.
+///
+/// # Example
+/// ```no_run
+/// # use blitz_ws::{protocol::message::Message, wasm::BrowserWebSocket};
+/// let mut socket = BrowserWebSocket::connect("ws://localhost:8080/socket").unwrap();
+/// socket.send(Message::Text("Hello!".into())).unwrap();
+///
+/// // Later, e.g. from a timer or an animation frame callback.
+/// while let Some(msg) = socket.read().unwrap() {
+///     println!("Received: {msg}");
+/// }
+/// ```
+pub struct BrowserWebSocket {
+    socket: JsWebSocket,
+    shared: Rc<RefCell<Shared>>,
+    _on_open: Closure<dyn FnMut(Event)>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    _on_close: Closure<dyn FnMut(CloseEvent)>,
+}
+
+/// State shared with the event handlers.
+#[derive(Debug, Default)]
+struct Shared {
+    /// Messages received and not read yet.
+    incoming: VecDeque<Message>,
+    /// Messages sent before the connection was open.
+    outgoing: Vec<Message>,
+    /// The connection is closed, no message follows the ones in `incoming`.
+    closed: bool,
+    /// `ConnectionClosed` was returned from `read` already.
+    terminated: bool,
+}
+
+impl BrowserWebSocket {
+    /// Start connecting to the given `ws://` or `wss://` URL.
+    pub fn connect(url: &str) -> Result<Self> {
+        Self::from_socket(JsWebSocket::new(url).map_err(js_error)?)
+    }
+
+    /// Start connecting to the given URL, offering the given subprotocols.
+    pub fn connect_with_protocols(url: &str, protocols: &[&str]) -> Result<Self> {
+        let protocols: js_sys::Array = protocols.iter().map(|p| JsValue::from_str(p)).collect();
+        Self::from_socket(JsWebSocket::new_with_str_sequence(url, &protocols).map_err(js_error)?)
+    }
+
+    fn from_socket(socket: JsWebSocket) -> Result<Self> {
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        let shared = Rc::new(RefCell::new(Shared::default()));
+
+        let on_open = {
+            let (socket, shared) = (socket.clone(), shared.clone());
+            Closure::<dyn FnMut(Event)>::new(move |_| {
+                for msg in take(&mut shared.borrow_mut().outgoing) {
+                    // There is nobody to report the error to, the close event follows anyway.
+                    let _ = send_message(&socket, msg);
+                }
+            })
+        };
+
+        let on_message = {
+            let shared = shared.clone();
+            Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+                let data = event.data();
+                let msg = if let Some(text) = data.as_string() {
+                    Message::Text(text.into())
+                } else if let Ok(buffer) = data.dyn_into::<ArrayBuffer>() {
+                    Message::Binary(Uint8Array::new(&buffer).to_vec().into())
+                } else {
+                    return;
+                };
+
+                shared.borrow_mut().incoming.push_back(msg);
+            })
+        };
+
+        let on_close = {
+            let shared = shared.clone();
+            Closure::<dyn FnMut(CloseEvent)>::new(move |event: CloseEvent| {
+                let frame = match CloseCode::from(event.code()) {
+                    CloseCode::Status => None,
+                    code => Some(CloseFrame { code, reason: event.reason().into() }),
+                };
+
+                let mut shared = shared.borrow_mut();
+                shared.incoming.push_back(Message::Close(frame));
+                shared.closed = true;
+            })
+        };
+
+        socket.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+        socket.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        socket.set_onclose(Some(on_close.as_ref().unchecked_ref()));
+
+        Ok(Self { socket, shared, _on_open: on_open, _on_message: on_message, _on_close: on_close })
+    }
+
+    /// Check if the connection is open, i.e. messages are sent right away.
+    pub fn is_open(&self) -> bool {
+        self.socket.ready_state() == JsWebSocket::OPEN
+    }
+
+    /// The subprotocol selected by the server, empty if none was.
+    pub fn protocol(&self) -> String {
+        self.socket.protocol()
+    }
+
+    /// Read the next received message, or `None` if there is none yet.
+    ///
+    /// Once the connection is closed and every message before the close was read,
+    /// [`Error::ConnectionClosed`] is returned, then [`Error::AlreadyClosed`].
+    pub fn read(&mut self) -> Result<Option<Message>> {
+        let mut shared = self.shared.borrow_mut();
+
+        if let Some(msg) = shared.incoming.pop_front() {
+            Ok(Some(msg))
+        } else if !shared.closed {
+            Ok(None)
+        } else if replace(&mut shared.terminated, true) {
+            Err(Error::AlreadyClosed)
+        } else {
+            Err(Error::ConnectionClosed)
+        }
+    }
+
+    /// Send a message, or queue it if the connection is not open yet.
+    ///
+    /// Sending [`Message::Close`] closes the connection. Browsers only accept
+    /// [`CloseCode::Normal`] and the codes reserved for libraries and applications
+    /// (3000 to 4999) there. Closing before the connection is open drops the messages
+    /// queued so far, they are never sent.
+    pub fn send(&mut self, msg: Message) -> Result<()> {
+        match msg {
+            Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {
+                return Err(Error::Io(io::ErrorKind::Unsupported.into()));
+            }
+            Message::Close(_) => {
+                self.shared.borrow_mut().outgoing.clear();
+                return send_message(&self.socket, msg);
+            }
+            Message::Text(_) | Message::Binary(_) => {}
+        }
+
+        match self.socket.ready_state() {
+            JsWebSocket::CONNECTING => {
+                self.shared.borrow_mut().outgoing.push(msg);
+                Ok(())
+            }
+            JsWebSocket::OPEN => send_message(&self.socket, msg),
+            _ => Err(Error::Protocol(ProtocolError::SendAfterClose)),
+        }
+    }
+
+    /// Close the connection, the same as sending [`Message::Close`].
+    ///
+    /// Messages queued while connecting are dropped.
+    pub fn close(&mut self, frame: Option<CloseFrame>) -> Result<()> {
+        self.send(Message::Close(frame))
+    }
+}
+
+impl Drop for BrowserWebSocket {
+    fn drop(&mut self) {
+        // The handlers are dropped along with `self`, the browser must not call them anymore.
+        self.socket.set_onopen(None);
+        self.socket.set_onmessage(None);
+        self.socket.set_onclose(None);
+
+        if matches!(self.socket.ready_state(), JsWebSocket::CONNECTING | JsWebSocket::OPEN) {
+            let _ = self.socket.close();
+        }
+    }
+}
+
+impl Debug for BrowserWebSocket {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BrowserWebSocket")
+            .field("url", &self.socket.url())
+            .field("shared", &self.shared)
+            .finish_non_exhaustive()
+    }
+}
+
+fn send_message(socket: &JsWebSocket, msg: Message) -> Result<()> {
+    match msg {
+        Message::Text(text) => socket.send_with_str(&text),
+        Message::Binary(data) => socket.send_with_u8_array(&data),
+        Message::Close(None) => socket.close(),
+        Message::Close(Some(CloseFrame { code, reason })) => {
+            socket.close_with_code_and_reason(code.into(), &reason)
+        }
+        Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => {
+            return Err(Error::Io(io::ErrorKind::Unsupported.into()));
+        }
+    }
+    .map_err(js_error)
+}
+
+/// Report an exception thrown by the browser as an I/O error.
+fn js_error(error: JsValue) -> Error {
+    Error::Io(io::Error::new(io::ErrorKind::Other, format!("{error:?}")))
+}
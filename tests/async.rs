@@ -0,0 +1,111 @@
+//! `AsyncWebSocket` over tokio streams.
+
+use blitz_ws::{
+    asynchronous::{accept_async, client_async, AsyncWebSocket},
+    error::Error,
+    protocol::{config::WebSocketConfig, message::Message, websocket::OperationMode},
+};
+use futures_util::{SinkExt, StreamExt};
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, TcpStream},
+};
+
+#[tokio::test]
+async fn echo_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut socket = accept_async(stream).await.unwrap();
+
+        loop {
+            match socket.read().await {
+                Ok(msg) if msg.is_data() => socket.send(msg).await.unwrap(),
+                Ok(_) => {}
+                Err(Error::ConnectionClosed) => break,
+                Err(e) => panic!("{e}"),
+            }
+        }
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (mut socket, _) = client_async(format!("ws://{addr}/"), stream).await.unwrap();
+
+    let messages = [
+        Message::new_text("hello"),
+        Message::Binary(vec![0xAB; 100_000].into()),
+        Message::new_text(""),
+    ];
+    for msg in &messages {
+        socket.send(msg.clone()).await.unwrap();
+        assert_eq!(&socket.read().await.unwrap(), msg);
+    }
+
+    socket.close(None).await.unwrap();
+    assert!(matches!(socket.read().await.unwrap(), Message::Close(None)));
+    assert!(matches!(socket.read().await, Err(Error::ConnectionClosed)));
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn split_halves_read_and_write_concurrently() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let socket = accept_async(stream).await.unwrap();
+        let (mut sink, mut stream) = socket.split();
+
+        while let Some(msg) = stream.next().await {
+            let msg = msg.unwrap();
+            if msg.is_data() {
+                sink.send(msg).await.unwrap();
+            }
+        }
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let (socket, _) = client_async(format!("ws://{addr}/"), stream).await.unwrap();
+    let (mut sink, mut stream) = socket.split();
+
+    let write = async {
+        for i in 0..100 {
+            sink.send(Message::new_text(i.to_string())).await.unwrap();
+        }
+        sink.close().await.unwrap();
+    };
+    let read = async {
+        let mut received = Vec::new();
+        while let Some(msg) = stream.next().await {
+            received.push(msg.unwrap());
+        }
+        received
+    };
+    let ((), received) = tokio::join!(write, read);
+
+    let mut expected: Vec<_> = (0..100).map(|i| Message::new_text(i.to_string())).collect();
+    expected.push(Message::Close(None));
+    assert_eq!(received, expected);
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn send_rejects_a_message_larger_than_the_write_buffer() {
+    let (stream, mut peer) = tokio::io::duplex(1 << 16);
+    let config = WebSocketConfig::default().write_buffer_size(10).max_write_buffer_size(100);
+    let mut socket = AsyncWebSocket::new(stream, OperationMode::Server, Some(config));
+
+    let err = socket.send(Message::Binary(vec![0; 1000].into())).await.unwrap_err();
+    assert!(matches!(err, Error::WriteBufferFull));
+
+    socket.send(Message::Binary(vec![1; 50].into())).await.unwrap();
+    let mut frame = [0; 52];
+    peer.read_exact(&mut frame).await.unwrap();
+    assert_eq!(frame[..2], [0x82, 50]);
+    assert_eq!(frame[2..], [1; 50]);
+}
@@ -0,0 +1,220 @@
+//! Close codes and the close handshake.
+
+mod common;
+
+use std::{
+    io::ErrorKind,
+    net::TcpStream,
+    thread::spawn,
+    time::{Duration, Instant},
+};
+
+use blitz_ws::{
+    error::{Error, ProtocolError},
+    protocol::{
+        config::WebSocketConfig,
+        frame::{codec::CloseCode, CloseFrame},
+        message::Message,
+        websocket::{OperationMode, WebSocket},
+    },
+};
+
+use common::{client_frames, masked_frame, server, server_frames, tcp_pair};
+
+fn close_frame(code: CloseCode, reason: &str) -> CloseFrame {
+    CloseFrame { code, reason: reason.into() }
+}
+
+#[test]
+fn abnormal_close_code_is_not_sent() {
+    let mut socket = server(Vec::new(), None);
+
+    let err = socket.close(Some(close_frame(CloseCode::Abnormal, ""))).unwrap_err();
+    assert!(matches!(err, Error::Protocol(ProtocolError::InvalidCloseCode(CloseCode::Abnormal))));
+
+    let err = socket.write(Message::Close(Some(close_frame(CloseCode::Abnormal, "")))).unwrap_err();
+    assert!(matches!(err, Error::Protocol(ProtocolError::InvalidCloseCode(CloseCode::Abnormal))));
+
+    assert!(socket.can_write());
+    assert!(socket.get_ref().output.is_empty());
+}
+
+#[test]
+fn normal_close_code_is_sent() {
+    let mut socket = server(Vec::new(), None);
+
+    socket.close(Some(close_frame(CloseCode::Normal, "bye"))).unwrap();
+
+    assert!(!socket.can_write());
+    assert_eq!(server_frames(&socket.get_ref().output), [(0x8, b"\x03\xe8bye".to_vec())]);
+}
+
+#[test]
+fn invalid_utf8_close_reason_is_answered_with_1007() {
+    let mut socket = server(masked_frame(0x88, b"\x03\xe8\xff\xfe"), None);
+
+    let err = socket.read().unwrap_err();
+    assert!(matches!(err, Error::Protocol(ProtocolError::InvalidCloseFrame)));
+
+    // A single, final close frame with code 1007 (0x03ef).
+    let output = &socket.get_ref().output;
+    assert_eq!(output[0], 0x88);
+    assert_eq!(server_frames(output), [(0x8, b"\x03\xef".to_vec())]);
+}
+
+/// Feed a close frame with `code` to a server, returning the read error and the close code
+/// replied with.
+fn receive_close_code(code: u16) -> (Error, Vec<u8>) {
+    let mut socket = server(masked_frame(0x88, &code.to_be_bytes()), None);
+
+    let err = socket.read().unwrap_err();
+    let mut frames = server_frames(&socket.get_ref().output);
+    assert_eq!(frames.len(), 1);
+    let (opcode, payload) = frames.remove(0);
+    assert_eq!(opcode, 0x8);
+
+    (err, payload)
+}
+
+#[test]
+fn received_1006_is_a_protocol_error() {
+    let (err, reply) = receive_close_code(1006);
+    assert!(matches!(err, Error::Protocol(ProtocolError::InvalidCloseCode(CloseCode::Abnormal))));
+    assert_eq!(reply, 1002u16.to_be_bytes());
+}
+
+#[test]
+fn received_999_is_a_protocol_error() {
+    let (err, reply) = receive_close_code(999);
+    assert!(
+        matches!(err, Error::Protocol(ProtocolError::InvalidCloseCode(code)) if u16::from(code) == 999)
+    );
+    assert_eq!(reply, 1002u16.to_be_bytes());
+}
+
+#[test]
+fn stray_continuation_frame_fails_the_connection_with_1002() {
+    let mut input = client_frames(&[Message::new_text("x")]);
+    input.extend(masked_frame(0x80, b"y"));
+    let mut socket = server(input, None);
+
+    assert_eq!(socket.read().unwrap(), Message::new_text("x"));
+    assert!(matches!(
+        socket.read().unwrap_err(),
+        Error::Protocol(ProtocolError::UnexpectedContinue)
+    ));
+
+    assert!(!socket.can_write());
+    assert_eq!(server_frames(&socket.get_ref().output), [(0x8, 1002u16.to_be_bytes().to_vec())]);
+}
+
+/// Read until the connection is closed, returning the close frame received on the way.
+fn read_until_closed(socket: &mut WebSocket<TcpStream>) -> Option<CloseFrame> {
+    let mut close = None;
+    loop {
+        match socket.read() {
+            Ok(Message::Close(frame)) => close = Some(frame),
+            Ok(msg) => panic!("unexpected {msg:?}"),
+            Err(Error::ConnectionClosed) => break,
+            Err(e) => panic!("{e}"),
+        }
+    }
+    close.expect("no close frame received")
+}
+
+#[test]
+fn close_handshake_initiated_by_the_client() {
+    let (client, server) = tcp_pair();
+    let mut client = WebSocket::new(client, OperationMode::Client, None);
+    let mut server = WebSocket::new(server, OperationMode::Server, None);
+
+    let server = spawn(move || {
+        let close = read_until_closed(&mut server);
+        assert!(!server.can_write());
+        close
+    });
+
+    client.close(Some(close_frame(CloseCode::Normal, "client done"))).unwrap();
+    // The server closes the TCP connection after replying, which ends the client's reads.
+    assert_eq!(read_until_closed(&mut client), Some(close_frame(CloseCode::Normal, "client done")));
+    assert_eq!(server.join().unwrap(), Some(close_frame(CloseCode::Normal, "client done")));
+    assert!(!client.can_write());
+}
+
+#[test]
+fn close_handshake_initiated_by_the_server() {
+    let (client, server) = tcp_pair();
+    let mut client = WebSocket::new(client, OperationMode::Client, None);
+    let mut server = WebSocket::new(server, OperationMode::Server, None);
+
+    let client = spawn(move || read_until_closed(&mut client));
+
+    server.close(Some(close_frame(CloseCode::Away, "server done"))).unwrap();
+    assert_eq!(read_until_closed(&mut server), Some(close_frame(CloseCode::Away, "server done")));
+    // Closing the TCP connection is up to the server, which ends the client's reads.
+    drop(server);
+    assert_eq!(client.join().unwrap(), Some(close_frame(CloseCode::Away, "server done")));
+}
+
+#[test]
+fn close_gracefully_returns_once_the_peer_replies() {
+    let (client, server) = tcp_pair();
+    let mut client = WebSocket::new(client, OperationMode::Client, None);
+    let mut server = WebSocket::new(server, OperationMode::Server, None);
+
+    let server = spawn(move || read_until_closed(&mut server));
+
+    client
+        .close_gracefully(Some(close_frame(CloseCode::Normal, "")), Some(Duration::from_secs(5)))
+        .unwrap();
+    assert!(!client.can_write());
+    assert_eq!(server.join().unwrap(), Some(close_frame(CloseCode::Normal, "")));
+}
+
+#[test]
+fn close_gracefully_times_out_without_a_reply() {
+    let (client, _server) = tcp_pair();
+    let mut client = WebSocket::new(client, OperationMode::Client, None);
+
+    let start = Instant::now();
+    let err = client.close_gracefully(None, Some(Duration::from_millis(100))).unwrap_err();
+    assert!(matches!(err, Error::Io(e) if e.kind() == ErrorKind::TimedOut));
+    assert!(start.elapsed() < Duration::from_secs(2));
+}
+
+/// Read messages of 7 bytes on the wire each until a connection limit is exceeded, returning
+/// the number of messages read before that.
+fn read_until_limit_exceeded(config: WebSocketConfig) -> usize {
+    let messages = vec![Message::new_text("a"); 5];
+    let mut socket = server(client_frames(&messages), Some(config));
+
+    let mut read = 0;
+    let err = loop {
+        match socket.read() {
+            Ok(msg) => {
+                assert_eq!(msg, Message::new_text("a"));
+                read += 1;
+            }
+            Err(err) => break err,
+        }
+    };
+    assert!(matches!(err, Error::Protocol(ProtocolError::ConnectionLimitExceeded)), "{err}");
+
+    socket.flush().unwrap();
+    assert_eq!(server_frames(&socket.get_ref().output), [(0x8, b"\x03\xf0".to_vec())]);
+    assert!(!socket.can_write());
+
+    read
+}
+
+#[test]
+fn max_messages_closes_with_1008() {
+    let config = WebSocketConfig::default().max_messages(Some(2));
+    assert_eq!(read_until_limit_exceeded(config), 2);
+}
+
+#[test]
+fn max_total_bytes_closes_with_1008() {
+    let config = WebSocketConfig::default().max_total_bytes(Some(20));
+    assert_eq!(read_until_limit_exceeded(config), 2);
+}
@@ -0,0 +1,95 @@
+//! Helpers shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::{
+    io::{Cursor, ErrorKind, Read, Result as IoResult, Write},
+    net::{TcpListener, TcpStream},
+};
+
+use blitz_ws::protocol::{
+    config::WebSocketConfig,
+    message::Message,
+    websocket::{OperationMode, WebSocket},
+};
+
+/// An in-memory stream reading from a fixed input and collecting everything written.
+#[derive(Debug, Default)]
+pub struct Stream {
+    pub input: Cursor<Vec<u8>>,
+    pub output: Vec<u8>,
+    /// Writes fail with `WouldBlock` while set.
+    pub write_blocked: bool,
+}
+
+impl Stream {
+    pub fn new(input: Vec<u8>) -> Self {
+        Self { input: Cursor::new(input), ..Self::default() }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        if self.write_blocked {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+/// The bytes a client socket writes when sending `messages`.
+pub fn client_frames(messages: &[Message]) -> Vec<u8> {
+    let mut client = WebSocket::new(Stream::default(), OperationMode::Client, None);
+    for msg in messages {
+        client.send(msg.clone()).unwrap();
+    }
+    client.into_inner().output
+}
+
+/// A server socket reading `input`.
+pub fn server(input: Vec<u8>, config: Option<WebSocketConfig>) -> WebSocket<Stream> {
+    WebSocket::new(Stream::new(input), OperationMode::Server, config)
+}
+
+/// A single frame as a client sends it, masked with an all-zero key. `payload` must be shorter
+/// than 126 bytes.
+pub fn masked_frame(first_byte: u8, payload: &[u8]) -> Vec<u8> {
+    assert!(payload.len() < 126);
+    let mut frame = vec![first_byte, 0x80 | payload.len() as u8, 0, 0, 0, 0];
+    frame.extend_from_slice(payload);
+    frame
+}
+
+/// Split the unmasked frames a server wrote into their opcodes and payloads.
+pub fn server_frames(mut output: &[u8]) -> Vec<(u8, Vec<u8>)> {
+    let mut frames = Vec::new();
+    while !output.is_empty() {
+        let opcode = output[0] & 0x0F;
+        let (len, header) = match output[1] & 0x7F {
+            126 => (u16::from_be_bytes([output[2], output[3]]) as usize, 4),
+            127 => (u64::from_be_bytes(output[2..10].try_into().unwrap()) as usize, 10),
+            len => (len as usize, 2),
+        };
+        frames.push((opcode, output[header..header + len].to_vec()));
+        output = &output[header + len..];
+    }
+    frames
+}
+
+/// Two ends of a loopback TCP connection.
+pub fn tcp_pair() -> (TcpStream, TcpStream) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let (server, _) = listener.accept().unwrap();
+    (client, server)
+}
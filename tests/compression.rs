@@ -0,0 +1,62 @@
+//! Compression counters.
+
+mod common;
+
+use blitz_ws::protocol::{
+    compression::{CompressionStats, PerMessageDeflate, WebSocketCompressionConfig},
+    message::Message,
+    websocket::{OperationMode, WebSocket},
+};
+
+use common::{server, Stream};
+
+#[test]
+fn no_counters_without_compression() {
+    let mut socket = server(Vec::new(), None);
+    socket.send(Message::new_text("a".repeat(1000))).unwrap();
+
+    assert_eq!(socket.compression_stats(), None);
+    socket.reset_compression_stats();
+    assert_eq!(socket.compression_stats(), None);
+}
+
+#[test]
+fn ratios_divide_compressed_by_uncompressed_sizes() {
+    let mut stats =
+        CompressionStats { sent_uncompressed: 1000, sent_compressed: 250, ..Default::default() };
+    assert_eq!(stats.sent_ratio(), Some(0.25));
+    assert_eq!(stats.received_ratio(), None);
+
+    stats.reset();
+    assert_eq!(stats, CompressionStats::default());
+    assert_eq!(stats.sent_ratio(), None);
+}
+
+#[test]
+fn counters_after_a_compressible_message() {
+    let deflate =
+        || Some(Box::new(PerMessageDeflate::new(WebSocketCompressionConfig::default())) as _);
+    let message = Message::new_text("a".repeat(1000));
+
+    let mut server = server(Vec::new(), None);
+    server.set_extension(deflate());
+    assert_eq!(server.compression_stats(), Some(CompressionStats::default()));
+    server.send(message.clone()).unwrap();
+
+    let sent = server.compression_stats().unwrap();
+    assert_eq!(sent.sent_uncompressed, 1000);
+    assert!(sent.sent_compressed < 100, "{sent:?}");
+    assert_eq!((sent.received_uncompressed, sent.received_compressed), (0, 0));
+
+    let output = server.into_inner().output;
+    let mut client = WebSocket::new(Stream::new(output), OperationMode::Client, None);
+    client.set_extension(deflate());
+    assert_eq!(client.read().unwrap(), message);
+
+    let received = client.compression_stats().unwrap();
+    assert_eq!(received.received_uncompressed, 1000);
+    assert_eq!(received.received_compressed, sent.sent_compressed);
+
+    client.reset_compression_stats();
+    assert_eq!(client.compression_stats(), Some(CompressionStats::default()));
+}
@@ -0,0 +1,18 @@
+//! Changing the configuration of an established connection.
+
+mod common;
+
+use blitz_ws::protocol::config::WebSocketConfig;
+
+use common::server;
+
+#[test]
+fn set_config_returns_the_closure_result() {
+    let config = WebSocketConfig::default().max_message_size(Some(1024));
+    let mut socket = server(Vec::new(), Some(config));
+
+    let previous = socket.set_config(|config| config.max_message_size.replace(4096));
+
+    assert_eq!(previous, Some(1024));
+    assert_eq!(socket.get_config().max_message_size, Some(4096));
+}
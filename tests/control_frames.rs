@@ -0,0 +1,158 @@
+//! Handling of received pings and pongs.
+
+mod common;
+
+use blitz_ws::{
+    error::Error,
+    protocol::{
+        config::WebSocketConfig,
+        message::{ControlEvent, Message},
+    },
+    Bytes,
+};
+
+use common::{client_frames, masked_frame, server, server_frames};
+
+/// A ping with payload "a", a pong with payload "b", then a text message.
+fn input() -> Vec<u8> {
+    let mut input = masked_frame(0x89, b"a");
+    input.extend(masked_frame(0x8A, b"b"));
+    input.extend(client_frames(&[Message::new_text("x")]));
+    input
+}
+
+#[test]
+fn pings_are_answered_when_delivered_as_messages() {
+    let mut socket = server(input(), None);
+
+    assert_eq!(socket.read().unwrap(), Message::Ping(Bytes::from_static(b"a")));
+    assert_eq!(socket.read().unwrap(), Message::Pong(Bytes::from_static(b"b")));
+    assert_eq!(socket.read().unwrap(), Message::new_text("x"));
+    assert!(socket.take_control_events().is_empty());
+
+    assert_eq!(server_frames(&socket.get_ref().output), [(0xA, b"a".to_vec())]);
+}
+
+#[test]
+fn pings_are_answered_when_delivered_as_control_events() {
+    let mut socket = server(input(), Some(WebSocketConfig::default().control_events(true)));
+
+    assert_eq!(socket.read().unwrap(), Message::new_text("x"));
+    assert_eq!(
+        socket.take_control_events(),
+        [
+            ControlEvent::Ping(Bytes::from_static(b"a")),
+            ControlEvent::Pong(Bytes::from_static(b"b"))
+        ]
+    );
+    assert!(socket.take_control_events().is_empty());
+
+    assert_eq!(server_frames(&socket.get_ref().output), [(0xA, b"a".to_vec())]);
+}
+
+/// Read ten pings and a text message while the stream cannot be written to and the write buffer
+/// only has room for one pong, then flush. Returns the payloads of the pongs sent.
+fn ping_flood(max_pending_pongs: usize) -> Vec<Vec<u8>> {
+    let mut input = Vec::new();
+    for i in 0..10u8 {
+        input.extend(masked_frame(0x89, &[i]));
+    }
+    input.extend(client_frames(&[Message::new_text("x")]));
+
+    let config = WebSocketConfig::default()
+        .write_buffer_size(1)
+        .max_write_buffer_size(4)
+        .control_events(true)
+        .max_pending_pongs(max_pending_pongs);
+    let mut socket = server(input, Some(config));
+    socket.get_mut().write_blocked = true;
+
+    assert_eq!(socket.read().unwrap(), Message::new_text("x"));
+    assert_eq!(socket.take_control_events().len(), 10);
+
+    // Each flush writes out the buffered pong and buffers the next queued one.
+    socket.get_mut().write_blocked = false;
+    for _ in 0..=max_pending_pongs {
+        socket.flush().unwrap();
+    }
+
+    server_frames(&socket.get_ref().output)
+        .into_iter()
+        .map(|(opcode, payload)| {
+            assert_eq!(opcode, 0xA);
+            payload
+        })
+        .collect()
+}
+
+#[test]
+fn ping_flood_answers_the_last_ping() {
+    // The reply to the first ping was buffered before the buffer filled up.
+    assert_eq!(ping_flood(1), [[0], [9]]);
+}
+
+#[test]
+fn ping_flood_answers_the_last_four_pings() {
+    assert_eq!(ping_flood(4), [[0], [6], [7], [8], [9]]);
+}
+
+#[test]
+fn passive_mode_writes_no_replies() {
+    let mut input = masked_frame(0x89, b"a");
+    input.extend(masked_frame(0x88, b"\x03\xe8"));
+    let mut socket = server(input, Some(WebSocketConfig::default().passive(true)));
+
+    assert_eq!(socket.read().unwrap(), Message::Ping(Bytes::from_static(b"a")));
+    assert!(matches!(socket.read().unwrap(), Message::Close(Some(_))));
+    assert!(socket.get_ref().output.is_empty());
+
+    // The application replies to the close itself.
+    assert!(matches!(socket.close(None), Err(Error::ConnectionClosed)));
+    assert_eq!(server_frames(&socket.get_ref().output), [(0x8, Vec::new())]);
+}
+
+#[test]
+fn heartbeat_pong_does_not_replace_the_ping_reply() {
+    let mut socket = server(masked_frame(0x89, b"a"), None);
+
+    // The reply to the ping is queued, to be sent with the next write.
+    assert_eq!(socket.read().unwrap(), Message::Ping(Bytes::from_static(b"a")));
+    assert!(socket.get_ref().output.is_empty());
+    socket.send_heartbeat_pong(Bytes::from_static(b"heartbeat")).unwrap();
+
+    let frames = server_frames(&socket.get_ref().output);
+    assert_eq!(frames.len(), 2);
+    assert!(frames.contains(&(0xA, b"a".to_vec())));
+    assert!(frames.contains(&(0xA, b"heartbeat".to_vec())));
+}
+
+/// Read a text message in two fragments with a ping in between, returning the messages read
+/// and the frames written in the meantime.
+fn ping_between_fragments(defer: bool) -> (Vec<Message>, Vec<(u8, Vec<u8>)>) {
+    let mut input = masked_frame(0x01, b"ab");
+    input.extend(masked_frame(0x89, b"hi"));
+    input.extend(masked_frame(0x80, b"cd"));
+    let config = WebSocketConfig::default().defer_control_during_fragment(defer);
+    let mut socket = server(input, Some(config));
+
+    let messages = vec![socket.read().unwrap(), socket.read().unwrap()];
+    socket.flush().unwrap();
+
+    (messages, server_frames(&socket.get_ref().output))
+}
+
+#[test]
+fn ping_between_fragments_is_read_first_by_default() {
+    let (messages, output) = ping_between_fragments(false);
+
+    assert_eq!(messages, [Message::Ping(Bytes::from_static(b"hi")), Message::new_text("abcd")]);
+    assert_eq!(output, [(0xA, b"hi".to_vec())]);
+}
+
+#[test]
+fn ping_between_fragments_is_deferred_after_the_message() {
+    let (messages, output) = ping_between_fragments(true);
+
+    assert_eq!(messages, [Message::new_text("abcd"), Message::Ping(Bytes::from_static(b"hi"))]);
+    assert_eq!(output, [(0xA, b"hi".to_vec())]);
+}
@@ -0,0 +1,74 @@
+//! Round trips between a client and a server over loopback TCP.
+
+mod common;
+
+use std::{
+    sync::{Arc, Mutex},
+    thread::spawn,
+};
+
+use blitz_ws::{
+    error::Error,
+    protocol::{
+        frame::{
+            codec::{Control, Data, OpCode},
+            FrameHeader,
+        },
+        message::Message,
+        websocket::{OperationMode, WebSocket},
+    },
+};
+
+use common::tcp_pair;
+
+type Frames = Arc<Mutex<Vec<(OpCode, usize)>>>;
+
+/// Record the opcode and payload length of every frame `socket` reads or writes.
+fn record_frames<T: std::io::Read + std::io::Write>(socket: &mut WebSocket<T>) -> Frames {
+    let frames = Frames::default();
+    let recorded = frames.clone();
+    socket.on_frame(move |header: &FrameHeader, len| {
+        recorded.lock().unwrap().push((header.opcode, len));
+    });
+    frames
+}
+
+#[test]
+fn on_frame_sees_every_frame_of_an_echo() {
+    let (client, server) = tcp_pair();
+    let mut client = WebSocket::new(client, OperationMode::Client, None);
+    let mut server = WebSocket::new(server, OperationMode::Server, None);
+    let client_frames = record_frames(&mut client);
+    let server_frames = record_frames(&mut server);
+
+    let server = spawn(move || loop {
+        match server.read() {
+            Ok(msg) if msg.is_data() => server.send(msg).unwrap(),
+            Ok(_) => {}
+            Err(Error::ConnectionClosed) => break,
+            Err(e) => panic!("{e}"),
+        }
+    });
+
+    client.send(Message::new_text("hello")).unwrap();
+    client.send(Message::Binary(vec![0; 300].into())).unwrap();
+    assert_eq!(client.read().unwrap(), Message::new_text("hello"));
+    assert!(client.read().unwrap().is_binary());
+    client.close(None).unwrap();
+    assert!(matches!(client.read(), Ok(Message::Close(None))));
+    assert!(matches!(client.read(), Err(Error::ConnectionClosed)));
+    server.join().unwrap();
+
+    let text = OpCode::Data(Data::Text);
+    let binary = OpCode::Data(Data::Binary);
+    let close = OpCode::Control(Control::Close);
+    // Written frames are reported as they are buffered, read frames as they arrive.
+    assert_eq!(
+        *client_frames.lock().unwrap(),
+        [(text, 5), (binary, 300), (text, 5), (binary, 300), (close, 0), (close, 0)]
+    );
+    assert_eq!(
+        *server_frames.lock().unwrap(),
+        [(text, 5), (text, 5), (binary, 300), (binary, 300), (close, 0), (close, 0)]
+    );
+}
@@ -0,0 +1,29 @@
+//! Reading and writing with `FrameSocket`.
+
+use std::io::Cursor;
+
+use blitz_ws::protocol::frame::{
+    codec::{Control, Data, OpCode},
+    core::FrameSocket,
+};
+
+#[test]
+fn read_message_reassembles_three_fragments() {
+    let mut input = Vec::new();
+    input.extend_from_slice(b"\x01\x02ab");
+    input.extend_from_slice(b"\x89\x04ping");
+    input.extend_from_slice(b"\x00\x02cd");
+    input.extend_from_slice(b"\x80\x01e");
+    let mut socket = FrameSocket::new(Cursor::new(input));
+
+    // The ping between the fragments comes first.
+    let (opcode, payload) = socket.read_message(None).unwrap().unwrap();
+    assert_eq!(opcode, OpCode::Control(Control::Ping));
+    assert_eq!(payload, &b"ping"[..]);
+
+    let (opcode, payload) = socket.read_message(None).unwrap().unwrap();
+    assert_eq!(opcode, OpCode::Data(Data::Text));
+    assert_eq!(payload, &b"abcde"[..]);
+
+    assert!(socket.read_message(None).unwrap().is_none());
+}
@@ -0,0 +1,227 @@
+//! Client and server handshakes over a loopback TCP connection.
+
+#![allow(clippy::result_large_err)]
+
+mod common;
+
+use std::{
+    io::{Cursor, Read, Result as IoResult, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::channel,
+    thread::{spawn, JoinHandle},
+};
+
+use blitz_ws::{
+    accept_header, accept_with_config, client,
+    error::{CapacityError, Error},
+    handshake::{
+        client::HandshakeOutcome,
+        core::{derive_accept_key, verify_accept_key},
+        server::{
+            Callback, ErrorResponse, NoCallback, Request, RequestExt, Response, SubprotocolSelector,
+        },
+        HandshakeError,
+    },
+    protocol::{compression::NegotiatedCompression, config::WebSocketConfig, websocket::WebSocket},
+    ClientRequestBuilder,
+};
+
+use common::Stream;
+
+/// Accept one connection on a loopback listener, returning the client end of it and the
+/// server-side handshake result.
+fn connect_pair<C>(callback: C) -> (TcpStream, JoinHandle<WebSocket<TcpStream>>)
+where
+    C: Callback + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+    let server = spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        accept_header(stream, callback).unwrap()
+    });
+
+    (client, server)
+}
+
+#[test]
+fn outcome_matches_response_headers() {
+    let callback = |_: &Request, mut res: Response| -> Result<Response, ErrorResponse> {
+        let headers = res.headers_mut();
+        headers.insert("Sec-WebSocket-Protocol", " chat ".parse().unwrap());
+        headers.insert(
+            "Sec-WebSocket-Extensions",
+            "permessage-deflate; client_no_context_takeover; server_max_window_bits=10"
+                .parse()
+                .unwrap(),
+        );
+        Ok(res)
+    };
+    let (stream, server) = connect_pair(callback);
+
+    let request = ClientRequestBuilder::new("ws://localhost/".parse().unwrap())
+        .with_subprotocol("chat")
+        .with_header("Sec-WebSocket-Extensions", "permessage-deflate; client_no_context_takeover");
+    let (_socket, res) = client(request, stream).unwrap();
+    server.join().unwrap();
+
+    let outcome = HandshakeOutcome::from_response(&res).unwrap();
+    assert_eq!(outcome.subprotocol.as_deref(), Some("chat"));
+    assert_eq!(outcome.extensions, ["permessage-deflate"]);
+    assert_eq!(
+        outcome.compression,
+        Some(NegotiatedCompression {
+            client_no_context_takeover: true,
+            server_max_window_bits: Some(10),
+            ..Default::default()
+        })
+    );
+}
+
+#[test]
+fn outcome_is_empty_without_negotiation() {
+    let (stream, server) = connect_pair(NoCallback);
+
+    let (_socket, res) = client("ws://localhost/", stream).unwrap();
+    server.join().unwrap();
+
+    assert_eq!(HandshakeOutcome::from_response(&res), Some(&HandshakeOutcome::default()));
+}
+
+#[test]
+fn verify_accept_key_accepts_the_derived_key() {
+    // The example from RFC 6455, section 1.3.
+    let expected = derive_accept_key(b"dGhlIHNhbXBsZSBub25jZQ==");
+    assert_eq!(expected, "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    assert!(verify_accept_key(&expected, b"s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+}
+
+#[test]
+fn verify_accept_key_rejects_other_keys() {
+    let expected = derive_accept_key(b"dGhlIHNhbXBsZSBub25jZQ==");
+    assert!(!verify_accept_key(&expected, b"s3pPLMBiTxaQ9kYGzzhZRbK+xOp="));
+    assert!(!verify_accept_key(&expected, b"S3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+    assert!(!verify_accept_key(&expected, b"s3pPLMBiTxaQ9kYGzzhZRbK+xOo"));
+    assert!(!verify_accept_key(&expected, b"s3pPLMBiTxaQ9kYGzzhZRbK+xOo=="));
+    assert!(!verify_accept_key(&expected, b""));
+}
+
+#[test]
+fn subprotocol_selector_picks_an_offered_protocol() {
+    let selector = SubprotocolSelector::new(|offered: &[&str]| {
+        assert_eq!(offered, ["a", "b"]);
+        offered.iter().copied().find(|protocol| *protocol == "b")
+    });
+    let (stream, server) = connect_pair(selector);
+
+    let request = ClientRequestBuilder::new("ws://localhost/".parse().unwrap())
+        .with_subprotocol("a")
+        .with_subprotocol("b");
+    let (_socket, res) = client(request, stream).unwrap();
+    server.join().unwrap();
+
+    assert_eq!(res.headers()["Sec-WebSocket-Protocol"], "b");
+}
+
+#[test]
+fn request_target_keeps_the_query() {
+    let (sender, receiver) = channel();
+    let callback = move |req: &Request, res: Response| -> Result<Response, ErrorResponse> {
+        sender.send(req.request_target().to_string()).unwrap();
+        Ok(res)
+    };
+    let (stream, server) = connect_pair(callback);
+
+    client("ws://localhost/chat?room=123&name=a%20b", stream).unwrap();
+    server.join().unwrap();
+
+    assert_eq!(receiver.recv().unwrap(), "/chat?room=123&name=a%20b");
+}
+
+/// A stream that delivers its input one byte per read, as a peer trickling a request would.
+struct Trickle {
+    input: Cursor<Vec<u8>>,
+    output: Vec<u8>,
+}
+
+impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        let len = buf.len().min(1);
+        self.input.read(&mut buf[..len])
+    }
+}
+
+impl Write for Trickle {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.output.write(buf)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+/// A 600 byte handshake request, read in 600 packets.
+fn trickled_request() -> Trickle {
+    let mut request = String::from(
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+        Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n",
+    );
+    let padding = 600 - request.len() - "X-Padding: \r\n\r\n".len();
+    request.push_str(&format!("X-Padding: {}\r\n\r\n", "a".repeat(padding)));
+    assert_eq!(request.len(), 600);
+
+    Trickle { input: Cursor::new(request.into_bytes()), output: Vec::new() }
+}
+
+#[test]
+fn trickled_handshake_is_rejected_by_default() {
+    match accept_with_config(trickled_request(), None) {
+        Err(HandshakeError::Failure(Error::AttackAttempt)) => {}
+        Err(err) => panic!("unexpected error: {err}"),
+        Ok(_) => panic!("the handshake succeeded"),
+    }
+}
+
+#[test]
+fn trickled_handshake_is_accepted_with_raised_limits() {
+    let config =
+        WebSocketConfig::default().handshake_max_packets(1000).handshake_min_packet_size(0);
+
+    let socket = accept_with_config(trickled_request(), Some(config)).unwrap();
+    let response = String::from_utf8(socket.get_ref().output.clone()).unwrap();
+    assert!(response.starts_with("HTTP/1.1 101"), "{response}");
+    assert!(response.contains("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="), "{response}");
+}
+
+/// A handshake request with 200 extra headers.
+fn request_with_200_headers() -> Stream {
+    let mut request = String::from(
+        "GET / HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+        Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n",
+    );
+    for i in 0..200 {
+        request.push_str(&format!("X-Forwarded-{i}: proxy\r\n"));
+    }
+    request.push_str("\r\n");
+
+    Stream::new(request.into_bytes())
+}
+
+#[test]
+fn too_many_headers_are_rejected_by_default() {
+    match accept_with_config(request_with_200_headers(), None) {
+        Err(HandshakeError::Failure(Error::Capacity(CapacityError::TooManyHeaders))) => {}
+        Err(err) => panic!("unexpected error: {err}"),
+        Ok(_) => panic!("the handshake succeeded"),
+    }
+}
+
+#[test]
+fn max_headers_allows_more_headers() {
+    let config = WebSocketConfig::default().max_headers(256);
+
+    let socket = accept_with_config(request_with_200_headers(), Some(config)).unwrap();
+    let response = String::from_utf8(socket.get_ref().output.clone()).unwrap();
+    assert!(response.starts_with("HTTP/1.1 101"), "{response}");
+}
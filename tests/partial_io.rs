@@ -0,0 +1,157 @@
+//! Partial reads and writes through the codec, scripted with `ThrottledStream`.
+
+use std::io::{Cursor, ErrorKind};
+
+use blitz_ws::{
+    error::Error,
+    protocol::{
+        message::Message,
+        websocket::{OperationMode, WebSocket},
+    },
+    test_util::ThrottledStream,
+};
+
+fn is_would_block(err: &Error) -> bool {
+    matches!(err, Error::Io(e) if e.kind() == ErrorKind::WouldBlock)
+}
+
+/// Frames written by a client socket, to be read by a server.
+fn client_frames(messages: &[Message]) -> Vec<u8> {
+    let mut client = WebSocket::new(Cursor::new(Vec::new()), OperationMode::Client, None);
+    for msg in messages {
+        client.send(msg.clone()).unwrap();
+    }
+    client.into_inner().into_inner()
+}
+
+#[test]
+fn flush_drains_one_byte_at_a_time() {
+    let msg = Message::Binary(vec![7; 1000].into());
+
+    let mut expected = WebSocket::new(Cursor::new(Vec::new()), OperationMode::Server, None);
+    expected.send(msg.clone()).unwrap();
+    let expected = expected.into_inner().into_inner();
+
+    let stream = ThrottledStream::builder().max_write(1).build(Cursor::new(Vec::new()));
+    let mut socket = WebSocket::new(stream, OperationMode::Server, None);
+    socket.send(msg).unwrap();
+
+    assert_eq!(socket.into_inner().into_inner().into_inner(), expected);
+}
+
+#[test]
+fn flush_resumes_after_would_block() {
+    let stream = ThrottledStream::builder()
+        .write_limit(3)
+        .write_would_block()
+        .write_limit(5)
+        .write_would_block()
+        .build(Cursor::new(Vec::new()));
+    let mut socket = WebSocket::new(stream, OperationMode::Server, None);
+
+    let mut result = socket.send(Message::new_text("hello, partial world"));
+    let mut blocked = 0;
+    while let Err(err) = result {
+        assert!(is_would_block(&err), "{err}");
+        blocked += 1;
+        result = socket.flush();
+    }
+
+    assert_eq!(blocked, 2);
+    let output = socket.into_inner().into_inner().into_inner();
+    assert_eq!(&output[..2], &[0x81, 20]);
+    assert_eq!(&output[2..], b"hello, partial world");
+}
+
+#[test]
+fn read_resumes_after_would_block() {
+    let input = client_frames(&[Message::new_text("first"), Message::Binary(vec![1; 300].into())]);
+    let stream = ThrottledStream::builder()
+        .read_limit(1)
+        .read_would_block()
+        .read_limit(4)
+        .read_would_block()
+        .read_limit(100)
+        .read_would_block()
+        .max_read(2)
+        .build(Cursor::new(input));
+    let mut socket = WebSocket::new(stream, OperationMode::Server, None);
+
+    let mut messages = Vec::new();
+    let mut blocked = 0;
+    while messages.len() < 2 {
+        match socket.read() {
+            Ok(msg) => messages.push(msg),
+            Err(err) if is_would_block(&err) => blocked += 1,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    assert_eq!(blocked, 3);
+    assert_eq!(messages, [Message::new_text("first"), Message::Binary(vec![1; 300].into())]);
+    assert!(socket.get_ref().is_script_done());
+}
+
+#[test]
+fn try_read_returns_none_partway_through_a_frame() {
+    let input = client_frames(&[Message::Binary(vec![3; 200].into())]);
+    let stream = ThrottledStream::builder()
+        .read_limit(1)
+        .read_would_block()
+        .read_limit(50)
+        .read_would_block()
+        .build(Cursor::new(input));
+    let mut socket = WebSocket::new(stream, OperationMode::Server, None);
+
+    // In the middle of the header, then in the middle of the payload.
+    assert_eq!(socket.try_read().unwrap(), None);
+    assert_eq!(socket.try_read().unwrap(), None);
+    assert_eq!(socket.try_read().unwrap(), Some(Message::Binary(vec![3; 200].into())));
+    assert!(socket.get_ref().is_script_done());
+}
+
+#[test]
+fn try_flush_returns_false_until_flushed() {
+    let stream = ThrottledStream::builder()
+        .write_limit(3)
+        .write_would_block()
+        .build(Cursor::new(Vec::new()));
+    let mut socket = WebSocket::new(stream, OperationMode::Server, None);
+
+    socket.write(Message::new_text("hello")).unwrap();
+    assert!(!socket.try_flush().unwrap());
+    assert!(socket.try_flush().unwrap());
+
+    let output = socket.into_inner().into_inner().into_inner();
+    assert_eq!(output, [&[0x81, 5][..], b"hello"].concat());
+}
+
+#[test]
+fn into_parts_resumes_with_from_partially_read() {
+    let second = Message::Binary(vec![2; 300].into());
+    let input = client_frames(&[Message::new_text("first"), second.clone()]);
+
+    // The first frame takes 11 bytes, the second one has an 8 byte header.
+    for split in [11, 14, 19, 119, input.len() - 1] {
+        let stream = ThrottledStream::builder()
+            .read_limit(split)
+            .read_would_block()
+            .build(Cursor::new(input.clone()));
+        let mut socket = WebSocket::new(stream, OperationMode::Server, None);
+
+        assert_eq!(socket.read().unwrap(), Message::new_text("first"));
+        let err = socket.read().unwrap_err();
+        assert!(is_would_block(&err), "{err}");
+
+        let (stream, unparsed) = socket.into_parts();
+        assert_eq!(unparsed, &input[11..split], "split at {split}");
+
+        let mut resumed = WebSocket::from_partially_read(
+            stream.into_inner(),
+            unparsed,
+            OperationMode::Server,
+            None,
+        );
+        assert_eq!(resumed.read().unwrap(), second, "split at {split}");
+    }
+}
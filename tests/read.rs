@@ -0,0 +1,220 @@
+//! Reading messages.
+
+mod common;
+
+use std::{
+    io::{Cursor, ErrorKind, Read, Result as IoResult, Write},
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+
+use blitz_ws::{
+    error::{Error, ProtocolError},
+    protocol::{
+        config::WebSocketConfig,
+        message::Message,
+        websocket::{OperationMode, WebSocket},
+    },
+};
+
+use common::{client_frames, masked_frame, server, tcp_pair};
+
+#[test]
+fn read_deadline_bounds_a_message_spread_over_frames() {
+    let (mut client, server) = tcp_pair();
+    let mut socket = WebSocket::new(server, OperationMode::Server, None);
+
+    // "abcd" in four fragments, 100 ms apart: each frame is well within the deadline, but the
+    // message as a whole is not.
+    let peer = spawn(move || {
+        for frame in [
+            masked_frame(0x01, b"a"),
+            masked_frame(0x00, b"b"),
+            masked_frame(0x00, b"c"),
+            masked_frame(0x80, b"d"),
+        ] {
+            client.write_all(&frame).unwrap();
+            sleep(Duration::from_millis(100));
+        }
+        client
+    });
+
+    let start = Instant::now();
+    let err = socket.read_deadline(start + Duration::from_millis(150)).unwrap_err();
+    let elapsed = start.elapsed();
+    assert!(matches!(err, Error::Io(e) if e.kind() == ErrorKind::TimedOut));
+    assert!(elapsed >= Duration::from_millis(150), "{elapsed:?}");
+    assert!(elapsed < Duration::from_millis(300), "{elapsed:?}");
+
+    // The received fragments are kept and the next read completes the message.
+    let message = socket.read_deadline(Instant::now() + Duration::from_secs(5)).unwrap();
+    assert_eq!(message, Message::new_text("abcd"));
+    assert_eq!(socket.get_ref().read_timeout().unwrap(), None);
+
+    drop(peer.join().unwrap());
+}
+
+#[test]
+fn read_with_size_of_a_single_frame_message() {
+    let mut socket = server(client_frames(&[Message::new_text("hello")]), None);
+
+    // 2 header bytes, 4 mask bytes and the payload.
+    assert_eq!(socket.read_with_size().unwrap(), (Message::new_text("hello"), 2 + 4 + 5));
+}
+
+#[test]
+fn read_with_size_of_a_fragmented_message() {
+    let mut input = masked_frame(0x01, b"ab");
+    input.extend(masked_frame(0x89, b"ping"));
+    input.extend(masked_frame(0x00, b"cd"));
+    input.extend(masked_frame(0x80, b"e"));
+    let mut socket = server(input, None);
+
+    // The ping in between is reported on its own.
+    let (ping, size) = socket.read_with_size().unwrap();
+    assert!(matches!(ping, Message::Ping(_)));
+    assert_eq!(size, 6 + 4);
+
+    assert_eq!(socket.read_with_size().unwrap(), (Message::new_text("abcde"), 8 + 8 + 7));
+}
+
+/// Records the size of the buffer passed to every read.
+struct ReadSizes {
+    input: Cursor<Vec<u8>>,
+    sizes: Vec<usize>,
+}
+
+impl Read for ReadSizes {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        self.sizes.push(buf.len());
+        self.input.read(buf)
+    }
+}
+
+impl Write for ReadSizes {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn set_config_changes_the_read_chunk_size() {
+    let large = Message::Binary(vec![1; 20_000].into());
+    let input = client_frames(&[large.clone(), large.clone()]);
+    let stream = ReadSizes { input: Cursor::new(input), sizes: Vec::new() };
+    let config = WebSocketConfig::default().read_buffer_size(1024);
+    let mut socket = WebSocket::new(stream, OperationMode::Server, Some(config));
+
+    assert_eq!(socket.read().unwrap(), large);
+    let sizes = std::mem::take(&mut socket.get_mut().sizes);
+    assert!(sizes.iter().all(|&size| size <= 1024), "{sizes:?}");
+
+    socket.set_config(|config| config.read_buffer_size = 8192);
+    assert_eq!(socket.read().unwrap(), large);
+    let sizes = &socket.get_ref().sizes;
+    assert!(sizes.iter().all(|&size| size <= 8192), "{sizes:?}");
+    assert!(sizes.iter().any(|&size| size > 1024), "{sizes:?}");
+}
+
+fn read_truncated(len: usize) -> Error {
+    let mut input =
+        client_frames(&[Message::new_text("first"), Message::Binary(vec![1; 300].into())]);
+    input.truncate(len);
+
+    let mut socket = server(input, None);
+    assert_eq!(socket.read().unwrap(), Message::new_text("first"));
+    socket.read().unwrap_err()
+}
+
+#[test]
+fn eof_between_frames_is_a_reset() {
+    let err = read_truncated(11);
+    assert!(matches!(err, Error::Protocol(ProtocolError::ResetWithoutClosing)), "{err}");
+}
+
+#[test]
+fn eof_in_a_frame_header_is_a_truncated_frame() {
+    let err = read_truncated(14);
+    assert!(matches!(err, Error::Protocol(ProtocolError::TruncatedFrame)), "{err}");
+}
+
+#[test]
+fn eof_in_a_frame_payload_is_a_truncated_frame() {
+    let err = read_truncated(119);
+    assert!(matches!(err, Error::Protocol(ProtocolError::TruncatedFrame)), "{err}");
+}
+
+#[test]
+fn single_frame_messages_share_the_read_buffer() {
+    let (first, second) = (vec![1; 4096], vec![2; 4096]);
+    let input = client_frames(&[
+        Message::Binary(first.clone().into()),
+        Message::Binary(second.clone().into()),
+    ]);
+    let mut socket = server(input, None);
+
+    let (first_read, second_read) = match (socket.read().unwrap(), socket.read().unwrap()) {
+        (Message::Binary(first), Message::Binary(second)) => (first, second),
+        other => panic!("unexpected messages: {other:?}"),
+    };
+    assert_eq!((&first_read[..], &second_read[..]), (&first[..], &second[..]));
+
+    // Both payloads stay where they were read, one 8 byte frame header apart.
+    let distance = second_read.as_ptr() as usize - first_read.as_ptr() as usize;
+    assert_eq!(distance, 4096 + 8);
+}
+
+/// Delivers its input one byte per read, 10 ms apart.
+struct Drip {
+    input: Cursor<Vec<u8>>,
+}
+
+impl Read for Drip {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        sleep(Duration::from_millis(10));
+        let len = buf.len().min(1);
+        self.input.read(&mut buf[..len])
+    }
+}
+
+impl Write for Drip {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn read_timeout_bounds_a_dripping_frame() {
+    // 17 bytes on the wire, taking 170 ms to arrive.
+    let input = client_frames(&[Message::new_text("hello world")]);
+    let mut socket =
+        WebSocket::new(Drip { input: Cursor::new(input) }, OperationMode::Server, None);
+    socket.read_timeout(Some(Duration::from_millis(50)));
+
+    let start = Instant::now();
+    let err = socket.read().unwrap_err();
+    let elapsed = start.elapsed();
+    assert!(matches!(err, Error::Io(ref e) if e.kind() == ErrorKind::TimedOut), "{err}");
+    assert!(elapsed >= Duration::from_millis(50), "{elapsed:?}");
+    assert!(elapsed < Duration::from_millis(150), "{elapsed:?}");
+
+    // Every read gets another 50 ms and resumes where the previous one stopped.
+    let mut timeouts = 1;
+    let message = loop {
+        match socket.read() {
+            Ok(message) => break message,
+            Err(Error::Io(e)) if e.kind() == ErrorKind::TimedOut => timeouts += 1,
+            Err(err) => panic!("{err}"),
+        }
+    };
+    assert_eq!(message, Message::new_text("hello world"));
+    assert!((2..=5).contains(&timeouts), "{timeouts}");
+}
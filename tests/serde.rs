@@ -0,0 +1,23 @@
+//! Loading `WebSocketConfig` from JSON.
+
+use blitz_ws::protocol::config::WebSocketConfig;
+
+#[test]
+fn config_round_trips_through_json() {
+    let config = WebSocketConfig::default()
+        .max_message_size(Some(1024))
+        .max_headers(32)
+        .max_messages(Some(100));
+
+    let json = serde_json::to_string(&config).unwrap();
+    let parsed: WebSocketConfig = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(parsed, config);
+}
+
+#[test]
+fn missing_fields_take_their_defaults() {
+    let parsed: WebSocketConfig = serde_json::from_str(r#"{ "max_frame_size": 4096 }"#).unwrap();
+
+    assert_eq!(parsed, WebSocketConfig::default().max_frame_size(Some(4096)));
+}
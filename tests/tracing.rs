@@ -0,0 +1,26 @@
+//! Tracing instrumentation.
+
+mod common;
+
+use std::thread::spawn;
+
+use blitz_ws::{accept, client};
+use tracing_test::traced_test;
+
+use common::tcp_pair;
+
+#[test]
+#[traced_test]
+fn handshake_span_is_emitted() {
+    let (client_stream, server_stream) = tcp_pair();
+    // Run the server in the span of the test, so that its logs are captured too.
+    let span = tracing::Span::current();
+    let server = spawn(move || span.in_scope(|| accept(server_stream).unwrap()));
+
+    client("ws://localhost/", client_stream).unwrap();
+    server.join().unwrap();
+
+    assert!(logs_contain("handshake{role=\"client\"}"));
+    assert!(logs_contain("handshake{role=\"server\"}"));
+    assert!(logs_contain("handshake complete"));
+}
@@ -0,0 +1,64 @@
+//! The browser client, against a running `echo_server` example.
+//!
+//! Run with `wasm-pack test --headless --firefox -- --features wasm`, the URL of the echo server
+//! can be changed with the `BLITZ_ECHO_URL` environment variable at build time.
+
+#![cfg(target_arch = "wasm32")]
+
+use blitz_ws::{error::Error, protocol::message::Message, wasm::BrowserWebSocket};
+use js_sys::{Function, Promise, Reflect};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+use wasm_bindgen_test::{wasm_bindgen_test, wasm_bindgen_test_configure};
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const ECHO_URL: &str = match option_env!("BLITZ_ECHO_URL") {
+    Some(url) => url,
+    None => "ws://localhost:8080/socket",
+};
+
+/// Give the browser a turn of its event loop to deliver events.
+async fn yield_to_browser() {
+    let promise = Promise::new(&mut |resolve, _| {
+        let set_timeout: Function =
+            Reflect::get(&js_sys::global(), &"setTimeout".into()).unwrap().into();
+        set_timeout.call2(&JsValue::NULL, &resolve, &10.into()).unwrap();
+    });
+    JsFuture::from(promise).await.unwrap();
+}
+
+async fn next_message(socket: &mut BrowserWebSocket) -> Result<Message, Error> {
+    loop {
+        if let Some(msg) = socket.read()? {
+            return Ok(msg);
+        }
+        yield_to_browser().await;
+    }
+}
+
+#[wasm_bindgen_test]
+async fn echo() {
+    let mut socket = BrowserWebSocket::connect(ECHO_URL).unwrap();
+
+    // Both are queued until the connection is open.
+    socket.send(Message::Text("Hello".into())).unwrap();
+    socket.send(Message::Binary(vec![1, 2, 3].into())).unwrap();
+
+    assert_eq!(next_message(&mut socket).await.unwrap(), Message::Text("Hello".into()));
+    assert_eq!(next_message(&mut socket).await.unwrap(), Message::Binary(vec![1, 2, 3].into()));
+
+    socket.close(None).unwrap();
+    assert!(matches!(next_message(&mut socket).await.unwrap(), Message::Close(_)));
+    assert!(matches!(next_message(&mut socket).await, Err(Error::ConnectionClosed)));
+}
+
+#[wasm_bindgen_test]
+async fn close_while_connecting_drops_queued_messages() {
+    let mut socket = BrowserWebSocket::connect(ECHO_URL).unwrap();
+    socket.send(Message::Text("Never sent".into())).unwrap();
+    socket.close(None).unwrap();
+
+    assert!(matches!(next_message(&mut socket).await.unwrap(), Message::Close(_)));
+    assert!(matches!(next_message(&mut socket).await, Err(Error::ConnectionClosed)));
+}
@@ -0,0 +1,279 @@
+//! Writing messages.
+
+mod common;
+
+use std::io::{Cursor, ErrorKind, IoSlice, Read, Result as IoResult, Write};
+
+use blitz_ws::{
+    error::{CapacityError, Error, ProtocolError},
+    protocol::{
+        config::WebSocketConfig,
+        frame::{
+            codec::{Data, OpCode},
+            Frame,
+        },
+        message::Message,
+        websocket::{OperationMode, WebSocket},
+    },
+    Bytes,
+};
+
+use common::{server_frames, Stream};
+
+/// A stream accepting at most `max` bytes per call, collecting everything written.
+///
+/// With `vectored` unset it keeps the default `write_vectored`, which only writes the first
+/// non-empty slice.
+struct PartialWriter {
+    output: Vec<u8>,
+    max: usize,
+    vectored: bool,
+    writes: usize,
+    flushes: usize,
+}
+
+impl PartialWriter {
+    fn new(max: usize, vectored: bool) -> Self {
+        Self { output: Vec::new(), max, vectored, writes: 0, flushes: 0 }
+    }
+}
+
+impl Read for PartialWriter {
+    fn read(&mut self, _: &mut [u8]) -> IoResult<usize> {
+        Ok(0)
+    }
+}
+
+impl Write for PartialWriter {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.writes += 1;
+        let len = buf.len().min(self.max);
+        self.output.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> IoResult<usize> {
+        if !self.vectored {
+            let buf = bufs.iter().find(|buf| !buf.is_empty()).map_or(&[][..], |buf| &buf[..]);
+            return self.write(buf);
+        }
+
+        self.writes += 1;
+        let mut written = 0;
+        for buf in bufs {
+            let len = buf.len().min(self.max - written);
+            self.output.extend_from_slice(&buf[..len]);
+            written += len;
+            if written == self.max {
+                break;
+            }
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        self.flushes += 1;
+        Ok(())
+    }
+}
+
+/// The payloads of a 1 MiB binary message surrounded by small text messages.
+fn payloads() -> Vec<(OpCode, Bytes)> {
+    let large: Vec<u8> = (0..1 << 20).map(|i| i as u8).collect();
+    vec![
+        (OpCode::Data(Data::Text), Bytes::from_static(b"before")),
+        (OpCode::Data(Data::Binary), large.into()),
+        (OpCode::Data(Data::Text), Bytes::from_static(b"after")),
+    ]
+}
+
+/// The frames formatted one by one into a single buffer.
+fn copied() -> Vec<u8> {
+    let mut output = Vec::new();
+    for (opcode, payload) in payloads() {
+        Frame::new_data(payload, opcode, true).format_to_buf(&mut output).unwrap();
+    }
+    output
+}
+
+fn send_through(writer: PartialWriter) -> Vec<u8> {
+    let mut socket = WebSocket::new(writer, OperationMode::Server, None);
+    for (opcode, payload) in payloads() {
+        let msg = match opcode {
+            OpCode::Data(Data::Text) => Message::Text(payload.try_into().unwrap()),
+            _ => Message::Binary(payload),
+        };
+        socket.write(msg).unwrap();
+    }
+    socket.flush().unwrap();
+    socket.into_inner().output
+}
+
+#[test]
+fn large_unmasked_message_is_written_unchanged() {
+    let writer = PartialWriter::new(usize::MAX, true);
+    assert!(send_through(writer) == copied());
+}
+
+#[test]
+fn large_unmasked_message_survives_partial_vectored_writes() {
+    for max in [1, 7, 4096, 100_000] {
+        let writer = PartialWriter::new(max, true);
+        assert!(send_through(writer) == copied(), "max {max}");
+    }
+}
+
+#[test]
+fn large_unmasked_message_survives_single_slice_writes() {
+    for max in [1000, usize::MAX] {
+        let writer = PartialWriter::new(max, false);
+        assert!(send_through(writer) == copied(), "max {max}");
+    }
+}
+
+#[test]
+fn write_batch_flushes_once() {
+    let mut socket =
+        WebSocket::new(PartialWriter::new(usize::MAX, true), OperationMode::Server, None);
+
+    socket.write_batch((0..100).map(|i| Message::new_text(i.to_string()))).unwrap();
+
+    let writer = socket.get_ref();
+    assert_eq!(writer.flushes, 1);
+    assert_eq!(writer.writes, 1);
+
+    let frames = server_frames(&writer.output);
+    assert_eq!(frames.len(), 100);
+    assert!(frames
+        .iter()
+        .enumerate()
+        .all(|(i, frame)| *frame == (0x1, i.to_string().into_bytes())));
+}
+
+#[test]
+fn write_buffer_len_grows_until_flushed() {
+    let config = WebSocketConfig::default().max_write_buffer_size(1 << 20);
+    let mut socket =
+        WebSocket::new(PartialWriter::new(usize::MAX, true), OperationMode::Server, Some(config));
+    assert_eq!(socket.write_buffer_len(), 0);
+    assert_eq!(socket.write_buffer_capacity(), 1 << 20);
+
+    // Frames of 2 header bytes and the payload.
+    socket.write(Message::new_text("abc")).unwrap();
+    assert_eq!(socket.write_buffer_len(), 5);
+    socket.write(Message::Binary(vec![0; 100].into())).unwrap();
+    assert_eq!(socket.write_buffer_len(), 107);
+    assert!(socket.get_ref().output.is_empty());
+
+    socket.flush().unwrap();
+    assert_eq!(socket.write_buffer_len(), 0);
+    assert_eq!(socket.get_ref().output.len(), 107);
+}
+
+#[test]
+fn message_larger_than_max_frame_size_is_fragmented() {
+    let config = WebSocketConfig::default().max_frame_size(Some(100));
+    let mut server = WebSocket::new(Stream::default(), OperationMode::Server, Some(config));
+    let payload: Vec<u8> = (0..1050).map(|i| i as u8).collect();
+    server.send(Message::Binary(payload.clone().into())).unwrap();
+
+    let output = server.into_inner().output;
+    let frames = server_frames(&output);
+    assert_eq!(frames.len(), 11);
+    assert_eq!(frames[0].0, 0x2);
+    assert!(frames[1..].iter().all(|(opcode, _)| *opcode == 0x0));
+    assert!(frames[..10].iter().all(|(_, payload)| payload.len() == 100));
+    assert_eq!(frames[10].1.len(), 50);
+
+    let mut client = WebSocket::new(Stream::new(output), OperationMode::Client, None);
+    assert_eq!(client.read().unwrap(), Message::Binary(payload.into()));
+}
+
+#[test]
+fn message_larger_than_max_message_size_is_rejected() {
+    let config = WebSocketConfig::default().max_message_size(Some(100));
+    let mut server = WebSocket::new(Stream::default(), OperationMode::Server, Some(config));
+
+    let err = server.send(Message::Binary(vec![0; 101].into())).unwrap_err();
+    assert!(matches!(err, Error::Capacity(CapacityError::MessageTooLarge { size: 101, max: 100 })));
+    assert!(server.get_ref().output.is_empty());
+
+    server.send(Message::Binary(vec![0; 100].into())).unwrap();
+    assert_eq!(server.get_ref().output.len(), 102);
+}
+
+#[test]
+fn send_from_reader_streams_a_large_message() {
+    let payload: Vec<u8> = (0..1024 * 1024).map(|i| i as u8).collect();
+    let mut server = WebSocket::new(Stream::default(), OperationMode::Server, None);
+
+    let size = server.send_from_reader(Cursor::new(payload.clone()), Data::Binary).unwrap();
+    assert_eq!(size, 1024 * 1024);
+
+    // Fragments of the default write buffer size, 128 KiB.
+    let output = server.into_inner().output;
+    let frames = server_frames(&output);
+    assert_eq!(frames.len(), 8);
+    assert!(frames.iter().all(|(_, payload)| payload.len() == 128 * 1024));
+
+    let mut client = WebSocket::new(Stream::new(output), OperationMode::Client, None);
+    assert_eq!(client.read().unwrap(), Message::Binary(payload.into()));
+}
+
+/// A stream accepting `limit` bytes in total, then failing.
+struct FailingWriter {
+    output: Vec<u8>,
+    limit: usize,
+}
+
+impl Read for FailingWriter {
+    fn read(&mut self, _: &mut [u8]) -> IoResult<usize> {
+        Ok(0)
+    }
+}
+
+impl Write for FailingWriter {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let len = buf.len().min(self.limit - self.output.len());
+        if len == 0 {
+            return Err(ErrorKind::BrokenPipe.into());
+        }
+        self.output.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> IoResult<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn send_from_reader_closes_when_the_stream_fails_mid_message() {
+    let config = WebSocketConfig::default().write_buffer_size(1000);
+    let stream = FailingWriter { output: Vec::new(), limit: 2500 };
+    let mut server = WebSocket::new(stream, OperationMode::Server, Some(config));
+
+    let err = server.send_from_reader(Cursor::new(vec![7; 10_000]), Data::Binary).unwrap_err();
+    assert!(matches!(err, Error::Io(ref e) if e.kind() == ErrorKind::BrokenPipe), "{err}");
+
+    // Nothing may follow the fragments already sent.
+    assert!(!server.can_write());
+    let err = server.send(Message::new_text("hello")).unwrap_err();
+    assert!(matches!(err, Error::Protocol(ProtocolError::SendAfterClose)), "{err}");
+}
+
+#[test]
+fn send_from_reader_keeps_the_connection_if_nothing_was_sent() {
+    let config = WebSocketConfig::default().write_buffer_size(1000);
+    let stream = FailingWriter { output: Vec::new(), limit: 0 };
+    let mut server = WebSocket::new(stream, OperationMode::Server, Some(config));
+
+    // The message fits into one frame, which is buffered whole.
+    let err = server.send_from_reader(Cursor::new(vec![7; 500]), Data::Binary).unwrap_err();
+    assert!(matches!(err, Error::Io(ref e) if e.kind() == ErrorKind::BrokenPipe), "{err}");
+
+    assert!(server.can_write());
+    server.get_mut().limit = 504;
+    server.flush().unwrap();
+    assert_eq!(server.get_ref().output.len(), 504);
+}